@@ -0,0 +1,37 @@
+//! Detects whether the target PHP is a thread-safe (ZTS) build, the same way
+//! ext-php-rs does, and exposes it as `cfg(php_zts)` to this crate.
+
+use std::env;
+use std::process::Command;
+
+fn main() {
+    println!("cargo::rustc-check-cfg=cfg(php_zts)");
+    println!("cargo:rerun-if-env-changed=PHP");
+    println!("cargo:rerun-if-env-changed=PATH");
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // docs.rs and other binding-less builds compile against the NTS stubs.
+    if env::var_os("DOCS_RS").is_some() {
+        return;
+    }
+
+    // ext-php-rs fails the build without a usable `php` too, so there is no
+    // build for a guessed mode to fall back to.
+    let php = env::var_os("PHP").unwrap_or_else(|| "php".into());
+    let output = match Command::new(&php).arg("-i").output() {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => panic!("`{} -i` failed with {}", php.to_string_lossy(), output.status),
+        Err(err) => panic!("could not run `{} -i` to detect thread safety: {}", php.to_string_lossy(), err),
+    };
+
+    let info = String::from_utf8_lossy(&output.stdout);
+    let zts = info
+        .lines()
+        .filter_map(|line| line.split_once("=>"))
+        .find(|(key, _)| key.trim() == "Thread Safety")
+        .map(|(_, value)| value.trim() == "enabled")
+        .unwrap_or_else(|| panic!("`{} -i` does not report Thread Safety", php.to_string_lossy()));
+    if zts {
+        println!("cargo:rustc-cfg=php_zts");
+    }
+}
@@ -0,0 +1,56 @@
+//! Custom JSON representations for classes the caller does not own, see
+//! `Codec::registerAdapter()`. Adapters hold PHP callables and therefore
+//! last for the current request only.
+
+use ext_php_rs::zend::ClassEntry;
+
+use crate::globals;
+use crate::options::Callback;
+
+#[derive(Clone)]
+pub(crate) struct Adapter {
+    /// The class name as registered.
+    class: String,
+    serialize: Callback,
+    deserialize: Option<Callback>,
+}
+
+/// Adds an adapter for `class`, replacing any registered for the same name.
+pub(crate) fn register(class: &str, serialize: Callback, deserialize: Option<Callback>) {
+    let class = class.trim_start_matches('\\').to_string();
+    let (_replaced, mut kept): (Vec<_>, Vec<_>) = globals::replace(|g| &mut g.adapters, Vec::new())
+        .into_iter()
+        .partition(|adapter| adapter.class.eq_ignore_ascii_case(&class));
+    kept.push(Adapter {
+        class,
+        serialize,
+        deserialize,
+    });
+    globals::replace(|g| &mut g.adapters, kept);
+}
+
+pub(crate) fn reset() {
+    globals::replace(|g| &mut g.adapters, Vec::new());
+}
+
+/// The serializers to consult on encode, in registration order, with
+/// their classes resolved. Classes that no longer resolve are left out.
+pub(crate) fn serializers() -> Vec<(&'static ClassEntry, Callback)> {
+    // Resolving a class may autoload it, which must not happen inside
+    // `globals::with()`.
+    let adapters = globals::with(|g| g.adapters.clone());
+    adapters
+        .into_iter()
+        .filter_map(|adapter| Some((ClassEntry::try_find(&adapter.class)?, adapter.serialize)))
+        .collect()
+}
+
+/// The deserializer registered for exactly `class`, if any.
+pub(crate) fn deserializer(class: &str) -> Option<Callback> {
+    globals::with(|g| {
+        g.adapters
+            .iter()
+            .find(|adapter| adapter.class.eq_ignore_ascii_case(class.trim_start_matches('\\')))
+            .and_then(|adapter| adapter.deserialize.clone())
+    })
+}
@@ -0,0 +1,73 @@
+//! Unpadded base64url (RFC 4648 §5), as used by JOSE/JWT segments, and
+//! the padded standard alphabet (§4) on top of it.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().fold(0u32, |n, b| n << 8 | u32::from(*b)) << (8 * (3 - chunk.len()));
+        for i in 0..=chunk.len() {
+            out.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 0x3f] as char);
+        }
+    }
+    out
+}
+
+/// Rejects padding, characters outside the alphabet and non-canonical
+/// trailing bits, as RFC 7515 requires.
+pub(crate) fn decode(s: &str) -> Result<Vec<u8>, String> {
+    let input = s.as_bytes();
+    if input.len() % 4 == 1 {
+        return Err("Invalid base64url length".to_owned());
+    }
+
+    let mut out = Vec::with_capacity(input.len() / 4 * 3 + 2);
+    for (index, chunk) in input.chunks(4).enumerate() {
+        let mut n = 0u32;
+        for (i, c) in chunk.iter().enumerate() {
+            let value = match c {
+                b'A'..=b'Z' => c - b'A',
+                b'a'..=b'z' => c - b'a' + 26,
+                b'0'..=b'9' => c - b'0' + 52,
+                b'-' => 62,
+                b'_' => 63,
+                _ => return Err(format!("Invalid base64url character at offset {}", index * 4 + i)),
+            };
+            n = n << 6 | u32::from(value);
+        }
+        n <<= 6 * (4 - chunk.len());
+
+        let bytes = chunk.len() - 1;
+        if bytes < 3 && n & (0xffffff >> (8 * bytes)) != 0 {
+            return Err("Non-canonical base64url trailing bits".to_owned());
+        }
+        out.extend_from_slice(&n.to_be_bytes()[1..=bytes]);
+    }
+    Ok(out)
+}
+
+/// Padded base64 with the standard alphabet.
+pub(crate) fn encode_standard(bytes: &[u8]) -> String {
+    let mut out: String = encode(bytes)
+        .chars()
+        .map(|c| match c {
+            '-' => '+',
+            '_' => '/',
+            c => c,
+        })
+        .collect();
+    while !out.len().is_multiple_of(4) {
+        out.push('=');
+    }
+    out
+}
+
+/// Requires the padding and rejects the url-safe characters.
+pub(crate) fn decode_standard(s: &str) -> Result<Vec<u8>, String> {
+    let unpadded = s.strip_suffix("==").or_else(|| s.strip_suffix('=')).unwrap_or(s);
+    if !s.len().is_multiple_of(4) || unpadded.contains(['-', '_', '=']) {
+        return Err("Invalid base64".to_owned());
+    }
+    decode(&unpadded.replace('+', "-").replace('/', "_")).map_err(|_| "Invalid base64".to_owned())
+}
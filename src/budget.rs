@@ -0,0 +1,99 @@
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+use ext_php_rs::zend::ProcessGlobals;
+
+use crate::error::JsonError;
+use crate::flags;
+
+unsafe extern "C" {
+    fn zend_memory_usage(real_usage: bool) -> usize;
+}
+
+/// Reading the clock for every event would dominate small documents.
+const CHECK_INTERVAL: u32 = 4096;
+
+/// Input the memory guard sees decoded before projecting from it. The heap
+/// used per input byte ranges from about one for long strings to ten or
+/// more for small scalars, so no fixed ratio fits every document.
+const MIN_MEMORY_SAMPLE: usize = 64 * 1024;
+
+/// Wall-clock and memory allowance for one parse, checked as events are
+/// consumed.
+pub(crate) struct Budget {
+    deadline: Option<Instant>,
+    millis: u64,
+    memory_guard: bool,
+    /// Heap in use and input left at the first check.
+    baseline: Cell<Option<(usize, usize)>>,
+    ticks: Cell<u32>,
+}
+
+impl Budget {
+    /// Starts the clock; `None` means unlimited.
+    pub fn start(millis: Option<u64>, memory_guard: bool) -> Self {
+        Self {
+            deadline: millis.map(|ms| Instant::now() + Duration::from_millis(ms)),
+            millis: millis.unwrap_or(0),
+            memory_guard,
+            baseline: Cell::new(None),
+            ticks: Cell::new(0),
+        }
+    }
+
+    /// Called once per event with the number of input bytes not yet read.
+    pub fn tick(&self, remaining_input: usize) -> Result<(), JsonError> {
+        let ticks = self.ticks.get();
+        self.ticks.set(ticks.wrapping_add(1));
+        if !ticks.is_multiple_of(CHECK_INTERVAL) {
+            return Ok(());
+        }
+
+        if self.deadline.is_some_and(|deadline| Instant::now() > deadline) {
+            return Err(JsonError::new(
+                flags::ERROR_TIME_BUDGET,
+                format!("Parsing exceeded the time budget of {} ms", self.millis),
+            ));
+        }
+        if self.memory_guard {
+            self.check_memory(remaining_input)?;
+        }
+        Ok(())
+    }
+
+    /// Fails when decoding the rest of the input would likely exceed
+    /// `memory_limit`, which the engine would otherwise report as a fatal
+    /// error halfway through building the result. The rest is assumed to
+    /// cost as much heap per byte as the input decoded so far.
+    fn check_memory(&self, remaining_input: usize) -> Result<(), JsonError> {
+        let limit = ProcessGlobals::get().memory_limit;
+        let Ok(limit) = usize::try_from(limit) else {
+            return Ok(());
+        };
+
+        // Memory handed out to values, not the chunks reserved from the
+        // system, which run ahead of it by up to 2 MB.
+        let used = unsafe { zend_memory_usage(false) };
+        let Some((used_before, remaining_before)) = self.baseline.get() else {
+            self.baseline.set(Some((used, remaining_input)));
+            return Ok(());
+        };
+        let decoded = remaining_before.saturating_sub(remaining_input);
+        if decoded < MIN_MEMORY_SAMPLE {
+            return Ok(());
+        }
+        let grown = used.saturating_sub(used_before);
+        let projected = (remaining_input as u128 * grown as u128 / decoded as u128).min(usize::MAX as u128) as usize;
+        if used.saturating_add(projected) > limit {
+            return Err(JsonError::new(
+                flags::ERROR_MEMORY_LIMIT,
+                format!(
+                    "Document too large to decode: about {} more bytes needed with {} of memory_limit left",
+                    projected,
+                    limit.saturating_sub(used)
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
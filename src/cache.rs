@@ -0,0 +1,213 @@
+//! Decoded documents kept across requests, for `Codec::decodeCached()`.
+//!
+//! Zend values are freed at the end of each request, so the cache holds a
+//! Rust-owned copy of the decoded value instead and builds PHP values from
+//! it on a hit. That skips lexing, unescaping and number parsing, which
+//! is most of the cost of decoding. Entries live in the module globals and
+//! are therefore per worker thread.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use ext_php_rs::boxed::ZBox;
+use ext_php_rs::convert::IntoZval;
+use ext_php_rs::types::{ArrayKey, ZendHashTable, ZendObject, Zval};
+
+use crate::error::JsonError;
+use crate::options::DecoderOptions;
+use crate::{decode_bytes, globals, ini, DecodeConfig};
+
+/// An immutable copy of a decoded value.
+enum Frozen {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(Box<str>),
+    Array(Box<[(Key, Frozen)]>),
+    Object(Box<[(Box<str>, Frozen)]>),
+}
+
+enum Key {
+    Index(i64),
+    Name(Box<str>),
+}
+
+impl Frozen {
+    /// Copies `value`, or returns `None` if it holds anything other than
+    /// what ext/json-style decoding produces: scalars, arrays and
+    /// `stdClass` objects.
+    fn freeze(value: &Zval) -> Option<Self> {
+        if value.is_null() {
+            return Some(Frozen::Null);
+        }
+        if let Some(b) = value.bool() {
+            return Some(Frozen::Bool(b));
+        }
+        if let Some(n) = value.long() {
+            return Some(Frozen::Int(n));
+        }
+        if let Some(f) = value.double() {
+            return Some(Frozen::Float(f));
+        }
+        if let Some(s) = value.str() {
+            return Some(Frozen::String(s.into()));
+        }
+        if let Some(arr) = value.array() {
+            let mut elements = Vec::with_capacity(arr.len());
+            for (key, value) in arr {
+                let key = match key {
+                    ArrayKey::Long(index) => Key::Index(index),
+                    key => Key::Name(key.to_string().into()),
+                };
+                elements.push((key, Self::freeze(value)?));
+            }
+            return Some(Frozen::Array(elements.into()));
+        }
+        let obj = value.object().filter(|obj| obj.get_class_name().is_ok_and(|name| name == "stdClass"))?;
+        let properties = obj.get_properties().ok()?;
+        let mut members = Vec::with_capacity(properties.len());
+        for (key, value) in properties {
+            members.push((key.to_string().into(), Self::freeze(value)?));
+        }
+        Some(Frozen::Object(members.into()))
+    }
+
+    fn thaw(&self) -> Result<Zval, JsonError> {
+        let mut zval = Zval::new();
+        match self {
+            Frozen::Null => zval.set_null(),
+            Frozen::Bool(b) => zval.set_bool(*b),
+            Frozen::Int(n) => zval.set_long(*n),
+            Frozen::Float(f) => zval.set_double(*f),
+            Frozen::String(s) => zval.set_zend_string((&**s).into()),
+            Frozen::Array(elements) => {
+                let mut arr: ZBox<ZendHashTable> = ZendHashTable::with_capacity(elements.len() as u32);
+                for (key, value) in elements.iter() {
+                    match key {
+                        Key::Index(index) => arr.insert_at_index(*index, value.thaw()?),
+                        Key::Name(name) => arr.insert(&**name, value.thaw()?),
+                    }
+                    .map_err(JsonError::engine)?;
+                }
+                zval.set_hashtable(arr);
+            }
+            Frozen::Object(members) => {
+                let mut obj = ZendObject::new_stdclass();
+                for (name, value) in members.iter() {
+                    obj.set_property(name, value.thaw()?).map_err(JsonError::engine)?;
+                }
+                return obj.into_zval(false).map_err(JsonError::engine);
+            }
+        }
+        Ok(zval)
+    }
+}
+
+/// What a document was decoded with; the same input decodes differently
+/// under other settings.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Settings {
+    /// See `DecoderOptions::fingerprint()`.
+    options: Rc<str>,
+    depth: i64,
+}
+
+struct Entry {
+    json: Box<[u8]>,
+    settings: Settings,
+    value: Rc<Frozen>,
+    expires: Option<Instant>,
+    /// When the entry was last hit, for evicting the least recently used.
+    used: Instant,
+}
+
+/// The cached documents of one worker, keyed by a hash of their input.
+#[derive(Default)]
+pub(crate) struct DocumentCache {
+    entries: HashMap<(u64, Settings), Entry>,
+    /// Total input size of the entries.
+    bytes: usize,
+}
+
+impl DocumentCache {
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.bytes = 0;
+    }
+
+    fn get(&mut self, hash: u64, json: &[u8], settings: &Settings) -> Option<Rc<Frozen>> {
+        let now = Instant::now();
+        let entry = self.entries.get_mut(&(hash, settings.clone()))?;
+        if *entry.json != *json || entry.expires.is_some_and(|expires| expires <= now) {
+            return None;
+        }
+        entry.used = now;
+        Some(entry.value.clone())
+    }
+
+    /// Stores `entry`, evicting the least recently used entries to stay
+    /// within `max_bytes` of input. Inputs larger than that are not kept.
+    fn put(&mut self, hash: u64, entry: Entry, max_bytes: usize) {
+        if let Some(old) = self.entries.remove(&(hash, entry.settings.clone())) {
+            self.bytes -= old.json.len();
+        }
+        if entry.json.len() > max_bytes {
+            return;
+        }
+        while self.bytes + entry.json.len() > max_bytes {
+            let Some(oldest) = self.entries.iter().min_by_key(|(_, entry)| entry.used).map(|(key, _)| key.clone()) else {
+                break;
+            };
+            if let Some(old) = self.entries.remove(&oldest) {
+                self.bytes -= old.json.len();
+            }
+        }
+        self.bytes += entry.json.len();
+        self.entries.insert((hash, entry.settings.clone()), entry);
+    }
+}
+
+/// Backs `Codec::decodeCached()`: decodes `json` once per worker and
+/// `ttl` seconds, `0` meaning until the worker exits or the cache is
+/// cleared. Options with a key transform decode without the cache.
+pub(crate) fn decode(json: &[u8], ttl: u64, options: DecoderOptions, depth: Option<i64>) -> Result<Zval, JsonError> {
+    let fingerprint = options.fingerprint();
+    let config = DecodeConfig::new(options, depth);
+    let Some(fingerprint) = fingerprint else {
+        return decode_bytes(json, config);
+    };
+    let settings = Settings {
+        options: fingerprint.into(),
+        depth: config.max_depth,
+    };
+    let mut hasher = DefaultHasher::new();
+    json.hash(&mut hasher);
+    let hash = hasher.finish();
+
+    // Values are built outside `globals::with()`, which must not nest.
+    if let Some(frozen) = globals::with(|g| g.cache.get(hash, json, &settings)) {
+        return frozen.thaw();
+    }
+    let value = decode_bytes(json, config)?;
+    if let Some(frozen) = Frozen::freeze(&value) {
+        let now = Instant::now();
+        let entry = Entry {
+            json: json.into(),
+            settings,
+            value: Rc::new(frozen),
+            expires: (ttl > 0).then(|| now + Duration::from_secs(ttl)),
+            used: now,
+        };
+        globals::with(|g| g.cache.put(hash, entry, ini::cache_max_bytes()));
+    }
+    Ok(value)
+}
+
+/// Backs `Codec::clearCache()`.
+pub(crate) fn clear() {
+    globals::with(|g| g.cache.clear());
+}
@@ -0,0 +1,163 @@
+//! Byte order marks and UTF-16/UTF-32 input, which RFC 8259 no longer
+//! allows but Windows tooling still produces.
+
+use std::borrow::Cow;
+
+use ext_php_rs::error::php_error;
+use ext_php_rs::flags::ErrorType;
+
+use crate::error::JsonError;
+use crate::flags;
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// What to do with a UTF-8 byte order mark before the document.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum BomPolicy {
+    #[default]
+    Skip,
+    /// Skip it and raise an `E_WARNING`.
+    Warn,
+    /// Fail with a syntax error, as ext/json does.
+    Reject,
+}
+
+/// `bytes` without a leading UTF-8 byte order mark, as `policy` allows.
+pub(crate) fn strip_bom(bytes: &[u8], policy: BomPolicy) -> &[u8] {
+    match bytes.strip_prefix(UTF8_BOM) {
+        Some(rest) if policy != BomPolicy::Reject => {
+            if policy == BomPolicy::Warn {
+                php_error(&ErrorType::Warning, "JSON input starts with a UTF-8 byte order mark");
+            }
+            rest
+        }
+        _ => bytes,
+    }
+}
+
+/// What to do with bytes inside strings that are not valid UTF-8, as
+/// `JSON_INVALID_UTF8_IGNORE` and `JSON_INVALID_UTF8_SUBSTITUTE` ask.
+/// Outside strings they always fail, as in ext/json.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum InvalidUtf8 {
+    #[default]
+    Reject,
+    Ignore,
+    Substitute,
+}
+
+impl InvalidUtf8 {
+    /// The policy ext/json's flags describe; substitution wins when both
+    /// are set.
+    pub(crate) fn from_flags(flags: i64) -> Self {
+        if flags & flags::INVALID_UTF8_SUBSTITUTE != 0 {
+            Self::Substitute
+        } else if flags & flags::INVALID_UTF8_IGNORE != 0 {
+            Self::Ignore
+        } else {
+            Self::Reject
+        }
+    }
+}
+
+/// `bytes` as UTF-8 text, with invalid bytes inside strings dropped or
+/// replaced by U+FFFD as `policy` allows. Like ext/json, each invalid byte
+/// is replaced on its own.
+pub(crate) fn to_utf8(bytes: &[u8], policy: InvalidUtf8) -> Result<Cow<'_, str>, JsonError> {
+    let malformed = || JsonError::new(flags::ERROR_UTF8, JsonError::native_message(flags::ERROR_UTF8));
+    match std::str::from_utf8(bytes) {
+        Ok(text) => return Ok(Cow::Borrowed(text)),
+        Err(_) if policy == InvalidUtf8::Reject => return Err(malformed()),
+        Err(_) => {}
+    }
+
+    let mut text = String::with_capacity(bytes.len());
+    let (mut quoted, mut escaped) = (false, false);
+    for chunk in bytes.utf8_chunks() {
+        for byte in chunk.valid().bytes() {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' if quoted => escaped = true,
+                b'"' => quoted = !quoted,
+                _ => {}
+            }
+        }
+        text.push_str(chunk.valid());
+        if chunk.invalid().is_empty() {
+            continue;
+        }
+        if !quoted {
+            return Err(malformed());
+        }
+        escaped = false;
+        if policy == InvalidUtf8::Substitute {
+            text.extend(chunk.invalid().iter().map(|_| '\u{FFFD}'));
+        }
+    }
+    Ok(Cow::Owned(text))
+}
+
+#[derive(Clone, Copy)]
+enum Encoding {
+    Utf16Be,
+    Utf16Le,
+    Utf32Be,
+    Utf32Le,
+}
+
+/// Detects UTF-16 and UTF-32 input by its byte order mark or, failing
+/// that, by the pattern of zero bytes its first two characters leave, as
+/// RFC 4627 section 3 describes; both are ASCII in any JSON text. Returns
+/// the input as UTF-8, or `None` when it is UTF-8 already.
+pub(crate) fn transcode(bytes: &[u8]) -> Result<Option<String>, JsonError> {
+    let (encoding, bom) = match bytes {
+        [0, 0, 0xFE, 0xFF, ..] => (Encoding::Utf32Be, 4),
+        [0xFF, 0xFE, 0, 0, ..] => (Encoding::Utf32Le, 4),
+        [0xFE, 0xFF, ..] => (Encoding::Utf16Be, 2),
+        [0xFF, 0xFE, ..] => (Encoding::Utf16Le, 2),
+        [0, 0, 0, a, ..] if *a != 0 => (Encoding::Utf32Be, 0),
+        [a, 0, 0, 0, ..] if *a != 0 => (Encoding::Utf32Le, 0),
+        [0, a, 0, b, ..] if *a != 0 && *b != 0 => (Encoding::Utf16Be, 0),
+        [a, 0, b, 0, ..] if *a != 0 && *b != 0 => (Encoding::Utf16Le, 0),
+        // A single character, such as `1`.
+        [0, a] if *a != 0 => (Encoding::Utf16Be, 0),
+        [a, 0] if *a != 0 => (Encoding::Utf16Le, 0),
+        _ => return Ok(None),
+    };
+    let bytes = &bytes[bom..];
+    let text = match encoding {
+        Encoding::Utf16Be | Encoding::Utf16Le => {
+            let big_endian = matches!(encoding, Encoding::Utf16Be);
+            let units = units::<2>(bytes, "UTF-16")?.map(|unit| match big_endian {
+                true => u16::from_be_bytes(unit),
+                false => u16::from_le_bytes(unit),
+            });
+            char::decode_utf16(units)
+                .collect::<Result<String, _>>()
+                .map_err(|_| JsonError::new(flags::ERROR_UTF16, "Unpaired surrogate in UTF-16 input"))?
+        }
+        Encoding::Utf32Be | Encoding::Utf32Le => {
+            let big_endian = matches!(encoding, Encoding::Utf32Be);
+            units::<4>(bytes, "UTF-32")?
+                .map(|unit| match big_endian {
+                    true => u32::from_be_bytes(unit),
+                    false => u32::from_le_bytes(unit),
+                })
+                .map(char::from_u32)
+                .collect::<Option<String>>()
+                .ok_or_else(|| JsonError::new(flags::ERROR_UTF8, "Malformed UTF-32 input"))?
+        }
+    };
+    Ok(Some(text))
+}
+
+/// `bytes` as a sequence of `N`-byte code units.
+fn units<'a, const N: usize>(bytes: &'a [u8], name: &str) -> Result<impl Iterator<Item = [u8; N]> + 'a, JsonError> {
+    if !bytes.len().is_multiple_of(N) {
+        return Err(JsonError::new(
+            flags::ERROR_UTF8,
+            format!("{} input ends in the middle of a character", name),
+        ));
+    }
+    Ok(bytes.chunks_exact(N).map(|chunk| chunk.try_into().unwrap_or([0; N])))
+}
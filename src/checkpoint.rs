@@ -0,0 +1,83 @@
+//! Opaque resume tokens for the streaming readers.
+//!
+//! A checkpoint is the reader's byte offset plus the little parser state
+//! needed to carry on from it, as base64url text, so it can be stored in a
+//! job table or a file between runs. Offsets are relative to where the
+//! reader started, so a resumed reader must be given the input positioned
+//! the same way.
+
+use ext_php_rs::prelude::*;
+use ext_php_rs::zend::ce;
+
+use crate::base64url;
+
+const VERSION: &str = "1";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Checkpoint {
+    /// An `ArrayCursor` before element `index`, which follows a `,` when
+    /// `after_element` is set.
+    Cursor {
+        offset: usize,
+        index: usize,
+        after_element: bool,
+        done: bool,
+    },
+    /// A `LinesReader` after line `line`.
+    Lines { offset: usize, line: usize },
+}
+
+impl Checkpoint {
+    pub fn encode(&self) -> String {
+        let text = match self {
+            Checkpoint::Cursor {
+                offset,
+                index,
+                after_element,
+                done,
+            } => format!("{}:c:{}:{}:{}:{}", VERSION, offset, index, *after_element as u8, *done as u8),
+            Checkpoint::Lines { offset, line } => format!("{}:l:{}:{}", VERSION, offset, line),
+        };
+        base64url::encode(text.as_bytes())
+    }
+
+    /// Parses a token made by `encode()`, argument `position` of the caller.
+    pub fn decode(token: &str, position: u8) -> PhpResult<Self> {
+        Self::parse(token).ok_or_else(|| Self::invalid(position))
+    }
+
+    /// The error for a token that is malformed or from another kind of
+    /// reader.
+    pub fn invalid(position: u8) -> PhpException {
+        PhpException::new(
+            format!("Argument #{} ($checkpoint) is not a valid checkpoint", position),
+            0,
+            ce::value_error(),
+        )
+    }
+
+    fn parse(token: &str) -> Option<Self> {
+        let bytes = base64url::decode(token).ok()?;
+        let text = std::str::from_utf8(&bytes).ok()?;
+        let fields: Vec<&str> = text.split(':').collect();
+        let number = |i: usize| fields.get(i)?.parse::<usize>().ok();
+        let flag = |i: usize| match fields.get(i) {
+            Some(&"0") => Some(false),
+            Some(&"1") => Some(true),
+            _ => None,
+        };
+        match fields.as_slice() {
+            [VERSION, "c", _, _, _, _] => Some(Checkpoint::Cursor {
+                offset: number(2)?,
+                index: number(3)?,
+                after_element: flag(4)?,
+                done: flag(5)?,
+            }),
+            [VERSION, "l", _, _] => Some(Checkpoint::Lines {
+                offset: number(2)?,
+                line: number(3)?,
+            }),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use ext_php_rs::boxed::ZBox;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::ZendHashTable;
+
+use crate::reader::{string_content, Event, ReadError, Reader};
+use crate::{DecodeConfig, JsonDecoder};
+
+/// Turns an array of objects into one array per member name.
+///
+/// Rows lacking a column get `null` in that column so every column has one
+/// entry per row. With `selected`, only those columns are built (in the given
+/// order) and other members are skipped without being decoded.
+pub(crate) fn decode_columns(json: &str, selected: Option<Vec<String>>) -> PhpResult<ZBox<ZendHashTable>> {
+    let decoder = JsonDecoder::new(DecodeConfig::from_flags(Some(true), None, None));
+    decoder.check_input_size(json)?;
+    let syntax = |e: ReadError| format!("JSON syntax error: {}", e);
+
+    let fixed = selected.is_some();
+    let mut columns: Vec<(String, ZBox<ZendHashTable>)> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for name in selected.unwrap_or_default() {
+        if !index.contains_key(&name) {
+            index.insert(name.clone(), columns.len());
+            columns.push((name, ZendHashTable::new()));
+        }
+    }
+
+    let mut reader = Reader::new(json);
+    if !matches!(reader.next_event().map_err(syntax)?, Some((_, Event::BeginArray))) {
+        return Err("Expected a top-level array of objects".into());
+    }
+
+    let mut row = 0i64;
+    loop {
+        match reader.next_event().map_err(syntax)? {
+            Some((_, Event::EndArray)) => break,
+            Some((_, Event::BeginObject)) => {}
+            Some((offset, _)) => {
+                return Err(format!("Row {} at offset {} is not an object", row, offset).into());
+            }
+            None => return Err("Unexpected end of input".into()),
+        }
+
+        while let Some((_, Event::Key(raw))) = reader.next_event().map_err(syntax)? {
+            let key = string_content(raw)?;
+            let slot = match index.get(&key) {
+                Some(&i) => Some(i),
+                None if fixed => None,
+                None => {
+                    let mut column = ZendHashTable::new();
+                    for i in 0..row {
+                        column.insert_at_index(i, decoder.make_null())?;
+                    }
+                    index.insert(key.clone(), columns.len());
+                    columns.push((key, column));
+                    Some(columns.len() - 1)
+                }
+            };
+
+            let Some(i) = slot else {
+                reader.skip_value().map_err(syntax)?;
+                continue;
+            };
+            let event = decoder.next_event(&mut reader)?;
+            columns[i].1.insert_at_index(row, decoder.read_value(&mut reader, event)?)?;
+        }
+
+        for (_, column) in &mut columns {
+            if (column.len() as i64) <= row {
+                column.insert_at_index(row, decoder.make_null())?;
+            }
+        }
+        row += 1;
+    }
+
+    reader.next_event().map_err(syntax)?;
+
+    let mut result = ZendHashTable::new();
+    for (name, column) in columns {
+        result.insert(name.as_str(), column)?;
+    }
+    Ok(result)
+}
@@ -0,0 +1,294 @@
+//! gzip, zlib and zstd support for `decode()` input and the compressed
+//! encode methods.
+//!
+//! The work is delegated to PHP's zlib and zstd extensions, so only the
+//! formats whose extension is loaded are available. Decoder input is
+//! inflated in full before parsing starts, not streamed into the parser;
+//! encoder output is compressed a chunk at a time as it is written.
+
+use ext_php_rs::binary::Binary;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ZendCallable, ZendHashTable, ZendStr, Zval};
+use ext_php_rs::zend::ce;
+
+use crate::error::JsonError;
+use crate::flags;
+
+#[derive(Clone, Copy)]
+pub(crate) enum Format {
+    Gzip,
+    /// RFC 1950, what HTTP's `Content-Encoding: deflate` carries.
+    Zlib,
+    Zstd,
+}
+
+impl Format {
+    /// No JSON text starts with any of these signatures: `8` is the only
+    /// JSON start byte that fits a zlib header, and none of the bytes that
+    /// would complete one may follow a digit.
+    fn detect(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0x1f, 0x8b, ..] => Some(Self::Gzip),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Self::Zstd),
+            [cmf, flg, ..]
+                if cmf & 0x0f == 8
+                    && cmf >> 4 <= 7
+                    && flg & 0x20 == 0
+                    && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0 =>
+            {
+                Some(Self::Zlib)
+            }
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zlib => "zlib",
+            Self::Zstd => "zstd",
+        }
+    }
+
+    /// Accepted compression levels.
+    fn levels(self) -> std::ops::RangeInclusive<i64> {
+        match self {
+            Self::Gzip | Self::Zlib => 0..=9,
+            Self::Zstd => 1..=22,
+        }
+    }
+
+    /// The PHP functions that start and continue incremental compression.
+    fn deflater(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Gzip | Self::Zlib => ("deflate_init", "deflate_add"),
+            Self::Zstd => ("zstd_compress_init", "zstd_compress_add"),
+        }
+    }
+
+    /// The PHP functions that start and continue incremental decompression,
+    /// and how many bytes one input byte can expand to at most: about 1032
+    /// for deflate, and a 128 KiB block from a four-byte RLE block for zstd.
+    fn inflater(self) -> (&'static str, &'static str, usize) {
+        match self {
+            Self::Gzip | Self::Zlib => ("inflate_init", "inflate_add", 1032),
+            Self::Zstd => ("zstd_uncompress_init", "zstd_uncompress_add", 32 * 1024),
+        }
+    }
+}
+
+/// `ZLIB_ENCODING_ANY`, which accepts both gzip and zlib headers.
+const ZLIB_ENCODING_ANY: i64 = 47;
+const ZLIB_ENCODING_GZIP: i64 = 31;
+const ZLIB_ENCODING_DEFLATE: i64 = 15;
+const ZLIB_NO_FLUSH: i64 = 0;
+const ZLIB_SYNC_FLUSH: i64 = 2;
+const ZLIB_FINISH: i64 = 4;
+const ZLIB_STREAM_END: i64 = 1;
+
+/// The most input handed to one incremental call while output is capped.
+const MAX_CHUNK: usize = 64 * 1024;
+
+/// Returns `bytes` decompressed, as a PHP string, if they carry a gzip, zlib
+/// or zstd header, or `None` otherwise. Output beyond `max_len` fails with
+/// `Json::ERROR_INPUT_TOO_LARGE`.
+pub(crate) fn decompress(bytes: &[u8], max_len: Option<usize>) -> Result<Option<Zval>, JsonError> {
+    let Some(format) = Format::detect(bytes) else {
+        return Ok(None);
+    };
+    let max_len = max_len.filter(|max| *max > 0);
+
+    // Older ext-zstd releases only decompress in one piece, which will do
+    // when the output is not capped.
+    let (_, add, _) = format.inflater();
+    if let (Format::Zstd, None, Err(_)) = (format, max_len, ZendCallable::try_from_name(add)) {
+        let result = php_function(format, "zstd_uncompress")?
+            .try_call(vec![&string(bytes)])
+            .map_err(JsonError::engine)?;
+        return match result.is_string() {
+            true => Ok(Some(result)),
+            false => Err(undecodable(format)),
+        };
+    }
+    Ok(Some(string(&inflate(format, bytes, max_len)?)))
+}
+
+/// Decompresses `bytes` incrementally. With `max_len`, the input is fed in
+/// chunks small enough that none can expand past the bytes still allowed,
+/// so decompression stops soon after the output exceeds it.
+fn inflate(format: Format, bytes: &[u8], max_len: Option<usize>) -> Result<Vec<u8>, JsonError> {
+    let (init, add, ratio) = format.inflater();
+    let add = php_function(format, add)?;
+    let init = php_function(format, init)?;
+    let context = match format {
+        Format::Zstd => init.try_call(vec![]),
+        Format::Gzip | Format::Zlib => init.try_call(vec![&ZLIB_ENCODING_ANY]),
+    }
+    .map_err(JsonError::engine)?;
+    if !context.is_object() {
+        return Err(undecodable(format));
+    }
+
+    let mut out = Vec::new();
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let len = match max_len {
+            Some(max) => (max.saturating_sub(out.len()) / ratio).clamp(1, MAX_CHUNK),
+            None => rest.len(),
+        };
+        let (chunk, tail) = rest.split_at(len.min(rest.len()));
+        rest = tail;
+
+        let chunk = string(chunk);
+        let flush = if rest.is_empty() { ZLIB_FINISH } else { ZLIB_SYNC_FLUSH };
+        let result = match format {
+            Format::Zstd => add.try_call(vec![&context, &chunk]),
+            Format::Gzip | Format::Zlib => add.try_call(vec![&context, &chunk, &flush]),
+        }
+        .map_err(JsonError::engine)?;
+        out.extend_from_slice(result.zend_str().ok_or_else(|| undecodable(format))?.as_bytes());
+
+        if let Some(max) = max_len.filter(|max| out.len() > *max) {
+            return Err(JsonError::new(
+                flags::ERROR_INPUT_TOO_LARGE,
+                format!("Decompressed input exceeds {} bytes", max),
+            ));
+        }
+    }
+
+    // zlib reports a truncated stream only through its status.
+    if let Format::Gzip | Format::Zlib = format {
+        let status = php_function(format, "inflate_get_status")?
+            .try_call(vec![&context])
+            .map_err(JsonError::engine)?;
+        if status.long() != Some(ZLIB_STREAM_END) {
+            return Err(undecodable(format));
+        }
+    }
+    Ok(out)
+}
+
+fn undecodable(format: Format) -> JsonError {
+    JsonError::new(
+        flags::ERROR_SYNTAX,
+        format!("Input looks {}-compressed but could not be decompressed", format.name()),
+    )
+}
+
+fn string(bytes: &[u8]) -> Zval {
+    let mut zval = Zval::new();
+    zval.set_zend_string(ZendStr::new(bytes, false));
+    zval
+}
+
+/// Compresses encoder output at `level` as it is produced. Older ext-zstd
+/// releases without `zstd_compress_add()` get the whole text in one
+/// `zstd_compress()` call instead.
+pub(crate) struct Compressor {
+    format: Format,
+    level: i64,
+    /// The incremental function and its context, when there is one.
+    stream: Option<(ZendCallable<'static>, Zval)>,
+    /// Compressed output so far, or the text itself without `stream`.
+    out: Vec<u8>,
+}
+
+impl Compressor {
+    pub(crate) fn new(format: Format, level: i64) -> PhpResult<Self> {
+        let levels = format.levels();
+        if !levels.contains(&level) {
+            return Err(PhpException::new(
+                format!(
+                    "Argument #2 ($level) must be between {} and {}",
+                    levels.start(),
+                    levels.end()
+                ),
+                0,
+                ce::value_error(),
+            ));
+        }
+
+        let (init, add) = format.deflater();
+        let mut compressor = Self {
+            format,
+            level,
+            stream: None,
+            out: Vec::new(),
+        };
+        if let (Format::Zstd, Err(_)) = (format, ZendCallable::try_from_name(add)) {
+            php_function(format, "zstd_compress")?;
+            return Ok(compressor);
+        }
+
+        let add = php_function(format, add)?;
+        let init = php_function(format, init)?;
+        let context = match format {
+            Format::Zstd => init.try_call(vec![&level]),
+            Format::Gzip | Format::Zlib => {
+                let encoding = match format {
+                    Format::Gzip => ZLIB_ENCODING_GZIP,
+                    _ => ZLIB_ENCODING_DEFLATE,
+                };
+                let mut options = ZendHashTable::new();
+                options.insert("level", level)?;
+                let mut options_zval = Zval::new();
+                options_zval.set_hashtable(options);
+                init.try_call(vec![&encoding, &options_zval])
+            }
+        }
+        .map_err(JsonError::engine)?;
+        if !context.is_object() {
+            return Err(compress_failed(format).into());
+        }
+        compressor.stream = Some((add, context));
+        Ok(compressor)
+    }
+
+    pub(crate) fn write(&mut self, text: &str) -> Result<(), JsonError> {
+        self.add(text.as_bytes(), false)
+    }
+
+    pub(crate) fn finish(mut self) -> Result<Binary<u8>, JsonError> {
+        if self.stream.is_some() {
+            self.add(b"", true)?;
+            return Ok(Binary::from(self.out));
+        }
+        let result = php_function(self.format, "zstd_compress")?
+            .try_call(vec![&string(&self.out), &self.level])
+            .map_err(JsonError::engine)?;
+        let bytes = result.binary::<u8>().ok_or_else(|| compress_failed(self.format))?;
+        Ok(Binary::from(bytes))
+    }
+
+    fn add(&mut self, bytes: &[u8], last: bool) -> Result<(), JsonError> {
+        let Some((add, context)) = &self.stream else {
+            self.out.extend_from_slice(bytes);
+            return Ok(());
+        };
+        let chunk = string(bytes);
+        let result = match self.format {
+            Format::Zstd => add.try_call(vec![context, &chunk, &last]),
+            Format::Gzip | Format::Zlib => {
+                let flush = if last { ZLIB_FINISH } else { ZLIB_NO_FLUSH };
+                add.try_call(vec![context, &chunk, &flush])
+            }
+        }
+        .map_err(JsonError::engine)?;
+        let compressed = result.zend_str().ok_or_else(|| compress_failed(self.format))?;
+        self.out.extend_from_slice(compressed.as_bytes());
+        Ok(())
+    }
+}
+
+fn compress_failed(format: Format) -> JsonError {
+    JsonError::new(flags::ERROR_STATE_MISMATCH, format!("{} compression failed", format.name()))
+}
+
+fn php_function(format: Format, name: &str) -> Result<ZendCallable<'static>, JsonError> {
+    ZendCallable::try_from_name(name).map_err(|_| {
+        JsonError::new(
+            flags::ERROR_STATE_MISMATCH,
+            format!("{} support needs {}(), which is not available", format.name(), name),
+        )
+    })
+}
@@ -0,0 +1,479 @@
+//! Element-by-element iteration over one array inside a large document.
+//!
+//! Only the array's elements are decoded, and each is validated on its own;
+//! the rest of the document is scanned for structure alone. Stream input is
+//! read a chunk at a time and only the current element is kept in memory.
+
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::Zval;
+use ext_php_rs::zend::{ce, ClassEntry};
+
+use crate::checkpoint::Checkpoint;
+use crate::error::JsonError;
+use crate::options::DecoderOptions;
+use crate::pointer::PointerArg;
+use crate::reader::{string_content, ReadError};
+use crate::{decode_bytes, pointer, stream, DecodeConfig};
+
+/// The document bytes, held whole for string input and as a sliding window
+/// for streams. Offsets are relative to where reading started.
+struct Source {
+    stream: Option<Zval>,
+    /// The stream's position when the cursor was created.
+    origin: i64,
+    buffer: Vec<u8>,
+    /// Offset of `buffer[0]`.
+    base: usize,
+    pos: usize,
+    /// Offset from which bytes must be kept, while an element is read.
+    mark: Option<usize>,
+    eof: bool,
+}
+
+impl Source {
+    fn text(json: Vec<u8>) -> Self {
+        Self {
+            stream: None,
+            origin: 0,
+            buffer: json,
+            base: 0,
+            pos: 0,
+            mark: None,
+            eof: true,
+        }
+    }
+
+    fn stream(stream: &Zval) -> PhpResult<Self> {
+        Ok(Self {
+            stream: Some(stream.shallow_clone()),
+            origin: stream::tell(stream)?.unwrap_or(0),
+            eof: false,
+            ..Self::text(Vec::new())
+        })
+    }
+
+    fn peek(&mut self) -> PhpResult<Option<u8>> {
+        while self.pos - self.base >= self.buffer.len() {
+            if !self.refill()? {
+                return Ok(None);
+            }
+        }
+        Ok(Some(self.buffer[self.pos - self.base]))
+    }
+
+    fn refill(&mut self) -> PhpResult<bool> {
+        let Some(stream) = self.stream.as_ref().filter(|_| !self.eof) else {
+            return Ok(false);
+        };
+        let keep = self.mark.unwrap_or(self.pos).min(self.base + self.buffer.len());
+        self.buffer.drain(..keep - self.base);
+        self.base = keep;
+
+        match stream::read_chunk(stream, 1)? {
+            Some(chunk) => self.buffer.extend(chunk),
+            None => self.eof = true,
+        }
+        Ok(!self.eof)
+    }
+
+    /// Moves to `offset`, seeking the stream if it is no longer buffered.
+    fn seek(&mut self, offset: usize) -> PhpResult<()> {
+        if offset >= self.base && offset <= self.base + self.buffer.len() {
+            self.pos = offset;
+            return Ok(());
+        }
+        let Some(stream) = &self.stream else {
+            return Err(format!("Offset {} is outside the document", offset).into());
+        };
+
+        if !stream::seek(stream, self.origin + offset as i64)? {
+            return Err("The stream is not seekable".into());
+        }
+        self.buffer.clear();
+        self.base = offset;
+        self.pos = offset;
+        self.eof = false;
+        Ok(())
+    }
+
+    fn error(&self, message: &str) -> PhpException {
+        JsonError::syntax(ReadError::new(message, self.pos)).into()
+    }
+
+    fn next(&mut self) -> PhpResult<u8> {
+        let byte = self.peek()?.ok_or_else(|| self.error("Unexpected end of input"))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn skip_whitespace(&mut self) -> PhpResult<()> {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek()? {
+            self.pos += 1;
+        }
+        Ok(())
+    }
+
+    fn expect(&mut self, byte: u8) -> PhpResult<()> {
+        self.skip_whitespace()?;
+        if self.next()? != byte {
+            self.pos -= 1;
+            return Err(self.error(&format!("Expected '{}'", byte as char)));
+        }
+        Ok(())
+    }
+
+    /// Skips a string whose opening quote is next, returning its raw text.
+    fn string(&mut self) -> PhpResult<Vec<u8>> {
+        let start = self.pos;
+        let mark = self.mark.replace(self.mark.unwrap_or(start));
+        self.pos += 1;
+        loop {
+            match self.next()? {
+                b'"' => break,
+                b'\\' => {
+                    self.next()?;
+                }
+                _ => {}
+            }
+        }
+        let raw = self.buffer[start - self.base..self.pos - self.base].to_vec();
+        self.mark = mark;
+        Ok(raw)
+    }
+
+    /// Skips the value that starts next, only checking that its brackets
+    /// balance.
+    fn skip_value(&mut self) -> PhpResult<()> {
+        self.skip_whitespace()?;
+        let start = self.pos;
+        let mut depth = 0usize;
+        while let Some(byte) = self.peek()? {
+            match byte {
+                b'"' => {
+                    self.string()?;
+                }
+                b'{' | b'[' => {
+                    depth += 1;
+                    self.pos += 1;
+                }
+                b'}' | b']' if depth > 0 => {
+                    depth -= 1;
+                    self.pos += 1;
+                }
+                b'}' | b']' | b',' | b':' | b' ' | b'\t' | b'\n' | b'\r' if depth == 0 => break,
+                _ => self.pos += 1,
+            }
+            if depth == 0 && matches!(byte, b'"' | b'}' | b']') {
+                break;
+            }
+        }
+        if depth > 0 {
+            return Err(self.error("Unexpected end of input"));
+        }
+        if self.pos == start {
+            return Err(self.error("Expected a value"));
+        }
+        Ok(())
+    }
+}
+
+/// Iterates the elements of the array at a JSON Pointer inside a raw
+/// document or stream, decoding one at a time:
+///
+/// ```php
+/// foreach (ArrayCursor::over(fopen('dump.json', 'rb'), '/records') as $i => $record) { ... }
+/// ```
+///
+/// `seek()` jumps to an element by index. Offsets of the elements passed
+/// are remembered, so seeking back is direct; on streams it needs
+/// `fseek()` support. `checkpoint()` and `resume()` let a long import
+/// carry on in a later process.
+#[php_class]
+#[php(name = "Elephant\\Json\\ArrayCursor")]
+#[php(implements(ce = seekable_iterator_ce, stub = "\\SeekableIterator"))]
+pub struct ArrayCursor {
+    source: Source,
+    options: DecoderOptions,
+    /// Index of the first element this cursor can reach: 0, or the
+    /// element after the checkpoint it was resumed from.
+    first: usize,
+    /// Offsets of the elements found so far, from `first` on.
+    offsets: Vec<usize>,
+    /// Index of the element the source is positioned at or before.
+    next_index: usize,
+    /// Whether a `,` or `]` comes before the next element.
+    after_element: bool,
+    done: bool,
+    current: Option<(usize, Zval)>,
+}
+
+#[php_impl]
+impl ArrayCursor {
+    /// `$input` is a JSON string, a stream resource or a PSR-7 stream;
+    /// `$as_array` takes a bool or an `Elephant\Json\DecoderOptions`
+    /// instance and applies to each element.
+    #[php(defaults(as_array = None))]
+    pub fn over(input: &Zval, pointer: PointerArg, as_array: Option<&Zval>) -> PhpResult<ArrayCursor> {
+        let mut cursor = Self::new(source(input)?, DecoderOptions::from_arg(as_array, None, 3, "as_array")?);
+        cursor.open(&pointer)?;
+        Ok(cursor)
+    }
+
+    /// Continues from a `checkpoint()` taken on the same input, without
+    /// scanning what comes before it. `rewind()` goes back to the
+    /// checkpoint, and earlier elements cannot be reached.
+    #[php(defaults(as_array = None))]
+    pub fn resume(input: &Zval, checkpoint: &str, as_array: Option<&Zval>) -> PhpResult<ArrayCursor> {
+        let Checkpoint::Cursor {
+            offset,
+            index,
+            after_element,
+            done,
+        } = Checkpoint::decode(checkpoint, 2)?
+        else {
+            return Err(Checkpoint::invalid(2));
+        };
+        let mut cursor = Self::new(source(input)?, DecoderOptions::from_arg(as_array, None, 3, "as_array")?);
+        cursor.source.seek(offset)?;
+        cursor.first = index;
+        cursor.next_index = index;
+        cursor.after_element = after_element;
+        cursor.done = done;
+        Ok(cursor)
+    }
+
+    /// An opaque token for `resume()`, pointing after the current element.
+    pub fn checkpoint(&self) -> String {
+        Checkpoint::Cursor {
+            offset: self.source.pos,
+            index: self.next_index,
+            after_element: self.after_element,
+            done: self.done,
+        }
+        .encode()
+    }
+
+    pub fn rewind(&mut self) -> PhpResult<()> {
+        self.go_to(0)?;
+        self.advance()
+    }
+
+    pub fn valid(&self) -> bool {
+        self.current.is_some()
+    }
+
+    pub fn current(&self) -> Zval {
+        match &self.current {
+            Some((_, value)) => value.shallow_clone(),
+            None => Zval::new(),
+        }
+    }
+
+    pub fn key(&self) -> Option<i64> {
+        self.current.as_ref().map(|(index, _)| *index as i64)
+    }
+
+    pub fn next(&mut self) -> PhpResult<()> {
+        self.advance()
+    }
+
+    /// Moves to the element at `$offset`, throwing `OutOfBoundsException`
+    /// past the end of the array.
+    pub fn seek(&mut self, offset: i64) -> PhpResult<()> {
+        let index = usize::try_from(offset).ok();
+        if let Some(index) = index {
+            self.go_to(index)?;
+            self.advance()?;
+        }
+        match (&self.current, index) {
+            (Some((current, _)), Some(index)) if *current == index => Ok(()),
+            _ => Err(PhpException::new(
+                format!("Seek position {} is out of range", offset),
+                0,
+                out_of_bounds_ce(),
+            )),
+        }
+    }
+}
+
+impl ArrayCursor {
+    /// A cursor over the root array of `input`, for `Codec::lazyDecode()`.
+    pub(crate) fn over_root(input: &Zval, options: DecoderOptions) -> PhpResult<Self> {
+        let mut cursor = Self::new(source(input)?, options);
+        cursor.open(&PointerArg::root())?;
+        Ok(cursor)
+    }
+
+    fn new(source: Source, options: DecoderOptions) -> Self {
+        Self {
+            source,
+            options,
+            first: 0,
+            offsets: Vec::new(),
+            next_index: 0,
+            after_element: false,
+            done: false,
+            current: None,
+        }
+    }
+
+    /// Walks down to the array at `pointer` and stops after its `[`.
+    fn open(&mut self, json_pointer: &PointerArg) -> PhpResult<()> {
+        let source = &mut self.source;
+        for token in json_pointer.tokens()?.iter() {
+            let missing = || -> PhpException { format!("No value at '{}'", json_pointer.as_str()).into() };
+            source.skip_whitespace()?;
+            match source.peek()? {
+                Some(b'{') => {
+                    source.pos += 1;
+                    loop {
+                        source.skip_whitespace()?;
+                        if source.peek()? != Some(b'"') {
+                            return Err(missing());
+                        }
+                        let raw = source.string()?;
+                        let key = std::str::from_utf8(&raw)
+                            .map_err(|_| source.error("Malformed UTF-8 in key"))
+                            .and_then(|raw| string_content(raw).map_err(|e| source.error(&e)))?;
+                        source.expect(b':')?;
+                        if key == *token {
+                            break;
+                        }
+                        source.skip_value()?;
+                        source.skip_whitespace()?;
+                        if source.next()? != b',' {
+                            return Err(missing());
+                        }
+                    }
+                }
+                Some(b'[') => {
+                    source.pos += 1;
+                    let index = pointer::array_index(token).ok_or_else(missing)?;
+                    for _ in 0..index {
+                        source.skip_whitespace()?;
+                        if source.peek()? == Some(b']') {
+                            return Err(missing());
+                        }
+                        source.skip_value()?;
+                        source.skip_whitespace()?;
+                        if source.next()? != b',' {
+                            return Err(missing());
+                        }
+                    }
+                    source.skip_whitespace()?;
+                    if source.peek()? == Some(b']') {
+                        return Err(missing());
+                    }
+                }
+                _ => return Err(missing()),
+            }
+        }
+
+        source.skip_whitespace()?;
+        if source.peek()? != Some(b'[') {
+            return Err(format!("No array at '{}'", json_pointer.as_str()).into());
+        }
+        source.pos += 1;
+        Ok(())
+    }
+
+    /// Positions the source before element `index`, or at the end if the
+    /// array is shorter, without decoding anything.
+    fn go_to(&mut self, index: usize) -> PhpResult<()> {
+        // Carry on from here when that is on the way, otherwise restart
+        // from the closest element already found.
+        let index = index.max(self.first);
+        let nearest = self.offsets.len().checked_sub(1).map(|last| (self.first + last).min(index));
+        let on_the_way = self.next_index <= index && nearest.is_none_or(|n| self.next_index >= n);
+        if let (false, Some(nearest)) = (on_the_way, nearest) {
+            self.source.seek(self.offsets[nearest - self.first])?;
+            self.next_index = nearest;
+            self.after_element = false;
+            self.done = false;
+        }
+
+        while self.next_index < index && !self.done {
+            if self.element_start()?.is_some() {
+                self.source.skip_value()?;
+                self.next_index += 1;
+                self.after_element = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Consumes the separator before the next element and records where
+    /// it starts; `None` at the end of the array.
+    fn element_start(&mut self) -> PhpResult<Option<usize>> {
+        let source = &mut self.source;
+        source.skip_whitespace()?;
+        if source.peek()? == Some(b']') {
+            self.done = true;
+            return Ok(None);
+        }
+        if self.after_element {
+            if source.next()? != b',' {
+                source.pos -= 1;
+                return Err(source.error("Expected ',' or ']'"));
+            }
+            source.skip_whitespace()?;
+        }
+        let start = source.pos;
+        if self.first + self.offsets.len() == self.next_index {
+            self.offsets.push(start);
+        }
+        Ok(Some(start))
+    }
+
+    fn advance(&mut self) -> PhpResult<()> {
+        self.current = None;
+        if self.done {
+            return Ok(());
+        }
+        let Some(start) = self.element_start()? else {
+            return Ok(());
+        };
+
+        self.source.mark = Some(start);
+        let skipped = self.source.skip_value();
+        self.source.mark = None;
+        skipped?;
+
+        let end = self.source.pos;
+        let base = self.source.base;
+        let bytes = &self.source.buffer[start - base..end - base];
+        let index = self.next_index;
+        let value = decode_bytes(bytes, DecodeConfig::new(self.options.clone(), None))
+            .map_err(|err| JsonError::new(err.code, format!("Element {}: {}", index, err)))?;
+
+        self.current = Some((index, value));
+        self.next_index += 1;
+        self.after_element = true;
+        Ok(())
+    }
+}
+
+fn source(input: &Zval) -> PhpResult<Source> {
+    if let Some(json) = input.binary::<u8>().filter(|_| input.is_string()) {
+        Ok(Source::text(json))
+    } else if input.is_resource() || input.object().is_some() {
+        Source::stream(input)
+    } else {
+        Err(PhpException::new(
+            format!(
+                "Argument #1 ($input) must be of type string, a stream resource or a Psr\\Http\\Message\\StreamInterface, {} given",
+                input.get_type()
+            ),
+            0,
+            ce::type_error(),
+        ))
+    }
+}
+
+fn seekable_iterator_ce() -> &'static ClassEntry {
+    ClassEntry::try_find("SeekableIterator").unwrap_or_else(ce::iterator)
+}
+
+fn out_of_bounds_ce() -> &'static ClassEntry {
+    ClassEntry::try_find("OutOfBoundsException").unwrap_or_else(ce::exception)
+}
@@ -0,0 +1,130 @@
+//! Where two documents differ, without building a patch.
+
+use serde_json::Value;
+
+use crate::pointer;
+use crate::schema::equal;
+
+#[derive(Clone, Copy)]
+pub(crate) enum Change {
+    Added,
+    Removed,
+    Modified,
+}
+
+impl Change {
+    pub fn name(self) -> &'static str {
+        match self {
+            Change::Added => "added",
+            Change::Removed => "removed",
+            Change::Modified => "modified",
+        }
+    }
+}
+
+/// The JSON Pointers at which `b` differs from `a`, depth first with
+/// members in name order. A value whose type or scalar content changed is
+/// reported once, without its descendants; containers present on both
+/// sides are compared member by member and element by element.
+pub(crate) fn changed_paths(a: &Value, b: &Value) -> Vec<(String, Change)> {
+    let mut changes = Vec::new();
+    compare(a, b, &mut String::new(), &mut changes);
+    changes
+}
+
+fn compare(a: &Value, b: &Value, path: &mut String, changes: &mut Vec<(String, Change)>) {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (name, old) in a {
+                within(path, name, |path| match b.get(name) {
+                    Some(new) => compare(old, new, path, changes),
+                    None => changes.push((path.clone(), Change::Removed)),
+                });
+            }
+            for name in b.keys().filter(|name| !a.contains_key(*name)) {
+                within(path, name, |path| changes.push((path.clone(), Change::Added)));
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            for i in 0..a.len().max(b.len()) {
+                within(path, &i.to_string(), |path| match (a.get(i), b.get(i)) {
+                    (Some(old), Some(new)) => compare(old, new, path, changes),
+                    (Some(_), None) => changes.push((path.clone(), Change::Removed)),
+                    _ => changes.push((path.clone(), Change::Added)),
+                });
+            }
+        }
+        _ if equal(a, b) => {}
+        _ => changes.push((path.clone(), Change::Modified)),
+    }
+}
+
+/// How far apart two numbers may be and still count as equal, as with
+/// Python's `math.isclose()`.
+#[derive(Clone, Copy)]
+pub(crate) struct Tolerance {
+    pub absolute: f64,
+    /// Relative to the larger magnitude.
+    pub relative: f64,
+}
+
+impl Tolerance {
+    fn accepts(self, a: f64, b: f64) -> bool {
+        (a - b).abs() <= self.absolute.max(self.relative * a.abs().max(b.abs()))
+    }
+}
+
+/// The first JSON Pointer, in the order of `changed_paths()`, at which `a`
+/// and `b` differ by more than `tolerance`; `None` if they are equal
+/// within it. Only numbers are compared with tolerance.
+pub(crate) fn first_difference(a: &Value, b: &Value, tolerance: Tolerance) -> Option<String> {
+    let mut path = String::new();
+    differs(a, b, tolerance, &mut path).then_some(path)
+}
+
+/// Whether `a` and `b` differ, leaving `path` pointing at the difference.
+fn differs(a: &Value, b: &Value, tolerance: Tolerance, path: &mut String) -> bool {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            let names = a.keys().chain(b.keys().filter(|name| !a.contains_key(*name)));
+            for name in names {
+                let len = path.len();
+                path.push('/');
+                path.push_str(&pointer::escape(name));
+                match (a.get(name), b.get(name)) {
+                    (Some(old), Some(new)) if !differs(old, new, tolerance, path) => path.truncate(len),
+                    _ => return true,
+                }
+            }
+            false
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            for i in 0..a.len().max(b.len()) {
+                let len = path.len();
+                path.push('/');
+                path.push_str(&i.to_string());
+                match (a.get(i), b.get(i)) {
+                    (Some(old), Some(new)) if !differs(old, new, tolerance, path) => path.truncate(len),
+                    _ => return true,
+                }
+            }
+            false
+        }
+        (Value::Number(x), Value::Number(y)) if !equal(a, b) => {
+            match (x.as_f64(), y.as_f64()) {
+                (Some(x), Some(y)) => !tolerance.accepts(x, y),
+                _ => true,
+            }
+        }
+        _ => !equal(a, b),
+    }
+}
+
+/// Runs `f` with `segment` appended to `path`.
+fn within(path: &mut String, segment: &str, f: impl FnOnce(&mut String)) {
+    let len = path.len();
+    path.push('/');
+    path.push_str(&pointer::escape(segment));
+    f(path);
+    path.truncate(len);
+}
@@ -0,0 +1,590 @@
+//! Lazily decoded documents.
+//!
+//! [`Document::parse`] reads the whole input once but keeps only a flat
+//! index of value spans; PHP values are built for the parts a caller asks
+//! for, by decoding just that span.
+
+use std::rc::Rc;
+
+use ext_php_rs::binary::Binary;
+use ext_php_rs::convert::FromZval;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ZendHashTable, Zval};
+use ext_php_rs::zend::ce;
+
+use crate::error::JsonError;
+use crate::options::{DecoderOptions, DuplicateKeys};
+use crate::pointer::{self, PointerArg};
+use crate::reader::{string_content, Event};
+use crate::{DecodeConfig, EncodeConfig, JsonDecoder, JsonEncoder};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Object,
+    Array,
+    Scalar,
+}
+
+/// One value of the document, stored in document order so a container's
+/// descendants directly follow it.
+struct Node {
+    kind: Kind,
+    /// Byte span of the value.
+    start: usize,
+    end: usize,
+    /// Byte span of the member name lexeme, quotes included, for object
+    /// members.
+    key: Option<(usize, usize)>,
+    /// Index of the node after this one's subtree, i.e. its next sibling.
+    next: usize,
+    /// Number of direct children.
+    len: usize,
+}
+
+struct Index {
+    json: String,
+    nodes: Vec<Node>,
+    options: DecoderOptions,
+}
+
+impl Index {
+    fn build(json: String, options: DecoderOptions) -> Result<Self, JsonError> {
+        let decoder = JsonDecoder::new(DecodeConfig::new(options.clone(), None));
+        decoder.check_input_size(&json)?;
+
+        let mut nodes: Vec<Node> = Vec::new();
+        let mut open: Vec<usize> = Vec::new();
+        let mut key = None;
+        let mut reader = decoder.reader(&json);
+
+        loop {
+            let event = decoder.next_event(&mut reader)?;
+            let start = reader.token_start();
+            let kind = match event {
+                Event::Key(raw) => {
+                    key = Some((start, start + raw.len()));
+                    continue;
+                }
+                Event::EndObject | Event::EndArray => {
+                    let Some(i) = open.pop() else {
+                        return Err(JsonError::syntax("unexpected token"));
+                    };
+                    nodes[i].end = reader.position();
+                    nodes[i].next = nodes.len();
+                    if open.is_empty() {
+                        break;
+                    }
+                    continue;
+                }
+                Event::BeginObject => Kind::Object,
+                Event::BeginArray => Kind::Array,
+                _ => Kind::Scalar,
+            };
+
+            if let Some(&parent) = open.last() {
+                nodes[parent].len += 1;
+            }
+            nodes.push(Node {
+                kind,
+                start,
+                end: reader.position(),
+                key: key.take(),
+                next: nodes.len() + 1,
+                len: 0,
+            });
+
+            if kind == Kind::Scalar {
+                if open.is_empty() {
+                    break;
+                }
+            } else {
+                decoder.check_depth(&reader)?;
+                open.push(nodes.len() - 1);
+            }
+        }
+
+        decoder.finish(&mut reader)?;
+        Ok(Self { json, nodes, options })
+    }
+
+    /// Serializes the nodes for `Document::index()`. Offsets are stored as
+    /// deltas in LEB128 varints, after a header identifying the input.
+    fn export(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(INDEX_MAGIC.len() + 32 + self.nodes.len() * 6);
+        out.extend_from_slice(INDEX_MAGIC);
+        write_varint(&mut out, self.json.len());
+        out.extend_from_slice(&fingerprint(&self.json).to_le_bytes());
+        write_varint(&mut out, self.nodes.len());
+        let mut previous = 0;
+        for (i, node) in self.nodes.iter().enumerate() {
+            let kind = match node.kind {
+                Kind::Object => 0,
+                Kind::Array => 1,
+                Kind::Scalar => 2,
+            };
+            out.push(kind | if node.key.is_some() { 4 } else { 0 });
+            write_varint(&mut out, node.start - previous);
+            write_varint(&mut out, node.end - node.start);
+            if let Some((start, end)) = node.key {
+                write_varint(&mut out, node.start - start);
+                write_varint(&mut out, end - start);
+            }
+            write_varint(&mut out, node.next - i);
+            write_varint(&mut out, node.len);
+            previous = node.start;
+        }
+        out
+    }
+
+    /// Rebuilds the index of `json` from `export()` output, checking that
+    /// it was made for these exact bytes and that every span is in bounds.
+    /// The grammar and limits were checked when the index was built, and
+    /// are not checked again.
+    fn import(json: String, bytes: &[u8], options: DecoderOptions) -> Option<Self> {
+        let mut input = bytes.strip_prefix(INDEX_MAGIC)?;
+        if read_varint(&mut input)? != json.len() {
+            return None;
+        }
+        let (hash, rest) = input.split_first_chunk::<8>()?;
+        if u64::from_le_bytes(*hash) != fingerprint(&json) {
+            return None;
+        }
+        input = rest;
+
+        let count = read_varint(&mut input)?;
+        let span = |start: usize, len: usize| {
+            let end = start.checked_add(len).filter(|&end| end <= json.len())?;
+            (json.is_char_boundary(start) && json.is_char_boundary(end)).then_some(end)
+        };
+        let mut nodes = Vec::with_capacity(count.min(json.len()));
+        let mut previous: usize = 0;
+        for i in 0..count {
+            let (&flags, rest) = input.split_first()?;
+            input = rest;
+            let kind = match flags & 3 {
+                0 => Kind::Object,
+                1 => Kind::Array,
+                2 => Kind::Scalar,
+                _ => return None,
+            };
+            let start = previous.checked_add(read_varint(&mut input)?)?;
+            let end = span(start, read_varint(&mut input)?)?;
+            let key = match flags & 4 {
+                0 => None,
+                _ => {
+                    let key_start = start.checked_sub(read_varint(&mut input)?)?;
+                    let key_end = span(key_start, read_varint(&mut input)?).filter(|&end| end <= start)?;
+                    Some((key_start, key_end))
+                }
+            };
+            let next = i.checked_add(read_varint(&mut input)?)?;
+            if next <= i || next > count || (kind == Kind::Scalar && next != i + 1) {
+                return None;
+            }
+            let len = read_varint(&mut input)?;
+            nodes.push(Node { kind, start, end, key, next, len });
+            previous = start;
+        }
+        (input.is_empty() && !nodes.is_empty()).then_some(Self { json, nodes, options })
+    }
+
+    fn raw(&self, node: usize) -> &str {
+        let node = &self.nodes[node];
+        &self.json[node.start..node.end]
+    }
+
+    /// Direct children of `node`, in document order.
+    fn children(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        // Checked before indexing, as a container at the end of the
+        // document ends with the node list.
+        let end = self.nodes[node].next;
+        std::iter::successors(Some(node + 1).filter(|&first| first < end), move |&child| {
+            Some(self.nodes[child].next).filter(|&next| next < end)
+        })
+    }
+
+    fn key(&self, node: usize) -> Option<Result<String, String>> {
+        self.nodes[node]
+            .key
+            .map(|(start, end)| string_content(&self.json[start..end]))
+    }
+
+    /// Whether the member name of `node` is `name`, unescaping only when the
+    /// lexeme contains escapes.
+    fn key_is(&self, node: usize, name: &str) -> Result<bool, String> {
+        let Some((start, end)) = self.nodes[node].key else {
+            return Ok(false);
+        };
+        let raw = &self.json[start + 1..end - 1];
+        if raw.contains('\\') {
+            Ok(string_content(&self.json[start..end])? == name)
+        } else {
+            Ok(raw == name)
+        }
+    }
+
+    /// Resolves a JSON Pointer (RFC 6901) relative to `node`.
+    fn resolve(&self, node: usize, pointer: &PointerArg) -> Result<Option<usize>, String> {
+        self.walk(node, &pointer.tokens()?, pointer.as_str())
+    }
+
+    fn walk(&self, node: usize, tokens: &[String], pointer: &str) -> Result<Option<usize>, String> {
+        let mut current = node;
+        for token in tokens {
+            match self.child(current, token, pointer)? {
+                Some(child) => current = child,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(current))
+    }
+
+    /// The child of `node` named by `token`, picking among repeated member
+    /// names the way the `duplicateKeys()` policy would.
+    fn child(&self, node: usize, token: &str, pointer: &str) -> Result<Option<usize>, String> {
+        match self.nodes[node].kind {
+            Kind::Object => {
+                let mut found = None;
+                for child in self.children(node) {
+                    if !self.key_is(child, token)? {
+                        continue;
+                    }
+                    match self.options.duplicate_keys {
+                        DuplicateKeys::First => return Ok(Some(child)),
+                        DuplicateKeys::Error if found.is_some() => {
+                            return Err(format!("Duplicate key '{}' in '{}'", token, pointer));
+                        }
+                        _ => found = Some(child),
+                    }
+                }
+                Ok(found)
+            }
+            Kind::Array => Ok(pointer::array_index(token).and_then(|i| self.children(node).nth(i))),
+            Kind::Scalar => Ok(None),
+        }
+    }
+
+    /// Where `node` starts including its member name, if it has one.
+    fn entry_start(&self, node: usize) -> usize {
+        let node = &self.nodes[node];
+        node.key.map_or(node.start, |(start, _)| start)
+    }
+
+    /// A new index over this document with `start..end` replaced by `text`.
+    fn splice(&self, start: usize, end: usize, text: &str) -> Result<Self, JsonError> {
+        let json = [&self.json[..start], text, &self.json[end..]].concat();
+        Self::build(json, self.options.clone())
+    }
+
+    fn decode(&self, node: usize) -> Result<Zval, JsonError> {
+        JsonDecoder::new(DecodeConfig::new(self.options.clone(), None)).decode(self.raw(node))
+    }
+}
+
+/// Leads every exported index; the last byte is the format version.
+const INDEX_MAGIC: &[u8] = b"EJDX\x01";
+
+fn write_varint(out: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn read_varint(input: &mut &[u8]) -> Option<usize> {
+    let mut n: usize = 0;
+    for shift in (0..usize::BITS).step_by(7) {
+        let (&byte, rest) = input.split_first()?;
+        *input = rest;
+        n |= usize::from(byte & 0x7f).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(n);
+        }
+    }
+    None
+}
+
+/// FNV-1a, so an index is never applied to other bytes than it was built
+/// from. Unlike `DefaultHasher` it is stable across builds, which matters
+/// for indexes kept in storage.
+fn fingerprint(json: &str) -> u64 {
+    json.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// A parsed document whose values are decoded only when they are read.
+///
+/// Every method takes a JSON Pointer relative to this document's root and
+/// defaulting to the root itself. Iterating yields the root's members or
+/// elements, each decoded as the loop reaches it.
+#[php_class]
+#[php(name = "Elephant\\Json\\Document")]
+#[php(implements(ce = ce::iterator, stub = "\\Iterator"))]
+#[php(implements(ce = ce::countable, stub = "\\Countable"))]
+pub struct Document {
+    index: Rc<Index>,
+    root: usize,
+    /// The child the iterator is at, and its position among its siblings.
+    cursor: usize,
+    position: i64,
+}
+
+#[php_impl]
+impl Document {
+    /// Checks the whole document against the grammar and the limits of
+    /// `$options`, but builds no PHP values.
+    #[php(defaults(options = None))]
+    pub fn parse(json: String, options: Option<&DecoderOptions>) -> PhpResult<Document> {
+        let options = options
+            .cloned()
+            .unwrap_or_else(|| DecoderOptions::resolve(None, None).0);
+        Ok(Self::view(Rc::new(Index::build(json, options)?), 0))
+    }
+
+    /// Rebuilds a document from its JSON and the output of `index()`,
+    /// without parsing it again. Throws a `ValueError` if `$index` was not
+    /// exported from a document of exactly these bytes.
+    #[php(defaults(options = None))]
+    pub fn from_index(json: String, index: Binary<u8>, options: Option<&DecoderOptions>) -> PhpResult<Document> {
+        let options = options
+            .cloned()
+            .unwrap_or_else(|| DecoderOptions::resolve(None, None).0);
+        let index = Index::import(json, &index, options).ok_or_else(|| {
+            PhpException::new(
+                "Argument #2 ($index) is not an index of this JSON document".to_string(),
+                0,
+                ce::value_error(),
+            )
+        })?;
+        Ok(Self::view(Rc::new(index), 0))
+    }
+
+    /// The structural index of the whole document, even for a document
+    /// returned by `at()`, as a binary string to store next to the JSON and
+    /// pass to `fromIndex()` later.
+    pub fn index(&self) -> Binary<u8> {
+        Binary::new(self.index.export())
+    }
+
+    /// Decodes the value at `$pointer` with the options given to `parse()`.
+    #[php(defaults(pointer = None))]
+    pub fn get(&self, pointer: Option<PointerArg>) -> PhpResult<Zval> {
+        let node = self.find(&pointer.unwrap_or_else(PointerArg::root))?;
+        Ok(self.index.decode(node)?)
+    }
+
+    #[php(defaults(pointer = None))]
+    pub fn has(&self, pointer: Option<PointerArg>) -> PhpResult<bool> {
+        let pointer = pointer.unwrap_or_else(PointerArg::root);
+        Ok(self.index.resolve(self.root, &pointer)?.is_some())
+    }
+
+    /// The value at `$pointer` exactly as it appears in the input.
+    #[php(defaults(pointer = None))]
+    pub fn raw(&self, pointer: Option<PointerArg>) -> PhpResult<String> {
+        let node = self.find(&pointer.unwrap_or_else(PointerArg::root))?;
+        Ok(self.index.raw(node).to_string())
+    }
+
+    /// Number of members or elements of the container at `$pointer`.
+    #[php(defaults(pointer = None))]
+    pub fn count(&self, pointer: Option<PointerArg>) -> PhpResult<i64> {
+        let pointer = pointer.unwrap_or_else(PointerArg::root);
+        let node = self.find(&pointer)?;
+        match self.index.nodes[node].kind {
+            Kind::Scalar => Err(not_a_container(pointer.as_str())),
+            _ => Ok(self.index.nodes[node].len as i64),
+        }
+    }
+
+    /// A document rooted at `$pointer`, sharing this one's index.
+    pub fn at(&self, pointer: PointerArg) -> PhpResult<Document> {
+        let node = self.find(&pointer)?;
+        Ok(Self::view(Rc::clone(&self.index), node))
+    }
+
+    /// A copy with the value at `$pointer` replaced by `$value`. When only
+    /// the last token is missing, the value is added instead: as a new member
+    /// of an object, or appended to an array if the token is `-` or the
+    /// array's length.
+    pub fn with(&self, pointer: PointerArg, value: &Zval) -> PhpResult<Document> {
+        let tokens = pointer.tokens()?;
+        let text = encode(value)?;
+        if let Some(node) = self.index.walk(self.root, &tokens, pointer.as_str())? {
+            let node = &self.index.nodes[node];
+            return self.edited(node.start, node.end, &text);
+        }
+
+        let Some((last, parents)) = tokens.split_last() else {
+            return Err(format!("No value at '{}'", pointer.as_str()).into());
+        };
+        let parent = self.parent(parents, pointer.as_str())?;
+        let parent_node = &self.index.nodes[parent];
+        let entry = match parent_node.kind {
+            Kind::Object => {
+                let mut key = Zval::new();
+                key.set_zend_string(last.as_str().into());
+                format!("{}:{}", encode(&key)?, text)
+            }
+            Kind::Array if last == "-" || pointer::array_index(last) == Some(parent_node.len) => text,
+            Kind::Array => {
+                return Err(format!("Index '{}' is out of range in '{}'", last, pointer::join(parents)).into());
+            }
+            Kind::Scalar => return Err(not_a_container(&pointer::join(parents))),
+        };
+        self.appended(parent, &entry)
+    }
+
+    /// A copy without the value at `$pointer`, along with its member name
+    /// and one separating comma.
+    pub fn without(&self, pointer: PointerArg) -> PhpResult<Document> {
+        let tokens = pointer.tokens()?;
+        let Some((_, parents)) = tokens.split_last() else {
+            return Err("Cannot remove the document root".into());
+        };
+        let node = self.find(&pointer)?;
+        let parent = self.parent(parents, pointer.as_str())?;
+
+        let siblings: Vec<usize> = self.index.children(parent).collect();
+        let position = siblings.iter().position(|&child| child == node).unwrap_or_default();
+        let index = &self.index;
+        let (start, end) = match (position.checked_sub(1), siblings.get(position + 1)) {
+            (Some(previous), _) => (index.nodes[siblings[previous]].end, index.nodes[node].end),
+            (None, Some(&next)) => (index.entry_start(node), index.entry_start(next)),
+            (None, None) => (index.entry_start(node), index.nodes[node].end),
+        };
+        self.edited(start, end, "")
+    }
+
+    /// A copy with `$value` appended to the array at `$pointer`.
+    pub fn push(&self, pointer: PointerArg, value: &Zval) -> PhpResult<Document> {
+        let node = self.find(&pointer)?;
+        if self.index.nodes[node].kind != Kind::Array {
+            return Err(format!("Value at '{}' is not an array", pointer.as_str()).into());
+        }
+        self.appended(node, &encode(value)?)
+    }
+
+    pub fn rewind(&mut self) {
+        self.cursor = self.root + 1;
+        self.position = 0;
+    }
+
+    pub fn valid(&self) -> bool {
+        self.cursor < self.index.nodes[self.root].next
+    }
+
+    pub fn current(&self) -> PhpResult<Zval> {
+        if !self.valid() {
+            let mut zval = Zval::new();
+            zval.set_null();
+            return Ok(zval);
+        }
+        Ok(self.index.decode(self.cursor)?)
+    }
+
+    pub fn key(&self) -> PhpResult<Zval> {
+        let mut zval = Zval::new();
+        if !self.valid() {
+            zval.set_null();
+            return Ok(zval);
+        }
+        match self.index.key(self.cursor) {
+            Some(key) => zval.set_zend_string(key?.as_str().into()),
+            None => zval.set_long(self.position),
+        }
+        Ok(zval)
+    }
+
+    pub fn next(&mut self) {
+        if self.valid() {
+            self.cursor = self.index.nodes[self.cursor].next;
+            self.position += 1;
+        }
+    }
+}
+
+impl Document {
+    fn view(index: Rc<Index>, root: usize) -> Self {
+        Self {
+            index,
+            root,
+            cursor: root + 1,
+            position: 0,
+        }
+    }
+
+    fn parent(&self, tokens: &[String], pointer: &str) -> PhpResult<usize> {
+        self.index
+            .walk(self.root, tokens, pointer)?
+            .ok_or_else(|| format!("No value at '{}'", pointer::join(tokens)).into())
+    }
+
+    /// Adds an already encoded member or element after the last child of
+    /// `container`.
+    fn appended(&self, container: usize, entry: &str) -> PhpResult<Document> {
+        match self.index.children(container).last() {
+            Some(last) => {
+                let at = self.index.nodes[last].end;
+                self.edited(at, at, &format!(",{}", entry))
+            }
+            None => {
+                let at = self.index.nodes[container].end - 1;
+                self.edited(at, at, entry)
+            }
+        }
+    }
+
+    /// Splices `text` into the raw document and indexes the result. Edits
+    /// stay within this document's root, which therefore keeps its offset.
+    fn edited(&self, start: usize, end: usize, text: &str) -> PhpResult<Document> {
+        let index = self.index.splice(start, end, text)?;
+        let offset = self.index.nodes[self.root].start;
+        let root = index.nodes.partition_point(|node| node.start < offset);
+        Ok(Self::view(Rc::new(index), root))
+    }
+
+    fn find(&self, pointer: &PointerArg) -> PhpResult<usize> {
+        self.index
+            .resolve(self.root, pointer)?
+            .ok_or_else(|| format!("No value at '{}'", pointer.as_str()).into())
+    }
+}
+
+/// Backs `Codec::edit()`: applies each of `operations` to `json` in turn,
+/// through the same splicing as `with()`, `without()` and `push()`.
+pub(crate) fn edit(json: String, operations: &ZendHashTable) -> PhpResult<String> {
+    let mut doc = Document::parse(json, None)?;
+    for (i, operation) in operations.values().enumerate() {
+        let invalid = |detail: &str| {
+            PhpException::new(
+                format!("Argument #2 ($operations) element {} {}", i, detail),
+                0,
+                ce::value_error(),
+            )
+        };
+        let operation = operation.array().ok_or_else(|| invalid("must be an array"))?;
+        let path = operation
+            .get("path")
+            .and_then(PointerArg::from_zval)
+            .ok_or_else(|| invalid("must have a string \"path\""))?;
+        let value = || operation.get("value").ok_or_else(|| invalid("must have a \"value\""));
+        doc = match operation.get("op").and_then(Zval::str) {
+            Some("set") => doc.with(path, value()?)?,
+            Some("remove") => doc.without(path)?,
+            Some("push") => doc.push(path, value()?)?,
+            _ => return Err(invalid("must have an \"op\" of 'set', 'remove' or 'push'")),
+        };
+    }
+    Ok(doc.index.json.clone())
+}
+
+fn encode(value: &Zval) -> Result<String, JsonError> {
+    JsonEncoder::new(EncodeConfig::from_flags(None, None)).encode(value)
+}
+
+fn not_a_container(pointer: &str) -> PhpException {
+    format!("Value at '{}' is not an array or object", pointer).into()
+}
@@ -0,0 +1,120 @@
+//! Truncated renderings for logs and error reports, see `Codec::dump()`.
+//!
+//! The output is laid out like pretty-printed JSON, but containers beyond
+//! the depth limit are collapsed to `[… 12 items]` and long ones end in a
+//! `… 4,982 more items` line, so it is meant for reading, not parsing.
+
+use crate::error::JsonError;
+use crate::reader::{Event, Reader};
+use crate::writer::{JsonWriter, WriterConfig};
+
+/// Re-renders the JSON document `json`, showing `max_depth` levels of
+/// nesting and the first `max_items` elements of each container.
+pub(crate) fn render(json: &str, max_depth: usize, max_items: usize) -> Result<String, JsonError> {
+    let mut config = WriterConfig::pretty();
+    config.escaping.unescaped_slashes = true;
+    config.escaping.unescaped_unicode = true;
+    let mut reader = Reader::new(json);
+    let mut writer = JsonWriter::new(&config);
+    // Open containers: whether each is an object, and its elements so far.
+    let mut open: Vec<(bool, usize)> = Vec::new();
+
+    loop {
+        let depth = reader.depth();
+        let Some((_, event)) = reader.next_event().map_err(JsonError::read)? else {
+            break;
+        };
+        if let Some((object, written)) = open.last_mut() {
+            let starts_element = match event {
+                Event::EndObject | Event::EndArray => false,
+                Event::Key(_) => true,
+                _ => !*object,
+            };
+            if starts_element && *written == max_items {
+                let object = *object;
+                // Finish the element just begun, then count the rest.
+                if matches!(event, Event::BeginObject | Event::BeginArray) {
+                    count_rest(&mut reader, depth + 1, false)?;
+                }
+                let more = 1 + count_rest(&mut reader, depth, object)?;
+                writer.annotation(&format!("… {} more {}", grouped(more), noun(more)));
+                close(&mut writer, object);
+                open.pop();
+                continue;
+            }
+            if starts_element {
+                *written += 1;
+            }
+        }
+        match event {
+            Event::BeginObject | Event::BeginArray if depth >= max_depth => {
+                let object = event == Event::BeginObject;
+                match count_rest(&mut reader, depth + 1, object)? {
+                    0 => {
+                        writer.event(event).map_err(JsonError::syntax)?;
+                        close(&mut writer, object);
+                    }
+                    n => {
+                        let (open, close) = if object { ("{", "}") } else { ("[", "]") };
+                        writer.annotation(&format!("{}… {} {}{}", open, grouped(n), noun(n), close));
+                    }
+                }
+            }
+            Event::BeginObject | Event::BeginArray => {
+                open.push((event == Event::BeginObject, 0));
+                writer.event(event).map_err(JsonError::syntax)?;
+            }
+            Event::EndObject | Event::EndArray => {
+                open.pop();
+                writer.event(event).map_err(JsonError::syntax)?;
+            }
+            event => writer.event(event).map_err(JsonError::syntax)?,
+        }
+    }
+    Ok(writer.finish())
+}
+
+/// Consumes the rest of the container open at `depth`, including its
+/// closing bracket, and returns how many elements were left in it.
+fn count_rest(reader: &mut Reader, depth: usize, object: bool) -> Result<usize, JsonError> {
+    let mut count = 0;
+    loop {
+        let before = reader.depth();
+        let Some((_, event)) = reader.next_event().map_err(JsonError::read)? else {
+            return Ok(count);
+        };
+        if before != depth {
+            continue;
+        }
+        match event {
+            Event::EndObject | Event::EndArray => return Ok(count),
+            Event::Key(_) => count += 1,
+            _ if !object => count += 1,
+            _ => {}
+        }
+    }
+}
+
+fn close(writer: &mut JsonWriter, object: bool) {
+    match object {
+        true => writer.end_object(),
+        false => writer.end_array(),
+    }
+}
+
+fn noun(n: usize) -> &'static str {
+    if n == 1 { "item" } else { "items" }
+}
+
+/// `n` with thousands separators: `4,982`.
+fn grouped(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(digit);
+    }
+    out
+}
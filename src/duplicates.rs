@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use crate::pointer;
+use crate::reader::{string_content, Event, ReadError, Reader};
+
+/// A member name that occurs more than once in one object.
+pub(crate) struct DuplicateKey {
+    /// JSON Pointer (RFC 6901) of the object; `""` is the document root.
+    pub path: String,
+    /// The decoded name, so `"a"` and `"\u0061"` count as the same key.
+    pub key: String,
+    /// Byte offsets of every occurrence of the key token.
+    pub offsets: Vec<usize>,
+}
+
+struct Container {
+    path: String,
+    kind: Kind,
+}
+
+enum Kind {
+    /// Index of the next element.
+    Array(usize),
+    Object {
+        current: Option<String>,
+        seen: HashMap<String, usize>,
+        members: Vec<(String, Vec<usize>)>,
+    },
+}
+
+/// Lists repeated member names across every object in a raw document, in
+/// the order their first occurrence appears.
+pub(crate) fn find_duplicate_keys(json: &str) -> Result<Vec<DuplicateKey>, ReadError> {
+    let mut reader = Reader::new(json);
+    let mut stack: Vec<Container> = Vec::new();
+    let mut found = Vec::new();
+
+    while let Some((offset, event)) = reader.next_event()? {
+        match event {
+            Event::BeginObject | Event::BeginArray => {
+                let kind = if event == Event::BeginArray {
+                    Kind::Array(0)
+                } else {
+                    Kind::Object {
+                        current: None,
+                        seen: HashMap::new(),
+                        members: Vec::new(),
+                    }
+                };
+                let path = child_path(&mut stack);
+                stack.push(Container { path, kind });
+            }
+            Event::EndObject | Event::EndArray => {
+                if let Some(Container {
+                    path,
+                    kind: Kind::Object { members, .. },
+                }) = stack.pop()
+                {
+                    found.extend(members.into_iter().filter(|(_, offsets)| offsets.len() > 1).map(
+                        |(key, offsets)| DuplicateKey {
+                            path: path.clone(),
+                            key,
+                            offsets,
+                        },
+                    ));
+                }
+            }
+            Event::Key(raw) => {
+                let key = string_content(raw).map_err(|message| ReadError::new(message, offset))?;
+                if let Some(Container {
+                    kind: Kind::Object { current, seen, members },
+                    ..
+                }) = stack.last_mut()
+                {
+                    match seen.get(&key) {
+                        Some(&i) => members[i].1.push(offset),
+                        None => {
+                            seen.insert(key.clone(), members.len());
+                            members.push((key.clone(), vec![offset]));
+                        }
+                    }
+                    *current = Some(key);
+                }
+            }
+            Event::String(_) | Event::Number(_) | Event::Bool(_) | Event::Null => {
+                if let Some(Container {
+                    kind: Kind::Array(index),
+                    ..
+                }) = stack.last_mut()
+                {
+                    *index += 1;
+                }
+            }
+        }
+    }
+
+    found.sort_by_key(|duplicate| duplicate.offsets[0]);
+    Ok(found)
+}
+
+/// Path of the value starting now, advancing the parent array's index.
+fn child_path(stack: &mut [Container]) -> String {
+    let Some(parent) = stack.last_mut() else {
+        return String::new();
+    };
+
+    let segment = match &mut parent.kind {
+        Kind::Array(index) => {
+            *index += 1;
+            (*index - 1).to_string()
+        }
+        Kind::Object { current, .. } => pointer::escape(current.as_deref().unwrap_or_default()),
+    };
+    format!("{}/{}", parent.path, segment)
+}
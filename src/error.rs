@@ -0,0 +1,102 @@
+use std::fmt;
+
+use ext_php_rs::exception::PhpException;
+use ext_php_rs::zend::ce;
+
+use crate::reader::ReadError;
+use crate::{flags, pointer};
+
+/// A failure carrying one of the `JSON_ERROR_*` codes alongside a detailed
+/// message and the location it happened at.
+#[derive(Debug, Clone)]
+pub struct JsonError {
+    pub code: i64,
+    pub message: String,
+    /// JSON Pointer of the offending value, empty for the root or when
+    /// unknown. Built up one segment at a time as the error unwinds, so
+    /// successful calls never pay for it.
+    pub path: String,
+}
+
+impl JsonError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            path: String::new(),
+        }
+    }
+
+    /// Places the error inside the member or element `segment` of the
+    /// container it came out of.
+    pub fn at(mut self, segment: &str) -> Self {
+        self.path = format!("/{}{}", pointer::escape(segment), self.path);
+        self
+    }
+
+    pub fn syntax(detail: impl fmt::Display) -> Self {
+        Self::new(flags::ERROR_SYNTAX, format!("JSON syntax error: {}", detail))
+    }
+
+    /// Keeps the reader's code when it is more specific than a syntax error.
+    pub(crate) fn read(err: ReadError) -> Self {
+        if err.code == flags::ERROR_SYNTAX {
+            Self::syntax(err)
+        } else {
+            Self::new(err.code, err.to_string())
+        }
+    }
+
+    pub fn depth() -> Self {
+        Self::new(flags::ERROR_DEPTH, "Maximum nesting depth exceeded")
+    }
+
+    /// A failure reported by the engine itself rather than by the document;
+    /// ext/json has no dedicated code for these.
+    pub fn engine(err: ext_php_rs::error::Error) -> Self {
+        Self::new(flags::ERROR_STATE_MISMATCH, err.to_string())
+    }
+
+    /// The fixed text ext/json's `json_last_error_msg()` reports for a code,
+    /// plus wording for the extension's own codes.
+    pub fn native_message(code: i64) -> &'static str {
+        match code {
+            flags::ERROR_NONE => "No error",
+            flags::ERROR_DEPTH => "Maximum stack depth exceeded",
+            flags::ERROR_STATE_MISMATCH => "State mismatch (invalid or malformed JSON)",
+            flags::ERROR_CTRL_CHAR => "Control character error, possibly incorrectly encoded",
+            flags::ERROR_SYNTAX => "Syntax error",
+            flags::ERROR_UTF8 => "Malformed UTF-8 characters, possibly incorrectly encoded",
+            flags::ERROR_RECURSION => "Recursion detected",
+            flags::ERROR_INF_OR_NAN => "Inf and NaN cannot be JSON encoded",
+            flags::ERROR_UNSUPPORTED_TYPE => "Type is not supported",
+            flags::ERROR_INVALID_PROPERTY_NAME => "The decoded property name is invalid",
+            flags::ERROR_UTF16 => "Single unpaired UTF-16 surrogate in unicode escape",
+            flags::ERROR_INPUT_TOO_LARGE => "Input exceeds the maximum size",
+            flags::ERROR_DUPLICATE_KEY => "Duplicate object key",
+            flags::ERROR_INTEGER_OVERFLOW => "Integer out of range",
+            flags::ERROR_LIMIT_EXCEEDED => "Decode limit exceeded",
+            flags::ERROR_TIME_BUDGET => "Parse time budget exceeded",
+            flags::ERROR_MEMORY_LIMIT => "Document too large to decode",
+            flags::ERROR_SCHEMA_VIOLATION => "Document does not match the schema",
+            flags::ERROR_FLOAT_OVERFLOW => "Number out of float range",
+            _ => "Unknown error",
+        }
+    }
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)?;
+        if !self.path.is_empty() {
+            write!(f, " at {}", self.path)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<JsonError> for PhpException {
+    fn from(err: JsonError) -> Self {
+        PhpException::new(err.to_string(), err.code as i32, ce::exception())
+    }
+}
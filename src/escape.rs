@@ -0,0 +1,220 @@
+use crate::flags;
+use crate::reader::ReadError;
+
+/// Letter case of hex digits in `\uXXXX` escapes.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub(crate) enum HexCase {
+    /// What ext/json emits: lowercase, except the uppercase `JSON_HEX_TAG` escapes.
+    #[default]
+    Native,
+    Lower,
+    Upper,
+}
+
+/// String escaping choices, mirroring the `JSON_HEX_*` and `JSON_UNESCAPED_*`
+/// encode flags.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Escaping {
+    pub hex_tag: bool,
+    pub hex_amp: bool,
+    pub hex_apos: bool,
+    pub hex_quot: bool,
+    pub unescaped_slashes: bool,
+    pub unescaped_unicode: bool,
+    pub unescaped_line_terminators: bool,
+    pub hex_case: HexCase,
+}
+
+impl Escaping {
+    pub fn from_flags(flags: i64) -> Self {
+        Self {
+            hex_tag: (flags & flags::HEX_TAG) != 0,
+            hex_amp: (flags & flags::HEX_AMP) != 0,
+            hex_apos: (flags & flags::HEX_APOS) != 0,
+            hex_quot: (flags & flags::HEX_QUOT) != 0,
+            unescaped_slashes: (flags & flags::UNESCAPED_SLASHES) != 0,
+            unescaped_unicode: (flags & flags::UNESCAPED_UNICODE) != 0,
+            unescaped_line_terminators: (flags & flags::UNESCAPED_LINE_TERMINATORS) != 0,
+            hex_case: HexCase::Native,
+        }
+    }
+
+    /// Escapes only what RFC 8259 requires: quotes, backslashes and control
+    /// characters.
+    pub fn minimal() -> Self {
+        Self {
+            unescaped_slashes: true,
+            unescaped_unicode: true,
+            unescaped_line_terminators: true,
+            ..Self::default()
+        }
+    }
+}
+
+/// Writes `s` as a quoted JSON string.
+pub(crate) fn escape_into(out: &mut String, s: &str, escaping: &Escaping) {
+    out.push('"');
+    escape_bare_into(out, s, escaping);
+    out.push('"');
+}
+
+/// Writes the escaped contents of `s` without surrounding quotes.
+pub(crate) fn escape_bare_into(out: &mut String, s: &str, escaping: &Escaping) {
+    for c in s.chars() {
+        match c {
+            '"' if escaping.hex_quot => push_unicode_escape(out, 0x22, escaping),
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '/' if !escaping.unescaped_slashes => out.push_str("\\/"),
+            '<' | '>' if escaping.hex_tag => {
+                let unit = c as u16;
+                if escaping.hex_case == HexCase::Native {
+                    out.push_str(&format!("\\u{:04X}", unit));
+                } else {
+                    push_unicode_escape(out, unit, escaping);
+                }
+            }
+            '&' if escaping.hex_amp => push_unicode_escape(out, 0x26, escaping),
+            '\'' if escaping.hex_apos => push_unicode_escape(out, 0x27, escaping),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{08}' => out.push_str("\\b"),
+            '\u{0c}' => out.push_str("\\f"),
+            c if (c as u32) < 0x20 => push_unicode_escape(out, c as u16, escaping),
+            '\u{2028}' | '\u{2029}' if !escaping.unescaped_line_terminators => {
+                push_unicode_escape(out, c as u16, escaping)
+            }
+            c if !c.is_ascii() && !escaping.unescaped_unicode => {
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    push_unicode_escape(out, *unit, escaping);
+                }
+            }
+            c => out.push(c),
+        }
+    }
+}
+
+fn push_unicode_escape(out: &mut String, unit: u16, escaping: &Escaping) {
+    if escaping.hex_case == HexCase::Upper {
+        out.push_str(&format!("\\u{:04X}", unit));
+    } else {
+        out.push_str(&format!("\\u{:04x}", unit));
+    }
+}
+
+/// Handling of lone surrogate escapes and raw control characters in
+/// decoded strings.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum CharPolicy {
+    /// Fail with `JSON_ERROR_UTF16` or `JSON_ERROR_CTRL_CHAR`, as ext/json does.
+    #[default]
+    Reject,
+    /// Substitute U+FFFD.
+    Replace,
+    /// Keep control characters as they are and lone surrogates as their
+    /// `\uXXXX` escape text, the six characters as written. A lone
+    /// surrogate has no UTF-8 encoding, and decoded strings stay valid
+    /// UTF-8 rather than carrying WTF-8.
+    Pass,
+}
+
+/// Resolves JSON escape sequences in `s`, which carries no surrounding quotes.
+pub(crate) fn unescape(s: &str) -> Result<String, String> {
+    unescape_with(s, CharPolicy::Reject, CharPolicy::Pass).map_err(|e| e.to_string())
+}
+
+/// [`unescape`] with explicit policies for lone surrogates and control
+/// characters.
+pub(crate) fn unescape_with(
+    s: &str,
+    surrogates: CharPolicy,
+    controls: CharPolicy,
+) -> Result<String, ReadError> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.char_indices();
+
+    while let Some((pos, c)) = chars.next() {
+        if (c as u32) < 0x20 {
+            match controls {
+                CharPolicy::Reject => {
+                    return Err(ReadError::with_code(
+                        flags::ERROR_CTRL_CHAR,
+                        "Control character in string",
+                        pos,
+                    ));
+                }
+                CharPolicy::Replace => out.push('\u{FFFD}'),
+                CharPolicy::Pass => out.push(c),
+            }
+            continue;
+        }
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.next().map(|(_, c)| c) {
+            Some('"') => out.push('"'),
+            Some('\\') => out.push('\\'),
+            Some('/') => out.push('/'),
+            Some('b') => out.push('\u{08}'),
+            Some('f') => out.push('\u{0c}'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('u') => {
+                let code = match read_hex4(&mut chars, pos)? {
+                    high @ 0xD800..=0xDBFF => {
+                        // Only consume the next escape if it completes the pair.
+                        let mut ahead = chars.clone();
+                        let low = match (ahead.next(), ahead.next()) {
+                            (Some((_, '\\')), Some((_, 'u'))) => read_hex4(&mut ahead, pos)
+                                .ok()
+                                .filter(|low| (0xDC00..0xE000).contains(low)),
+                            _ => None,
+                        };
+                        low.map(|low| {
+                            chars = ahead;
+                            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                        })
+                    }
+                    0xDC00..=0xDFFF => None,
+                    code => Some(code),
+                };
+                match (code, surrogates) {
+                    (Some(code), _) => out.push(char::from_u32(code).unwrap_or('\u{FFFD}')),
+                    (None, CharPolicy::Reject) => {
+                        return Err(ReadError::with_code(
+                            flags::ERROR_UTF16,
+                            "Lone surrogate escape",
+                            pos,
+                        ));
+                    }
+                    (None, CharPolicy::Replace) => out.push('\u{FFFD}'),
+                    // The escape text itself, e.g. `\uD800`, not its WTF-8 bytes.
+                    (None, CharPolicy::Pass) => out.push_str(&s[pos..pos + 6]),
+                }
+            }
+            Some(other) => {
+                return Err(ReadError::new(format!("Invalid escape sequence '\\{}'", other), pos));
+            }
+            None => return Err(ReadError::new("Unterminated escape sequence", pos)),
+        }
+    }
+
+    Ok(out)
+}
+
+fn read_hex4(chars: &mut std::str::CharIndices, pos: usize) -> Result<u32, ReadError> {
+    let mut code = 0;
+    for _ in 0..4 {
+        let digit = chars
+            .next()
+            .and_then(|(_, c)| c.to_digit(16))
+            .ok_or_else(|| ReadError::new("Invalid unicode escape", pos))?;
+        code = code * 16 + digit;
+    }
+    Ok(code)
+}
@@ -0,0 +1,177 @@
+//! File access for `decodeFile()` and `encodeFile()`.
+//!
+//! Plain local files are memory-mapped for reading on 64-bit Unix targets,
+//! read into memory elsewhere, and written directly.
+//! Stream wrapper paths go through PHP's stream layer, as does everything
+//! while `open_basedir` is in effect, so PHP's own restrictions still apply.
+
+use std::io::Read;
+use std::ops::Deref;
+
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::Zval;
+use ext_php_rs::zend::ProcessGlobals;
+
+use crate::stream;
+
+/// A file's bytes, mapped or read into memory.
+pub(crate) enum Contents {
+    #[cfg(all(unix, target_pointer_width = "64"))]
+    Mapped(mmap::Mapping),
+    Owned(Vec<u8>),
+}
+
+impl Deref for Contents {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            #[cfg(all(unix, target_pointer_width = "64"))]
+            Self::Mapped(mapping) => mapping,
+            Self::Owned(bytes) => bytes,
+        }
+    }
+}
+
+pub(crate) fn read(path: &str) -> PhpResult<Contents> {
+    let Some(local) = local_path(path) else {
+        let handle = open(path, "rb")?;
+        let bytes = stream::read_all(&handle, 1);
+        stream::call("fclose", vec![&handle])?;
+        return Ok(Contents::Owned(bytes?));
+    };
+
+    let file = std::fs::File::open(local).map_err(|e| io_error(path, e))?;
+    #[cfg(all(unix, target_pointer_width = "64"))]
+    {
+        // FIFOs, character devices and procfs files report a length of 0
+        // or none at all, so only regular files with contents are mapped.
+        let metadata = file.metadata().map_err(|e| io_error(path, e))?;
+        if metadata.is_file() && metadata.len() > 0 {
+            return Ok(Contents::Mapped(mmap::Mapping::new(&file, metadata.len()).map_err(|e| io_error(path, e))?));
+        }
+    }
+    let mut bytes = Vec::new();
+    (&file).read_to_end(&mut bytes).map_err(|e| io_error(path, e))?;
+    Ok(Contents::Owned(bytes))
+}
+
+pub(crate) fn write(path: &str, data: &str) -> PhpResult<()> {
+    let Some(local) = local_path(path) else {
+        let handle = open(path, "wb")?;
+        let result = stream::write_all(&handle, data, 1);
+        stream::call("fclose", vec![&handle])?;
+        return result;
+    };
+
+    std::fs::write(local, data).map_err(|e| io_error(path, e))
+}
+
+/// The filesystem path to use directly, or `None` when PHP's stream layer
+/// must handle `path`.
+pub(crate) fn local_path(path: &str) -> Option<&str> {
+    if open_basedir_active() {
+        return None;
+    }
+    match path.split_once("://") {
+        Some(("file", local)) => Some(local),
+        Some((scheme, _)) if is_scheme(scheme) => None,
+        _ => Some(path),
+    }
+}
+
+/// RFC 3986 scheme characters, plus the `.` PHP allows in wrapper names
+/// such as `compress.zlib`.
+fn is_scheme(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic())
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+fn open_basedir_active() -> bool {
+    let globals = ProcessGlobals::get();
+    !globals.open_basedir.is_null() && unsafe { *globals.open_basedir } != 0
+}
+
+fn open(path: &str, mode: &str) -> PhpResult<Zval> {
+    let handle = stream::call("fopen", vec![&path, &mode])?;
+    if !handle.is_resource() {
+        return Err(format!("Failed to open {}", path).into());
+    }
+    Ok(handle)
+}
+
+pub(crate) fn io_error(path: &str, err: std::io::Error) -> PhpException {
+    format!("Failed to access {}: {}", path, err).into()
+}
+
+#[cfg(all(unix, target_pointer_width = "64"))]
+mod mmap {
+    use std::ffi::{c_int, c_void};
+    use std::fs::File;
+    use std::ops::Deref;
+    use std::os::fd::AsRawFd;
+
+    unsafe extern "C" {
+        // `offset` is an `off_t`, which is 64 bits wide on the 64-bit
+        // targets this module is limited to.
+        fn mmap(addr: *mut c_void, len: usize, prot: c_int, flags: c_int, fd: c_int, offset: i64) -> *mut c_void;
+        fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    }
+
+    const PROT_READ: c_int = 1;
+    const MAP_PRIVATE: c_int = 2;
+    const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+    /// A read-only private mapping of a whole regular file.
+    ///
+    /// A file truncated by another process while it is mapped raises
+    /// SIGBUS when the pages past its new end are touched, which takes
+    /// the PHP process down. Files that may be truncated while they are
+    /// decoded, such as logs being rotated, should be read with
+    /// `file_get_contents()` and passed to `decode()` instead.
+    pub(crate) struct Mapping {
+        ptr: *mut c_void,
+        len: usize,
+    }
+
+    impl Mapping {
+        /// Maps the first `len` bytes of `file`, its length when opened.
+        pub fn new(file: &File, len: u64) -> std::io::Result<Self> {
+            let len = usize::try_from(len).map_err(|_| std::io::Error::other("file too large to map"))?;
+            // Zero-length mappings are invalid; an empty file needs none.
+            if len == 0 {
+                return Ok(Self {
+                    ptr: std::ptr::null_mut(),
+                    len,
+                });
+            }
+
+            let ptr = unsafe {
+                mmap(std::ptr::null_mut(), len, PROT_READ, MAP_PRIVATE, file.as_raw_fd(), 0)
+            };
+            if ptr == MAP_FAILED {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(Self { ptr, len })
+        }
+    }
+
+    impl Deref for Mapping {
+        type Target = [u8];
+
+        fn deref(&self) -> &[u8] {
+            if self.len == 0 {
+                return &[];
+            }
+            unsafe { std::slice::from_raw_parts(self.ptr.cast(), self.len) }
+        }
+    }
+
+    impl Drop for Mapping {
+        fn drop(&mut self) {
+            if self.len > 0 {
+                unsafe { munmap(self.ptr, self.len) };
+            }
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Dropping members from a document as it streams through, for
+//! `Codec::filterKeys()`. No PHP values are built: tokens are copied from
+//! the input as written, so the output only differs by the members left
+//! out and by insignificant whitespace.
+
+use crate::error::JsonError;
+use crate::pointer::PointerArg;
+use crate::reader::{string_content, Event, Reader};
+
+/// One reference token of an exclusion pattern.
+enum Token {
+    Name(String),
+    /// `*`: any one member name or array index.
+    Any,
+    /// `**`: any number of levels, none included.
+    AnyDepth,
+}
+
+/// A JSON Pointer whose tokens may be `*` or `**` wildcards.
+pub(crate) struct Pattern(Vec<Token>);
+
+impl Pattern {
+    pub fn parse(pointer: &PointerArg) -> Result<Self, String> {
+        let tokens = pointer.tokens()?;
+        if tokens.is_empty() {
+            return Err("Cannot remove the document root".to_string());
+        }
+        Ok(Pattern(
+            tokens
+                .iter()
+                .map(|token| match token.as_str() {
+                    "*" => Token::Any,
+                    "**" => Token::AnyDepth,
+                    _ => Token::Name(token.clone()),
+                })
+                .collect(),
+        ))
+    }
+
+    fn matches(&self, path: &[String]) -> bool {
+        fn walk(pattern: &[Token], path: &[String]) -> bool {
+            match (pattern.split_first(), path.split_first()) {
+                (None, _) => path.is_empty(),
+                (Some((Token::AnyDepth, rest)), _) => {
+                    walk(rest, path) || (!path.is_empty() && walk(pattern, &path[1..]))
+                }
+                (Some(_), None) => false,
+                (Some((Token::Any, rest)), Some((_, tail))) => walk(rest, tail),
+                (Some((Token::Name(name), rest)), Some((token, tail))) => name == token && walk(rest, tail),
+            }
+        }
+        walk(&self.0, path)
+    }
+}
+
+/// An open container of the output.
+struct Frame {
+    object: bool,
+    has_items: bool,
+    /// Index of the next element of an array in the input.
+    next_index: usize,
+}
+
+/// Copies `json` without the members and array elements any of `exclude`
+/// matches.
+pub(crate) fn filter_keys(json: &str, exclude: &[Pattern]) -> Result<String, JsonError> {
+    let mut out = String::with_capacity(json.len());
+    let mut reader = Reader::new(json);
+    let mut frames: Vec<Frame> = Vec::new();
+    // Reference tokens of the value being copied.
+    let mut path: Vec<String> = Vec::new();
+    let excluded = |path: &[String]| exclude.iter().any(|pattern| pattern.matches(path));
+
+    while let Some((_, event)) = reader.next_event().map_err(JsonError::read)? {
+        let depth = reader.depth();
+        match (frames.last_mut(), event) {
+            (_, Event::EndObject | Event::EndArray) => {
+                frames.pop();
+                out.push(if event == Event::EndObject { '}' } else { ']' });
+                if !frames.is_empty() {
+                    path.pop();
+                }
+                continue;
+            }
+            (Some(frame), Event::Key(raw)) => {
+                path.push(match raw.contains('\\') {
+                    true => string_content(raw).map_err(JsonError::syntax)?,
+                    false => raw[1..raw.len() - 1].to_string(),
+                });
+                if excluded(&path) {
+                    reader.skip_value().map_err(JsonError::read)?;
+                    path.pop();
+                    continue;
+                }
+                if frame.has_items {
+                    out.push(',');
+                }
+                frame.has_items = true;
+                out.push_str(raw);
+                out.push(':');
+                continue;
+            }
+            (Some(frame), _) if !frame.object => {
+                path.push(frame.next_index.to_string());
+                frame.next_index += 1;
+                if excluded(&path) {
+                    // Skip the rest of a container element.
+                    if matches!(event, Event::BeginObject | Event::BeginArray) {
+                        while reader.depth() >= depth {
+                            reader.next_event().map_err(JsonError::read)?;
+                        }
+                    }
+                    path.pop();
+                    continue;
+                }
+                if frame.has_items {
+                    out.push(',');
+                }
+                frame.has_items = true;
+            }
+            _ => {}
+        }
+
+        match event {
+            Event::BeginObject | Event::BeginArray => {
+                let object = event == Event::BeginObject;
+                out.push(if object { '{' } else { '[' });
+                frames.push(Frame {
+                    object,
+                    has_items: false,
+                    next_index: 0,
+                });
+                continue;
+            }
+            Event::String(raw) | Event::Number(raw) => out.push_str(raw),
+            Event::Bool(b) => out.push_str(if b { "true" } else { "false" }),
+            Event::Null => out.push_str("null"),
+            Event::Key(_) | Event::EndObject | Event::EndArray => {}
+        }
+        if !frames.is_empty() {
+            path.pop();
+        }
+    }
+    Ok(out)
+}
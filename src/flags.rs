@@ -0,0 +1,79 @@
+//! Flag and error code values, identical to ext/json's `JSON_*` constants.
+
+pub(crate) const HEX_TAG: i64 = 1;
+pub(crate) const HEX_AMP: i64 = 2;
+pub(crate) const HEX_APOS: i64 = 4;
+pub(crate) const HEX_QUOT: i64 = 8;
+pub(crate) const FORCE_OBJECT: i64 = 16;
+pub(crate) const NUMERIC_CHECK: i64 = 32;
+pub(crate) const UNESCAPED_SLASHES: i64 = 64;
+pub(crate) const PRETTY_PRINT: i64 = 128;
+pub(crate) const UNESCAPED_UNICODE: i64 = 256;
+pub(crate) const PARTIAL_OUTPUT_ON_ERROR: i64 = 512;
+pub(crate) const PRESERVE_ZERO_FRACTION: i64 = 1024;
+pub(crate) const UNESCAPED_LINE_TERMINATORS: i64 = 2048;
+
+pub(crate) const OBJECT_AS_ARRAY: i64 = 1;
+pub(crate) const BIGINT_AS_STRING: i64 = 2;
+
+pub(crate) const INVALID_UTF8_IGNORE: i64 = 1048576;
+pub(crate) const INVALID_UTF8_SUBSTITUTE: i64 = 2097152;
+pub(crate) const THROW_ON_ERROR: i64 = 4194304;
+
+// Extension-specific flags, in bits ext/json does not use.
+pub(crate) const BINARY_AS_BASE64: i64 = 1 << 30;
+pub(crate) const COMPAT: i64 = 1 << 29;
+
+pub(crate) const ERROR_NONE: i64 = 0;
+pub(crate) const ERROR_DEPTH: i64 = 1;
+pub(crate) const ERROR_STATE_MISMATCH: i64 = 2;
+pub(crate) const ERROR_CTRL_CHAR: i64 = 3;
+pub(crate) const ERROR_SYNTAX: i64 = 4;
+pub(crate) const ERROR_UTF8: i64 = 5;
+pub(crate) const ERROR_RECURSION: i64 = 6;
+pub(crate) const ERROR_INF_OR_NAN: i64 = 7;
+pub(crate) const ERROR_UNSUPPORTED_TYPE: i64 = 8;
+pub(crate) const ERROR_INVALID_PROPERTY_NAME: i64 = 9;
+pub(crate) const ERROR_UTF16: i64 = 10;
+
+// Extension-specific error codes, numbered clear of ext/json's.
+pub(crate) const ERROR_INPUT_TOO_LARGE: i64 = 100;
+pub(crate) const ERROR_DUPLICATE_KEY: i64 = 101;
+pub(crate) const ERROR_INTEGER_OVERFLOW: i64 = 102;
+pub(crate) const ERROR_LIMIT_EXCEEDED: i64 = 103;
+pub(crate) const ERROR_TIME_BUDGET: i64 = 104;
+pub(crate) const ERROR_MEMORY_LIMIT: i64 = 105;
+pub(crate) const ERROR_SCHEMA_VIOLATION: i64 = 106;
+pub(crate) const ERROR_FLOAT_OVERFLOW: i64 = 107;
+
+/// Global constants defined at startup unless ext/json already provides them.
+pub(crate) const GLOBAL_CONSTANTS: &[(&str, i64)] = &[
+    ("JSON_HEX_TAG", HEX_TAG),
+    ("JSON_HEX_AMP", HEX_AMP),
+    ("JSON_HEX_APOS", HEX_APOS),
+    ("JSON_HEX_QUOT", HEX_QUOT),
+    ("JSON_FORCE_OBJECT", FORCE_OBJECT),
+    ("JSON_NUMERIC_CHECK", NUMERIC_CHECK),
+    ("JSON_UNESCAPED_SLASHES", UNESCAPED_SLASHES),
+    ("JSON_PRETTY_PRINT", PRETTY_PRINT),
+    ("JSON_UNESCAPED_UNICODE", UNESCAPED_UNICODE),
+    ("JSON_PARTIAL_OUTPUT_ON_ERROR", PARTIAL_OUTPUT_ON_ERROR),
+    ("JSON_PRESERVE_ZERO_FRACTION", PRESERVE_ZERO_FRACTION),
+    ("JSON_UNESCAPED_LINE_TERMINATORS", UNESCAPED_LINE_TERMINATORS),
+    ("JSON_OBJECT_AS_ARRAY", OBJECT_AS_ARRAY),
+    ("JSON_BIGINT_AS_STRING", BIGINT_AS_STRING),
+    ("JSON_INVALID_UTF8_IGNORE", INVALID_UTF8_IGNORE),
+    ("JSON_INVALID_UTF8_SUBSTITUTE", INVALID_UTF8_SUBSTITUTE),
+    ("JSON_THROW_ON_ERROR", THROW_ON_ERROR),
+    ("JSON_ERROR_NONE", ERROR_NONE),
+    ("JSON_ERROR_DEPTH", ERROR_DEPTH),
+    ("JSON_ERROR_STATE_MISMATCH", ERROR_STATE_MISMATCH),
+    ("JSON_ERROR_CTRL_CHAR", ERROR_CTRL_CHAR),
+    ("JSON_ERROR_SYNTAX", ERROR_SYNTAX),
+    ("JSON_ERROR_UTF8", ERROR_UTF8),
+    ("JSON_ERROR_RECURSION", ERROR_RECURSION),
+    ("JSON_ERROR_INF_OR_NAN", ERROR_INF_OR_NAN),
+    ("JSON_ERROR_UNSUPPORTED_TYPE", ERROR_UNSUPPORTED_TYPE),
+    ("JSON_ERROR_INVALID_PROPERTY_NAME", ERROR_INVALID_PROPERTY_NAME),
+    ("JSON_ERROR_UTF16", ERROR_UTF16),
+];
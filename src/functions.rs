@@ -0,0 +1,48 @@
+//! `Elephant\Json\*` functions, thin wrappers over the `Codec` methods for
+//! code that prefers `use function` imports.
+
+use ext_php_rs::binary::Binary;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::Zval;
+
+use crate::options::DecoderOptions;
+use crate::Codec;
+
+pub(crate) fn register(module: ModuleBuilder) -> ModuleBuilder {
+    module
+        .function(wrap_function!(encode))
+        .function(wrap_function!(decode))
+        .function(wrap_function!(validate))
+}
+
+#[php_function]
+#[php(name = "Elephant\\Json\\encode")]
+#[php(defaults(options = None, depth = None, replacer = None))]
+pub fn encode(
+    value: &Zval,
+    options: Option<&Zval>,
+    depth: Option<i64>,
+    replacer: Option<&Zval>,
+) -> PhpResult<String> {
+    Codec::encode(value, options, depth, replacer)
+}
+
+#[php_function]
+#[php(name = "Elephant\\Json\\decode")]
+#[php(defaults(options = None, depth = None, flags = None, reviver = None))]
+pub fn decode(
+    json: Binary<u8>,
+    options: Option<&Zval>,
+    depth: Option<i64>,
+    flags: Option<i64>,
+    reviver: Option<&Zval>,
+) -> PhpResult<Zval> {
+    Codec::decode(json, options, depth, flags, reviver)
+}
+
+#[php_function]
+#[php(name = "Elephant\\Json\\validate")]
+#[php(defaults(options = None))]
+pub fn validate(json: String, options: Option<&DecoderOptions>) -> bool {
+    Codec::validate(json, options)
+}
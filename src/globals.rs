@@ -0,0 +1,122 @@
+//! PHP module globals.
+//!
+//! Everything that lives for a request is kept in one [`Globals`] value
+//! owned by the engine: a single static instance on NTS builds, and one per
+//! thread on ZTS builds, allocated through TSRM with `ginit`/`gshutdown`
+//! as constructor and destructor. The [`DocumentCache`] is the exception:
+//! it is kept for the life of the worker, across requests.
+
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::rc::Rc;
+
+use crate::adapters::Adapter;
+use crate::cache::DocumentCache;
+use crate::error::JsonError;
+use crate::hydrate::Plan;
+use crate::options::{Callback, DecodeDefaults, EncoderOptions};
+
+#[derive(Default)]
+pub(crate) struct Globals {
+    pub last_error: Option<JsonError>,
+    pub encode_defaults: Option<EncoderOptions>,
+    pub decode_defaults: Option<DecodeDefaults>,
+    pub cache: DocumentCache,
+    pub adapters: Vec<Adapter>,
+    /// Hydration plans by lowercase class name.
+    pub class_plans: HashMap<String, Rc<Plan>>,
+    pub metrics_hook: Option<Callback>,
+    /// Set while the metrics hook runs.
+    pub in_metrics_hook: bool,
+}
+
+unsafe extern "C" fn ginit(globals: *mut c_void) {
+    unsafe { globals.cast::<Globals>().write(Globals::default()) };
+}
+
+unsafe extern "C" fn gshutdown(globals: *mut c_void) {
+    unsafe { globals.cast::<Globals>().drop_in_place() };
+}
+
+/// Runs `f` against the current thread's globals.
+///
+/// Callers must not nest calls; nothing in a closure passed here calls back
+/// into PHP.
+pub(crate) fn with<R>(f: impl FnOnce(&mut Globals) -> R) -> R {
+    f(unsafe { &mut *storage::current() })
+}
+
+/// Puts `value` in the field `field` selects and returns what it held.
+///
+/// Values that hold PHP callables or objects leave the globals this way:
+/// releasing them can run PHP destructors, which may call back in, so the
+/// old value is only dropped by the caller, once `with()` has returned.
+pub(crate) fn replace<T>(field: impl FnOnce(&mut Globals) -> &mut T, value: T) -> T {
+    with(|g| std::mem::replace(field(g), value))
+}
+
+pub(crate) use storage::{shutdown, startup};
+
+#[cfg(php_zts)]
+mod storage {
+    use std::ffi::{c_int, c_void};
+    use std::mem::size_of;
+
+    use super::{ginit, gshutdown, Globals};
+
+    type Ctor = unsafe extern "C" fn(*mut c_void);
+
+    unsafe extern "C" {
+        fn ts_allocate_id(id: *mut c_int, size: usize, ctor: Option<Ctor>, dtor: Option<Ctor>) -> c_int;
+        fn ts_free_id(id: c_int);
+        fn tsrm_get_ls_cache() -> *mut c_void;
+    }
+
+    /// TSRM resource id; written once during module startup.
+    static mut GLOBALS_ID: c_int = 0;
+
+    pub(crate) fn startup() {
+        unsafe {
+            ts_allocate_id(&raw mut GLOBALS_ID, size_of::<Globals>(), Some(ginit), Some(gshutdown));
+        }
+    }
+
+    pub(crate) fn shutdown() {
+        unsafe { ts_free_id(GLOBALS_ID) };
+    }
+
+    /// Equivalent of `TSRMG_BULK(id, type)`.
+    pub(super) fn current() -> *mut Globals {
+        unsafe {
+            let table = *tsrm_get_ls_cache().cast::<*mut *mut c_void>();
+            (*table.add(GLOBALS_ID as usize - 1)).cast()
+        }
+    }
+}
+
+#[cfg(not(php_zts))]
+mod storage {
+    use std::cell::UnsafeCell;
+    use std::mem::MaybeUninit;
+
+    use super::{ginit, gshutdown, Globals};
+
+    struct Slot(UnsafeCell<MaybeUninit<Globals>>);
+
+    // NTS builds run one request at a time on a single thread.
+    unsafe impl Sync for Slot {}
+
+    static GLOBALS: Slot = Slot(UnsafeCell::new(MaybeUninit::uninit()));
+
+    pub(crate) fn startup() {
+        unsafe { ginit(current().cast()) };
+    }
+
+    pub(crate) fn shutdown() {
+        unsafe { gshutdown(current().cast()) };
+    }
+
+    pub(super) fn current() -> *mut Globals {
+        GLOBALS.0.get().cast()
+    }
+}
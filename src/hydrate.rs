@@ -0,0 +1,260 @@
+//! Building instances of a declared class from decoded JSON, for
+//! `Codec::decodeAs()`.
+//!
+//! What the hydrator needs to know about a class (its constructor
+//! parameters, public properties and their class types) is read through
+//! Reflection the first time the class is hydrated and kept as a [`Plan`]
+//! until the end of the request, so hydrating many objects of one class
+//! reflects it once. Plans are not kept across requests, as user classes
+//! themselves are not.
+
+use std::rc::Rc;
+
+use ext_php_rs::convert::{IntoZval, IntoZvalDyn};
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ZendHashTable, ZendObject, Zval};
+use ext_php_rs::zend::{ce, ClassEntry, ExecutorGlobals};
+
+use crate::{adapters, globals, pointer};
+
+/// `ReflectionProperty::IS_PUBLIC`.
+const IS_PUBLIC: i64 = 1;
+
+pub(crate) struct Plan {
+    class: &'static ClassEntry,
+    name: String,
+    /// Parameters of the constructor, in order; `None` without one.
+    constructor: Option<Vec<Slot>>,
+    /// Public properties set after construction: neither static, readonly
+    /// nor promoted by the constructor.
+    properties: Vec<Slot>,
+}
+
+/// A constructor parameter or property, filled from the member of the
+/// same name.
+struct Slot {
+    name: String,
+    /// The class the member is hydrated as, when declared with one.
+    class: Option<String>,
+    /// What a parameter gets when the member is missing.
+    default: Option<Zval>,
+    nullable: bool,
+}
+
+/// Builds an instance of `class` from `value`, a decoded object or
+/// associative array. A deserializer registered for `class` takes
+/// precedence over its plan.
+pub(crate) fn hydrate(value: &Zval, class: &str) -> PhpResult<Zval> {
+    match build(value, class, "") {
+        // The engine throws the exception PHP code raised once the call
+        // returns.
+        Err(_) if ExecutorGlobals::has_exception() => Ok(Zval::new()),
+        result => result,
+    }
+}
+
+fn build(value: &Zval, class: &str, path: &str) -> PhpResult<Zval> {
+    if let Some(deserialize) = adapters::deserializer(class) {
+        return Ok(deserialize.call(vec![value])?);
+    }
+    let plan = plan(class)?;
+    if value.object().is_some_and(|object| object.instance_of(plan.class)) {
+        return Ok(value.shallow_clone());
+    }
+    let members = match (value.array(), value.object()) {
+        (Some(array), _) => array,
+        (None, Some(object)) => object.get_properties()?,
+        _ => {
+            return Err(type_error(format!(
+                "Cannot hydrate {} from {} at {}",
+                plan.name,
+                value.get_type(),
+                location(path)
+            )))
+        }
+    };
+
+    let mut object = ZendObject::new(plan.class);
+    if let Some(parameters) = &plan.constructor {
+        let mut args = Vec::with_capacity(parameters.len());
+        for slot in parameters {
+            args.push(match (members.get(slot.name.as_str()), &slot.default) {
+                (Some(member), _) => member_value(member, slot, path)?,
+                (None, Some(default)) => default.shallow_clone(),
+                (None, None) if slot.nullable => Zval::new(),
+                (None, None) => {
+                    return Err(type_error(format!(
+                        "Cannot hydrate {} at {}: missing member \"{}\"",
+                        plan.name,
+                        location(path),
+                        slot.name
+                    )))
+                }
+            });
+        }
+        call(&object, "__construct", args.iter().map(|arg| arg as &dyn IntoZvalDyn).collect())?;
+    }
+    for slot in &plan.properties {
+        if let Some(member) = members.get(slot.name.as_str()) {
+            let value = member_value(member, slot, path)?;
+            object.set_property(&slot.name, value)?;
+            thrown()?;
+        }
+    }
+    Ok(object.into_zval(false)?)
+}
+
+/// A member as passed to its parameter or property: hydrated when the
+/// slot declares a class, as decoded otherwise.
+fn member_value(member: &Zval, slot: &Slot, path: &str) -> PhpResult<Zval> {
+    match &slot.class {
+        Some(class) if !member.is_null() => build(member, class, &format!("{}/{}", path, pointer::escape(&slot.name))),
+        _ => Ok(member.shallow_clone()),
+    }
+}
+
+/// The plan for `class`, compiled on first use in the request.
+fn plan(class: &str) -> PhpResult<Rc<Plan>> {
+    let key = class.trim_start_matches('\\').to_ascii_lowercase();
+    if let Some(plan) = globals::with(|g| g.class_plans.get(&key).cloned()) {
+        return Ok(plan);
+    }
+    // Compiled outside `globals::with()`: resolving the class may
+    // autoload it, and reflection runs PHP.
+    let plan = Rc::new(compile(class)?);
+    let _replaced = globals::with(|g| g.class_plans.insert(key, plan.clone()));
+    Ok(plan)
+}
+
+pub(crate) fn reset() {
+    let _plans = globals::with(|g| std::mem::take(&mut g.class_plans));
+}
+
+fn compile(class: &str) -> PhpResult<Plan> {
+    let Some(entry) = ClassEntry::try_find(class.trim_start_matches('\\')) else {
+        return Err(value_error(format!("Class {} does not exist", class)));
+    };
+    let reflection = ZendObject::new(reflection_class()?);
+    call(&reflection, "__construct", vec![&class.to_string()])?;
+    let name = string(&reflection, "getName")?;
+    if !flag(&reflection, "isInstantiable")? {
+        return Err(value_error(format!("{} cannot be instantiated", name)));
+    }
+
+    let constructor = match call(&reflection, "getConstructor", vec![])?.object() {
+        Some(method) => Some(parameters(method, &name)?),
+        None => None,
+    };
+    let mut properties = Vec::new();
+    for property in objects(&call(&reflection, "getProperties", vec![&IS_PUBLIC])?) {
+        if flag(property, "isStatic")? || flag(property, "isReadOnly")? || flag(property, "isPromoted")? {
+            continue;
+        }
+        let (class, nullable) = declared_type(property, &name)?;
+        properties.push(Slot {
+            name: string(property, "getName")?,
+            class,
+            default: None,
+            nullable,
+        });
+    }
+    Ok(Plan {
+        class: entry,
+        name,
+        constructor,
+        properties,
+    })
+}
+
+/// The slots for a constructor's parameters, up to the first that can
+/// only be left out along with the rest.
+fn parameters(method: &ZendObject, class: &str) -> PhpResult<Vec<Slot>> {
+    let mut slots = Vec::new();
+    for parameter in objects(&call(method, "getParameters", vec![])?) {
+        let default = match flag(parameter, "isDefaultValueAvailable")? {
+            true => Some(call(parameter, "getDefaultValue", vec![])?),
+            false => None,
+        };
+        if default.is_none() && flag(parameter, "isOptional")? {
+            break;
+        }
+        let (class_type, nullable) = declared_type(parameter, class)?;
+        slots.push(Slot {
+            name: string(parameter, "getName")?,
+            class: class_type,
+            default,
+            nullable,
+        });
+    }
+    Ok(slots)
+}
+
+/// The class a parameter or property is declared with, if it names
+/// exactly one, and whether it accepts `null`.
+fn declared_type(reflector: &ZendObject, class: &str) -> PhpResult<(Option<String>, bool)> {
+    let declared = call(reflector, "getType", vec![])?;
+    let Some(declared) = declared.object() else {
+        return Ok((None, true));
+    };
+    let nullable = flag(declared, "allowsNull")?;
+    // Union and intersection types name no single class to build.
+    if !declared.get_class_name()?.eq_ignore_ascii_case("ReflectionNamedType") || flag(declared, "isBuiltin")? {
+        return Ok((None, nullable));
+    }
+    let name = string(declared, "getName")?;
+    Ok(match name.to_ascii_lowercase().as_str() {
+        "self" | "static" => (Some(class.to_string()), nullable),
+        "parent" => (None, nullable),
+        _ => (Some(name), nullable),
+    })
+}
+
+fn reflection_class() -> PhpResult<&'static ClassEntry> {
+    // Always there since PHP 8.0, where ext/reflection can no longer be
+    // disabled.
+    ClassEntry::try_find("ReflectionClass").ok_or_else(|| value_error("ext/reflection is not loaded".to_string()))
+}
+
+/// The objects in an array returned by Reflection.
+fn objects(array: &Zval) -> impl Iterator<Item = &ZendObject> {
+    array.array().into_iter().flat_map(ZendHashTable::values).filter_map(Zval::object)
+}
+
+/// Calls `method` on `object`, failing if it threw.
+fn call(object: &ZendObject, method: &str, args: Vec<&dyn IntoZvalDyn>) -> PhpResult<Zval> {
+    let value = object.try_call_method(method, args)?;
+    thrown()?;
+    Ok(value)
+}
+
+fn string(object: &ZendObject, method: &str) -> PhpResult<String> {
+    Ok(call(object, method, vec![])?.string().unwrap_or_default())
+}
+
+fn flag(object: &ZendObject, method: &str) -> PhpResult<bool> {
+    Ok(call(object, method, vec![])?.bool().unwrap_or(false))
+}
+
+/// Stops hydrating once PHP code has thrown; `hydrate()` leaves the
+/// exception for the engine to throw.
+fn thrown() -> PhpResult<()> {
+    match ExecutorGlobals::has_exception() {
+        true => Err(PhpException::default(String::new())),
+        false => Ok(()),
+    }
+}
+
+fn location(path: &str) -> String {
+    match path {
+        "" => "the document root".to_string(),
+        path => format!("\"{}\"", path),
+    }
+}
+
+fn value_error(message: String) -> PhpException {
+    PhpException::new(message, 0, ce::value_error())
+}
+
+fn type_error(message: String) -> PhpException {
+    PhpException::new(message, 0, ce::type_error())
+}
@@ -0,0 +1,37 @@
+use ext_php_rs::zend::ModuleEntry;
+use ext_php_rs::{info_table_end, info_table_header, info_table_row, info_table_start};
+
+use crate::ini;
+
+pub(crate) const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Capabilities compiled into this build, for `Json::features()` and phpinfo.
+pub(crate) fn features() -> Vec<&'static str> {
+    vec!["pretty_color", "normalize", "inspect", "columns"]
+}
+
+pub(crate) fn build_type() -> &'static str {
+    if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    }
+}
+
+pub(crate) extern "C" fn php_module_info(_module: *mut ModuleEntry) {
+    info_table_start!();
+    info_table_row!("elephant_json support", "enabled");
+    info_table_row!("Version", VERSION);
+    info_table_row!("Features", features().join(", "));
+    info_table_row!("Build", build_type());
+    info_table_row!("Target", format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS));
+    info_table_row!("Thread safety", if ext_php_rs::PHP_ZTS { "enabled" } else { "disabled" });
+    info_table_end!();
+
+    info_table_start!();
+    info_table_header!("Directive", "Value");
+    for name in ini::ALL {
+        info_table_row!(*name, ini::get_string(name).unwrap_or_default());
+    }
+    info_table_end!();
+}
@@ -0,0 +1,102 @@
+use ext_php_rs::ffi::zend_ini_entry;
+use ext_php_rs::flags::IniEntryPermission;
+use ext_php_rs::zend::{ExecutorGlobals, IniEntryDef};
+
+pub(crate) const DEFAULT_DEPTH: &str = "elephant_json.default_depth";
+pub(crate) const DEFAULT_DECODE_FLAGS: &str = "elephant_json.default_decode_flags";
+pub(crate) const DEFAULT_ENCODE_FLAGS: &str = "elephant_json.default_encode_flags";
+pub(crate) const BUFFER_SIZE: &str = "elephant_json.buffer_size";
+pub(crate) const REPLACE_NATIVE: &str = "elephant_json.replace_native";
+pub(crate) const MAX_INPUT_BYTES: &str = "elephant_json.max_input_bytes";
+pub(crate) const MEMORY_GUARD: &str = "elephant_json.memory_guard";
+pub(crate) const CACHE_MAX_BYTES: &str = "elephant_json.cache_max_bytes";
+
+/// Every registered entry, in phpinfo() display order.
+pub(crate) const ALL: &[&str] = &[
+    DEFAULT_DEPTH,
+    DEFAULT_DECODE_FLAGS,
+    DEFAULT_ENCODE_FLAGS,
+    BUFFER_SIZE,
+    MAX_INPUT_BYTES,
+    MEMORY_GUARD,
+    CACHE_MAX_BYTES,
+    REPLACE_NATIVE,
+];
+
+pub(crate) fn register(module_number: i32) {
+    let entry = |name: &str, value: &str| {
+        IniEntryDef::new(name.to_owned(), value.to_owned(), &IniEntryPermission::All)
+    };
+    let system_entry = |name: &str, value: &str| {
+        IniEntryDef::new(name.to_owned(), value.to_owned(), &IniEntryPermission::System)
+    };
+
+    IniEntryDef::register(
+        vec![
+            entry(DEFAULT_DEPTH, "512"),
+            entry(DEFAULT_DECODE_FLAGS, "0"),
+            entry(DEFAULT_ENCODE_FLAGS, "0"),
+            entry(BUFFER_SIZE, "4096"),
+            entry(MAX_INPUT_BYTES, "0"),
+            entry(MEMORY_GUARD, "0"),
+            system_entry(CACHE_MAX_BYTES, "16777216"),
+            system_entry(REPLACE_NATIVE, "0"),
+        ],
+        module_number,
+    );
+}
+
+pub(crate) fn default_depth() -> i64 {
+    get_long(DEFAULT_DEPTH).unwrap_or(512)
+}
+
+pub(crate) fn default_decode_flags() -> i64 {
+    get_long(DEFAULT_DECODE_FLAGS).unwrap_or(0)
+}
+
+pub(crate) fn default_encode_flags() -> i64 {
+    get_long(DEFAULT_ENCODE_FLAGS).unwrap_or(0)
+}
+
+pub(crate) fn buffer_size() -> usize {
+    get_long(BUFFER_SIZE)
+        .and_then(|size| usize::try_from(size).ok())
+        .unwrap_or(4096)
+}
+
+/// Decode input size limit; `0` disables it.
+pub(crate) fn max_input_bytes() -> Option<usize> {
+    get_long(MAX_INPUT_BYTES)
+        .and_then(|bytes| usize::try_from(bytes).ok())
+}
+
+/// Whether decoding checks its projected memory use against `memory_limit`.
+pub(crate) fn memory_guard() -> bool {
+    get_long(MEMORY_GUARD).unwrap_or(0) != 0
+}
+
+/// Total input size `Codec::decodeCached()` keeps per worker.
+pub(crate) fn cache_max_bytes() -> usize {
+    get_long(CACHE_MAX_BYTES)
+        .and_then(|bytes| usize::try_from(bytes).ok())
+        .unwrap_or(16 * 1024 * 1024)
+}
+
+/// PHP's own `serialize_precision`, which ext/json formats floats with.
+pub(crate) fn serialize_precision() -> i64 {
+    get_long("serialize_precision").unwrap_or(-1)
+}
+
+pub(crate) fn get_long(name: &str) -> Option<i64> {
+    get_string(name)?.trim().parse().ok()
+}
+
+/// Reads the current value of a registered INI entry.
+pub(crate) fn get_string(name: &str) -> Option<String> {
+    let globals = ExecutorGlobals::get();
+    let directives = unsafe { globals.ini_directives.as_ref() }?;
+    let entry = directives.get(name)?;
+    let entry = unsafe { entry.ptr::<zend_ini_entry>()?.as_ref() }?;
+    let value = unsafe { entry.value.as_ref() }?;
+    value.as_str().ok().map(str::to_owned)
+}
@@ -0,0 +1,78 @@
+use crate::reader::{Event, ReadError, Reader};
+
+/// Shape statistics gathered in a single pass over a raw document.
+#[derive(Default)]
+pub(crate) struct DocumentStats {
+    pub objects: u64,
+    pub arrays: u64,
+    pub keys: u64,
+    pub strings: u64,
+    pub numbers: u64,
+    pub booleans: u64,
+    pub nulls: u64,
+    pub max_depth: u64,
+    /// Decoded byte length of the longest string value.
+    pub longest_string: u64,
+    /// Element count of the largest array.
+    pub largest_array: u64,
+}
+
+impl DocumentStats {
+    pub fn scalars(&self) -> u64 {
+        self.strings + self.numbers + self.booleans + self.nulls
+    }
+}
+
+pub(crate) fn inspect(json: &str) -> Result<DocumentStats, ReadError> {
+    let mut reader = Reader::new(json);
+    let mut stats = DocumentStats::default();
+    // Element counts of the open containers; `None` for objects.
+    let mut counts: Vec<Option<u64>> = Vec::new();
+
+    while let Some((offset, event)) = reader.next_event()? {
+        let is_element = !matches!(event, Event::Key(_) | Event::EndArray | Event::EndObject);
+        if let (true, Some(Some(count))) = (is_element, counts.last_mut()) {
+            *count += 1;
+        }
+
+        match event {
+            Event::BeginObject => {
+                stats.objects += 1;
+                counts.push(None);
+            }
+            Event::BeginArray => {
+                stats.arrays += 1;
+                counts.push(Some(0));
+            }
+            Event::EndObject => {
+                counts.pop();
+            }
+            Event::EndArray => {
+                if let Some(Some(count)) = counts.pop() {
+                    stats.largest_array = stats.largest_array.max(count);
+                }
+            }
+            Event::Key(_) => stats.keys += 1,
+            Event::String(raw) => {
+                stats.strings += 1;
+                let len = decoded_len(raw).map_err(|message| ReadError::new(message, offset))?;
+                stats.longest_string = stats.longest_string.max(len);
+            }
+            Event::Number(_) => stats.numbers += 1,
+            Event::Bool(_) => stats.booleans += 1,
+            Event::Null => stats.nulls += 1,
+        }
+
+        stats.max_depth = stats.max_depth.max(reader.depth() as u64);
+    }
+
+    Ok(stats)
+}
+
+fn decoded_len(raw: &str) -> Result<u64, String> {
+    let inner = &raw[1..raw.len() - 1];
+    if !inner.contains('\\') {
+        return Ok(inner.len() as u64);
+    }
+    crate::escape::unescape(inner).map(|s| s.len() as u64)
+}
@@ -0,0 +1,811 @@
+//! Amazon Ion text, transcoded to and from JSON text like UBJSON so that
+//! both directions go through the same encoder and decoder, and with them
+//! every encode and decode option.
+//!
+//! Ion types JSON lacks are mapped onto the nearest JSON value: symbols
+//! and timestamps become strings as written, blobs their base64 text,
+//! clobs their text, s-expressions lists and typed nulls `null`. Decimals
+//! and floats become numbers. Annotations survive as [`Annotated`]
+//! wrappers, carried through the JSON text as an object with the members
+//! [`ANNOTATIONS`] and [`VALUE`], whose names no Ion field may take.
+
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::Zval;
+
+use crate::error::JsonError;
+use crate::flags;
+use crate::reader::{is_number, string_content, Event, Reader, MAX_NESTING};
+use crate::writer::{JsonWriter, WriterConfig};
+
+pub(crate) const ANNOTATIONS: &str = "\0ion_annotations";
+pub(crate) const VALUE: &str = "\0ion_value";
+
+/// An Ion value together with its annotations, as `Codec::decodeIon()`
+/// returns annotated values and `Codec::encodeIon()` writes them. The JSON
+/// encoder writes the value alone.
+#[php_class]
+#[php(name = "Elephant\\Json\\Annotated")]
+pub struct Annotated {
+    annotations: Vec<String>,
+    value: Zval,
+}
+
+#[php_impl]
+impl Annotated {
+    pub fn __construct(annotations: Vec<String>, value: &Zval) -> Self {
+        Self::new(annotations, value.shallow_clone())
+    }
+
+    pub fn annotations(&self) -> Vec<String> {
+        self.annotations.clone()
+    }
+
+    pub fn value(&self) -> Zval {
+        self.value.shallow_clone()
+    }
+}
+
+impl Annotated {
+    pub(crate) fn new(annotations: Vec<String>, value: Zval) -> Self {
+        Self { annotations, value }
+    }
+
+    pub(crate) fn annotations_ref(&self) -> &[String] {
+        &self.annotations
+    }
+
+    pub(crate) fn value_ref(&self) -> &Zval {
+        &self.value
+    }
+}
+
+/// An open container of the JSON input.
+struct Frame {
+    object: bool,
+    /// An annotation envelope, which has no Ion counterpart of its own.
+    envelope: bool,
+    has_items: bool,
+}
+
+/// Converts a JSON document to Ion text. Numbers with a fraction or an
+/// exponent are written as Ion floats, since a bare `1.5` would be read
+/// back as a decimal. Strings keep their characters unescaped, as Ion has
+/// no surrogate pairs.
+pub(crate) fn from_json(json: &str) -> Result<String, JsonError> {
+    let mut out = String::with_capacity(json.len());
+    let mut reader = Reader::new(json);
+    let mut stack: Vec<Frame> = Vec::new();
+    // Whether a `{` was read but not yet written, as it may open an
+    // annotation envelope.
+    let mut pending = false;
+
+    while let Some((_, event)) = reader.next_event().map_err(JsonError::read)? {
+        if std::mem::take(&mut pending) {
+            let envelope = match event {
+                Event::Key(raw) => string_content(raw).map_err(JsonError::syntax)? == ANNOTATIONS,
+                _ => false,
+            };
+            if envelope {
+                write_annotations(&mut reader, &mut out)?;
+                stack.push(Frame {
+                    object: true,
+                    envelope: true,
+                    has_items: false,
+                });
+                continue;
+            }
+            out.push('{');
+            stack.push(Frame {
+                object: true,
+                envelope: false,
+                has_items: false,
+            });
+        }
+
+        match event {
+            Event::EndObject | Event::EndArray => {
+                if let Some(frame) = stack.pop().filter(|frame| !frame.envelope) {
+                    out.push(if frame.object { '}' } else { ']' });
+                }
+                continue;
+            }
+            Event::Key(raw) => {
+                let Some(frame) = stack.last_mut() else {
+                    return Err(JsonError::syntax("expected value"));
+                };
+                if frame.envelope {
+                    if string_content(raw).map_err(JsonError::syntax)? != VALUE {
+                        return Err(malformed_envelope());
+                    }
+                    continue;
+                }
+                if frame.has_items {
+                    out.push(',');
+                }
+                frame.has_items = true;
+                write_string(&mut out, &string_content(raw).map_err(JsonError::syntax)?, '"');
+                out.push(':');
+                continue;
+            }
+            _ => {}
+        }
+
+        if let Some(frame) = stack.last_mut().filter(|frame| !frame.object) {
+            if frame.has_items {
+                out.push(',');
+            }
+            frame.has_items = true;
+        }
+        match event {
+            Event::BeginObject => pending = true,
+            Event::BeginArray => {
+                out.push('[');
+                stack.push(Frame {
+                    object: false,
+                    envelope: false,
+                    has_items: false,
+                });
+            }
+            Event::String(raw) => write_string(&mut out, &string_content(raw).map_err(JsonError::syntax)?, '"'),
+            Event::Number(raw) if raw.contains(['e', 'E']) => out.push_str(raw),
+            Event::Number(raw) if raw.contains('.') => {
+                out.push_str(raw);
+                out.push_str("e0");
+            }
+            Event::Number(raw) => out.push_str(raw),
+            Event::Bool(b) => out.push_str(if b { "true" } else { "false" }),
+            Event::Null => out.push_str("null"),
+            Event::Key(_) | Event::EndObject | Event::EndArray => {}
+        }
+    }
+    Ok(out)
+}
+
+/// Writes the annotations listed in an envelope as `name::` prefixes.
+fn write_annotations(reader: &mut Reader, out: &mut String) -> Result<(), JsonError> {
+    if reader.next_event().map_err(JsonError::read)?.map(|(_, event)| event) != Some(Event::BeginArray) {
+        return Err(malformed_envelope());
+    }
+    loop {
+        match reader.next_event().map_err(JsonError::read)? {
+            Some((_, Event::String(raw))) => {
+                write_symbol(out, &string_content(raw).map_err(JsonError::syntax)?);
+                out.push_str("::");
+            }
+            Some((_, Event::EndArray)) => return Ok(()),
+            _ => return Err(malformed_envelope()),
+        }
+    }
+}
+
+fn malformed_envelope() -> JsonError {
+    JsonError::syntax("malformed Ion annotations")
+}
+
+/// Writes `text` as an identifier where Ion allows one, and quoted
+/// otherwise. Identifiers such as `$10` would be read as symbol ids.
+fn write_symbol(out: &mut String, text: &str) {
+    let identifier = text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && text.chars().all(is_identifier_char)
+        && !matches!(text, "null" | "true" | "false" | "nan");
+    match identifier {
+        true => out.push_str(text),
+        false => write_string(out, text, '\''),
+    }
+}
+
+/// Writes `text` between `quote`s, escaping only what Ion requires.
+fn write_string(out: &mut String, text: &str, quote: char) {
+    out.push(quote);
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c == quote => {
+                out.push('\\');
+                out.push(c);
+            }
+            c if (c as u32) < 0x20 || c == '\x7F' => out.push_str(&format!("\\x{:02x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push(quote);
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '$'
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Characters that make up operator symbols inside s-expressions.
+fn is_operator_char(b: u8) -> bool {
+    b"!#%&*+-./;<=>?@^`|~".contains(&b)
+}
+
+/// Converts an Ion text document holding a single value to JSON text of
+/// at most `max_len` bytes. The `$ion_1_0` version marker and local symbol
+/// tables are skipped; symbol ids such as `$10` are not resolved.
+pub(crate) fn to_json(text: &str, max_len: Option<usize>) -> Result<String, JsonError> {
+    let config = WriterConfig::compact();
+    let mut writer = JsonWriter::new(&config);
+    let mut parser = Parser {
+        text,
+        bytes: text.as_bytes(),
+        pos: 0,
+        depth: 0,
+        max_len,
+    };
+    let mut found = false;
+
+    loop {
+        parser.skip_space()?;
+        if parser.pos == parser.bytes.len() {
+            break;
+        }
+        let start = parser.pos;
+        let annotations = parser.annotations()?;
+        if annotations.is_empty() && parser.keyword("$ion_1_0") {
+            parser.pos += "$ion_1_0".len();
+            continue;
+        }
+        if annotations.first().is_some_and(|name| name == "$ion_symbol_table") {
+            let sink_config = WriterConfig::compact();
+            parser.value(&mut JsonWriter::new(&sink_config), Vec::new())?;
+            continue;
+        }
+        if found {
+            return Err(parser.error_at("Expected a single top-level value", start));
+        }
+        parser.value(&mut writer, annotations)?;
+        found = true;
+    }
+    if !found {
+        return Err(parser.error("Expected a value"));
+    }
+    parser.check_len(&writer)?;
+    Ok(writer.finish())
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+    depth: usize,
+    max_len: Option<usize>,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> JsonError {
+        self.error_at(message, self.pos)
+    }
+
+    fn error_at(&self, message: &str, offset: usize) -> JsonError {
+        JsonError::new(flags::ERROR_SYNTAX, format!("Ion syntax error: {} at offset {}", message, offset))
+    }
+
+    fn check_len(&self, writer: &JsonWriter) -> Result<(), JsonError> {
+        match self.max_len.filter(|max| writer.len() > *max) {
+            Some(max) => Err(JsonError::new(
+                flags::ERROR_INPUT_TOO_LARGE,
+                format!("Ion expands to more than the maximum of {} bytes of JSON", max),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn starts_with(&self, prefix: &str) -> bool {
+        self.text[self.pos..].starts_with(prefix)
+    }
+
+    /// Whether the identifier `word` comes next, as a whole token.
+    fn keyword(&self, word: &str) -> bool {
+        self.starts_with(word)
+            && !self
+                .text[self.pos + word.len()..]
+                .starts_with(is_identifier_char)
+    }
+
+    /// Skips whitespace and comments.
+    fn skip_space(&mut self) -> Result<(), JsonError> {
+        loop {
+            match self.peek() {
+                Some(b' ' | b'\t' | b'\n' | b'\r' | 0x0B | 0x0C) => self.pos += 1,
+                Some(b'/') if self.starts_with("//") => {
+                    self.pos = self.text[self.pos..].find('\n').map_or(self.bytes.len(), |i| self.pos + i);
+                }
+                Some(b'/') if self.starts_with("/*") => match self.text[self.pos + 2..].find("*/") {
+                    Some(i) => self.pos += i + 4,
+                    None => return Err(self.error("Unterminated comment")),
+                },
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Reads any `name::` annotations before a value.
+    fn annotations(&mut self) -> Result<Vec<String>, JsonError> {
+        let mut annotations = Vec::new();
+        loop {
+            let start = self.pos;
+            let symbol = match self.peek() {
+                Some(b'\'') if !self.starts_with("'''") => self.quoted_symbol()?,
+                Some(b) if is_identifier_start(b as char) => self.identifier().to_string(),
+                _ => return Ok(annotations),
+            };
+            self.skip_space()?;
+            if !self.starts_with("::") {
+                self.pos = start;
+                return Ok(annotations);
+            }
+            self.pos += 2;
+            self.skip_space()?;
+            annotations.push(symbol);
+        }
+    }
+
+    /// Writes the value at the current position, wrapped in an envelope
+    /// if it has annotations.
+    fn value(&mut self, writer: &mut JsonWriter, annotations: Vec<String>) -> Result<(), JsonError> {
+        if annotations.is_empty() {
+            return self.bare_value(writer);
+        }
+        writer.begin_object();
+        writer.key(ANNOTATIONS);
+        writer.begin_array();
+        for annotation in &annotations {
+            writer.string(annotation);
+        }
+        writer.end_array();
+        writer.key(VALUE);
+        self.bare_value(writer)?;
+        writer.end_object();
+        Ok(())
+    }
+
+    fn bare_value(&mut self, writer: &mut JsonWriter) -> Result<(), JsonError> {
+        match self.peek() {
+            Some(b'{') if self.starts_with("{{") => self.lob(writer),
+            Some(b'{') => self.container(writer, b'}'),
+            Some(b'[') => self.container(writer, b']'),
+            Some(b'(') => self.container(writer, b')'),
+            Some(b'"') => {
+                let text = self.short_string(b'"')?;
+                writer.string(&text);
+                Ok(())
+            }
+            Some(b'\'') if self.starts_with("'''") => {
+                let text = self.long_strings()?;
+                writer.string(&text);
+                Ok(())
+            }
+            Some(b'\'') => {
+                let symbol = self.quoted_symbol()?;
+                writer.string(&symbol);
+                Ok(())
+            }
+            Some(b'0'..=b'9') => self.number(writer),
+            Some(b'-' | b'+') if self.starts_with("+inf") || self.starts_with("-inf") => Err(not_finite()),
+            Some(b'-') if self.bytes.get(self.pos + 1).is_some_and(u8::is_ascii_digit) => self.number(writer),
+            Some(b) if is_identifier_start(b as char) => {
+                match self.identifier() {
+                    "null" => {
+                        // A typed null such as `null.int`.
+                        if self.peek() == Some(b'.') {
+                            self.pos += 1;
+                            if self.identifier().is_empty() {
+                                return Err(self.error("Expected a type after 'null.'"));
+                            }
+                        }
+                        writer.null();
+                    }
+                    "true" => writer.bool(true),
+                    "false" => writer.bool(false),
+                    "nan" => return Err(not_finite()),
+                    symbol => writer.string(symbol),
+                }
+                Ok(())
+            }
+            Some(_) => Err(self.error("Unexpected character")),
+            None => Err(self.error("Unexpected end of input")),
+        }
+    }
+
+    /// Reads a struct, list or s-expression up to `close`.
+    fn container(&mut self, writer: &mut JsonWriter, close: u8) -> Result<(), JsonError> {
+        self.depth += 1;
+        if self.depth > MAX_NESTING {
+            return Err(JsonError::depth());
+        }
+        self.pos += 1;
+        match close {
+            b'}' => writer.begin_object(),
+            _ => writer.begin_array(),
+        }
+        loop {
+            self.skip_space()?;
+            if self.peek() == Some(close) {
+                self.pos += 1;
+                break;
+            }
+            if close == b'}' {
+                let name = self.field_name()?;
+                if name == ANNOTATIONS || name == VALUE {
+                    return Err(self.error("Reserved field name"));
+                }
+                self.skip_space()?;
+                if self.peek() != Some(b':') || self.starts_with("::") {
+                    return Err(self.error("Expected ':'"));
+                }
+                self.pos += 1;
+                self.skip_space()?;
+                writer.key(&name);
+            }
+            let annotations = self.annotations()?;
+            match self.peek() {
+                // An operator symbol such as `+` or `<=`, outside numbers.
+                Some(b) if close == b')' && is_operator_char(b) && !self.number_ahead() => {
+                    if !annotations.is_empty() {
+                        return Err(self.error("Annotated operator"));
+                    }
+                    let start = self.pos;
+                    while self.peek().is_some_and(is_operator_char) {
+                        self.pos += 1;
+                    }
+                    writer.string(&self.text[start..self.pos]);
+                }
+                _ => self.value(writer, annotations)?,
+            }
+            self.check_len(writer)?;
+            if close == b')' {
+                continue;
+            }
+            self.skip_space()?;
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b) if b == close => {}
+                _ => return Err(self.error("Expected ',' or closing bracket")),
+            }
+        }
+        match close {
+            b'}' => writer.end_object(),
+            _ => writer.end_array(),
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+
+    /// Whether a number, rather than an operator, starts here.
+    fn number_ahead(&self) -> bool {
+        self.peek() == Some(b'-') && self.bytes.get(self.pos + 1).is_some_and(u8::is_ascii_digit)
+            || self.starts_with("+inf")
+            || self.starts_with("-inf")
+    }
+
+    fn field_name(&mut self) -> Result<String, JsonError> {
+        match self.peek() {
+            Some(b'"') => self.short_string(b'"'),
+            Some(b'\'') if self.starts_with("'''") => self.long_strings(),
+            Some(b'\'') => self.quoted_symbol(),
+            Some(b) if is_identifier_start(b as char) => Ok(self.identifier().to_string()),
+            _ => Err(self.error("Expected a field name")),
+        }
+    }
+
+    fn identifier(&mut self) -> &str {
+        let start = self.pos;
+        while self.peek().is_some_and(|b| is_identifier_char(b as char)) {
+            self.pos += 1;
+        }
+        &self.text[start..self.pos]
+    }
+
+    fn quoted_symbol(&mut self) -> Result<String, JsonError> {
+        self.short_string(b'\'')
+    }
+
+    /// A `"string"` or `'symbol'`, which cannot span lines.
+    fn short_string(&mut self, quote: u8) -> Result<String, JsonError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut out = Vec::new();
+        loop {
+            match self.peek() {
+                None | Some(b'\n' | b'\r') => return Err(self.error_at("Unterminated string", start)),
+                Some(b) if b == quote => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(b'\\') => self.escape(&mut out, false)?,
+                Some(b) => {
+                    out.push(b);
+                    self.pos += 1;
+                }
+            }
+        }
+        self.utf8(out, start)
+    }
+
+    /// One or more `'''long strings'''`, concatenated as Ion requires.
+    fn long_strings(&mut self) -> Result<String, JsonError> {
+        let start = self.pos;
+        let mut out = Vec::new();
+        loop {
+            self.long_string(&mut out, false)?;
+            let end = self.pos;
+            self.skip_space()?;
+            if !self.starts_with("'''") {
+                self.pos = end;
+                break;
+            }
+        }
+        self.utf8(out, start)
+    }
+
+    fn long_string(&mut self, out: &mut Vec<u8>, clob: bool) -> Result<(), JsonError> {
+        let start = self.pos;
+        self.pos += 3;
+        loop {
+            match self.peek() {
+                None => return Err(self.error_at("Unterminated string", start)),
+                Some(b'\'') if self.starts_with("'''") => {
+                    self.pos += 3;
+                    return Ok(());
+                }
+                Some(b'\\') => self.escape(out, clob)?,
+                Some(b) => {
+                    out.push(b);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    /// Appends what the escape sequence at the current position stands
+    /// for. `\xHH` is a byte in a clob and a code point elsewhere.
+    fn escape(&mut self, out: &mut Vec<u8>, clob: bool) -> Result<(), JsonError> {
+        let start = self.pos;
+        let Some(&b) = self.bytes.get(self.pos + 1) else {
+            return Err(self.error_at("Unterminated string", start));
+        };
+        self.pos += 2;
+        let c = match b {
+            b'a' => '\x07',
+            b'b' => '\x08',
+            b't' => '\t',
+            b'n' => '\n',
+            b'f' => '\x0C',
+            b'r' => '\r',
+            b'v' => '\x0B',
+            b'0' => '\0',
+            b'?' | b'\'' | b'"' | b'/' | b'\\' => b as char,
+            // A line continuation.
+            b'\n' => return Ok(()),
+            b'\r' => {
+                if self.peek() == Some(b'\n') {
+                    self.pos += 1;
+                }
+                return Ok(());
+            }
+            b'x' => {
+                let code = self.hex(2, start)?;
+                if clob {
+                    out.push(code as u8);
+                    return Ok(());
+                }
+                char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+            }
+            b'u' => {
+                let code = self.hex(4, start)?;
+                let code = match code {
+                    0xD800..=0xDBFF if self.starts_with("\\u") => {
+                        self.pos += 2;
+                        let low = self.hex(4, start)?;
+                        if !(0xDC00..=0xDFFF).contains(&low) {
+                            return Err(JsonError::new(flags::ERROR_UTF16, "Unpaired surrogate in Ion string"));
+                        }
+                        0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00)
+                    }
+                    code => code,
+                };
+                char::from_u32(code)
+                    .ok_or_else(|| JsonError::new(flags::ERROR_UTF16, "Unpaired surrogate in Ion string"))?
+            }
+            b'U' => {
+                let code = self.hex(8, start)?;
+                char::from_u32(code).ok_or_else(|| self.error_at("Invalid code point", start))?
+            }
+            _ => return Err(self.error_at("Invalid escape", start)),
+        };
+        let mut buf = [0; 4];
+        out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        Ok(())
+    }
+
+    fn hex(&mut self, len: usize, start: usize) -> Result<u32, JsonError> {
+        let digits = self
+            .text
+            .get(self.pos..self.pos + len)
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error_at("Invalid escape", start))?;
+        self.pos += len;
+        u32::from_str_radix(digits, 16).map_err(|_| self.error_at("Invalid escape", start))
+    }
+
+    fn utf8(&self, bytes: Vec<u8>, start: usize) -> Result<String, JsonError> {
+        String::from_utf8(bytes).map_err(|_| {
+            JsonError::new(flags::ERROR_UTF8, format!("Malformed UTF-8 in Ion string at offset {}", start))
+        })
+    }
+
+    /// A `{{ base64 }}` blob, kept as its base64 text, or a `{{ "clob" }}`.
+    fn lob(&mut self, writer: &mut JsonWriter) -> Result<(), JsonError> {
+        let start = self.pos;
+        self.pos += 2;
+        let skip_blanks = |parser: &mut Self| {
+            while parser.peek().is_some_and(|b| b.is_ascii_whitespace() || b == 0x0B) {
+                parser.pos += 1;
+            }
+        };
+        skip_blanks(self);
+        let text = match self.peek() {
+            Some(b'"') => {
+                let mut out = Vec::new();
+                self.pos += 1;
+                loop {
+                    match self.peek() {
+                        None | Some(b'\n' | b'\r') => return Err(self.error_at("Unterminated clob", start)),
+                        Some(b'"') => {
+                            self.pos += 1;
+                            break;
+                        }
+                        Some(b'\\') => self.escape(&mut out, true)?,
+                        Some(b) => {
+                            out.push(b);
+                            self.pos += 1;
+                        }
+                    }
+                }
+                self.utf8(out, start)?
+            }
+            Some(b'\'') if self.starts_with("'''") => {
+                let mut out = Vec::new();
+                loop {
+                    self.long_string(&mut out, true)?;
+                    skip_blanks(self);
+                    if !self.starts_with("'''") {
+                        break;
+                    }
+                }
+                self.utf8(out, start)?
+            }
+            _ => {
+                let mut base64 = String::new();
+                while let Some(b) = self.peek().filter(|&b| b != b'}') {
+                    match b {
+                        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'+' | b'/' | b'=' => base64.push(b as char),
+                        b if b.is_ascii_whitespace() || b == 0x0B => {}
+                        _ => return Err(self.error("Invalid character in blob")),
+                    }
+                    self.pos += 1;
+                }
+                base64
+            }
+        };
+        skip_blanks(self);
+        if !self.starts_with("}}") {
+            return Err(self.error("Expected '}}'"));
+        }
+        self.pos += 2;
+        writer.string(&text);
+        Ok(())
+    }
+
+    /// An integer, decimal, float or timestamp.
+    fn number(&mut self, writer: &mut JsonWriter) -> Result<(), JsonError> {
+        let start = self.pos;
+        self.pos += 1;
+        while self
+            .peek()
+            .is_some_and(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'.' | b'+' | b'-' | b':'))
+        {
+            // `+` and `-` only continue exponents, timestamps and offsets.
+            if matches!(self.peek(), Some(b'+' | b'-'))
+                && !self.bytes[self.pos - 1].is_ascii_alphanumeric()
+            {
+                break;
+            }
+            self.pos += 1;
+        }
+        let token = &self.text[start..self.pos];
+        let invalid = || self.error_at("Invalid number", start);
+
+        let digits = token.strip_prefix('-').unwrap_or(token);
+        let is_timestamp = digits.len() > 4
+            && token.len() == digits.len()
+            && digits.as_bytes()[..4].iter().all(u8::is_ascii_digit)
+            && matches!(digits.as_bytes()[4], b'-' | b'T');
+        if is_timestamp {
+            writer.string(token);
+            return Ok(());
+        }
+
+        let radix = match digits.get(..2) {
+            Some("0x" | "0X") => 16,
+            Some("0b" | "0B") => 2,
+            _ => 10,
+        };
+        let body = if radix == 10 { digits } else { &digits[2..] };
+        let bytes = body.as_bytes();
+        let underscores_ok = bytes.iter().enumerate().all(|(i, &b)| {
+            b != b'_'
+                || (i > 0
+                    && bytes[i - 1].is_ascii_hexdigit()
+                    && bytes.get(i + 1).is_some_and(u8::is_ascii_hexdigit))
+        });
+        if body.is_empty() || !underscores_ok {
+            return Err(invalid());
+        }
+        let body = body.replace('_', "");
+        let negative = token.len() != digits.len();
+
+        let mut number = String::with_capacity(token.len() + 2);
+        if negative {
+            number.push('-');
+        }
+        if radix != 10 {
+            number.push_str(&to_decimal(&body, radix).ok_or_else(invalid)?);
+        } else {
+            // `1.` and `1.e5` are Ion decimals; JSON wants a fraction digit.
+            let body = body.replace(['d', 'D'], "e");
+            match body.find('.') {
+                Some(dot) if !body[dot + 1..].starts_with(|c: char| c.is_ascii_digit()) => {
+                    number.push_str(&body[..=dot]);
+                    number.push('0');
+                    number.push_str(&body[dot + 1..]);
+                }
+                _ => number.push_str(&body),
+            }
+        }
+        if !is_number(&number) {
+            return Err(invalid());
+        }
+        writer.number(&number);
+        Ok(())
+    }
+}
+
+fn not_finite() -> JsonError {
+    JsonError::new(flags::ERROR_INF_OR_NAN, "Ion nan and inf cannot be represented in JSON")
+}
+
+/// The decimal digits of `digits` in base `radix`, of any length.
+pub(crate) fn to_decimal(digits: &str, radix: u32) -> Option<String> {
+    const BASE: u64 = 1_000_000_000;
+    // Little-endian limbs of nine decimal digits.
+    let mut limbs: Vec<u64> = vec![0];
+    for c in digits.chars() {
+        let mut carry = u64::from(c.to_digit(radix)?);
+        for limb in &mut limbs {
+            let value = *limb * u64::from(radix) + carry;
+            *limb = value % BASE;
+            carry = value / BASE;
+        }
+        if carry > 0 {
+            limbs.push(carry);
+        }
+    }
+    let mut out = limbs.last().map(u64::to_string).unwrap_or_default();
+    for limb in limbs.iter().rev().skip(1) {
+        out.push_str(&format!("{:09}", limb));
+    }
+    Some(out)
+}
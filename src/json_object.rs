@@ -0,0 +1,138 @@
+//! Decoded objects readable both as arrays and as objects, see
+//! `DecoderOptions::jsonObjects()`.
+
+use ext_php_rs::boxed::ZBox;
+use ext_php_rs::error::php_error;
+use ext_php_rs::flags::ErrorType;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ZendHashTable, ZendObject, Zval};
+use ext_php_rs::zend::{ce, ClassEntry};
+
+/// A JSON object whose members can be read as `$object['name']` or
+/// `$object->name`, counted and iterated, so callers need not care which
+/// decode mode produced it. It encodes back to a JSON object, `{}` when
+/// empty.
+///
+/// PHP reserves `Object` as a class name, hence `JsonObject`.
+#[php_class]
+#[php(name = "Elephant\\Json\\JsonObject")]
+#[php(implements(ce = ce::arrayaccess, stub = "\\ArrayAccess"))]
+#[php(implements(ce = ce::countable, stub = "\\Countable"))]
+#[php(implements(ce = ce::aggregate, stub = "\\IteratorAggregate"))]
+pub struct JsonObject {
+    members: ZBox<ZendHashTable>,
+}
+
+#[php_impl]
+impl JsonObject {
+    #[php(defaults(members = None))]
+    pub fn __construct(members: Option<&ZendHashTable>) -> PhpResult<Self> {
+        let mut table = ZendHashTable::new();
+        for (key, value) in members.into_iter().flat_map(ZendHashTable::iter) {
+            table.insert(key.to_string(), value.shallow_clone())?;
+        }
+        Ok(Self::new(table))
+    }
+
+    pub fn offset_exists(&self, offset: &Zval) -> PhpResult<bool> {
+        Ok(self.isset(&name(offset)?))
+    }
+
+    pub fn offset_get(&self, offset: &Zval) -> PhpResult<Zval> {
+        let name = name(offset)?;
+        Ok(self.get(&name).unwrap_or_else(|| {
+            php_error(&ErrorType::Warning, &format!("Undefined array key \"{}\"", name));
+            Zval::new()
+        }))
+    }
+
+    pub fn offset_set(&mut self, offset: &Zval, value: &Zval) -> PhpResult<()> {
+        if offset.is_null() {
+            return Err(PhpException::new(
+                "Cannot append to Elephant\\Json\\JsonObject, members need a name".to_string(),
+                0,
+                ce::type_error(),
+            ));
+        }
+        Ok(self.members.insert(name(offset)?, value.shallow_clone())?)
+    }
+
+    pub fn offset_unset(&mut self, offset: &Zval) -> PhpResult<()> {
+        self.members.remove(name(offset)?);
+        Ok(())
+    }
+
+    pub fn __get(&self, name: String) -> Zval {
+        self.get(&name).unwrap_or_else(|| {
+            php_error(&ErrorType::Warning, &format!("Undefined property: Elephant\\Json\\JsonObject::${}", name));
+            Zval::new()
+        })
+    }
+
+    pub fn __set(&mut self, name: String, value: &Zval) -> PhpResult<()> {
+        Ok(self.members.insert(name, value.shallow_clone())?)
+    }
+
+    pub fn __isset(&self, name: String) -> bool {
+        self.isset(&name)
+    }
+
+    pub fn __unset(&mut self, name: String) {
+        self.members.remove(name);
+    }
+
+    pub fn count(&self) -> i64 {
+        self.members.len() as i64
+    }
+
+    /// Iterates over a snapshot of the members, so members can be set or
+    /// removed inside the loop.
+    pub fn get_iterator(&self) -> PhpResult<ZBox<ZendObject>> {
+        let ce = ClassEntry::try_find("ArrayIterator").ok_or("ArrayIterator is not available")?;
+        let iterator = ZendObject::new(ce);
+        let mut storage = Zval::new();
+        storage.set_hashtable(self.members.to_owned());
+        iterator.try_call_method("__construct", vec![&storage])?;
+        Ok(iterator)
+    }
+
+    /// The members as an associative array; nested objects stay
+    /// `JsonObject` instances.
+    pub fn to_array(&self) -> ZBox<ZendHashTable> {
+        self.members.to_owned()
+    }
+}
+
+impl JsonObject {
+    pub(crate) fn new(members: ZBox<ZendHashTable>) -> Self {
+        Self { members }
+    }
+
+    pub(crate) fn members(&self) -> &ZendHashTable {
+        &self.members
+    }
+
+    fn get(&self, name: &str) -> Option<Zval> {
+        self.members.get(name).map(Zval::shallow_clone)
+    }
+
+    /// Whether the member exists and is not `null`, as `isset()` expects.
+    fn isset(&self, name: &str) -> bool {
+        self.members.get(name).is_some_and(|value| !value.is_null())
+    }
+}
+
+/// The member name an array offset refers to.
+fn name(offset: &Zval) -> PhpResult<String> {
+    if let Some(n) = offset.long() {
+        return Ok(n.to_string());
+    }
+    match offset.str() {
+        Some(name) => Ok(name.to_string()),
+        None => Err(PhpException::new(
+            format!("Cannot access offset of type {} on Elephant\\Json\\JsonObject", offset.get_type()),
+            0,
+            ce::type_error(),
+        )),
+    }
+}
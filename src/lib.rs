@@ -1,85 +1,1263 @@
 #![cfg_attr(windows, feature(abi_vectorcall))]
 use ext_php_rs::prelude::*;
-use ext_php_rs::types::{Zval, ZendHashTable};
-use serde_json::{Value, Map};
+use ext_php_rs::binary::Binary;
+use ext_php_rs::boxed::ZBox;
+use ext_php_rs::class::RegisteredClass;
+use ext_php_rs::constant::IntoConst;
+use ext_php_rs::convert::IntoZval;
+use ext_php_rs::error::php_error;
+use ext_php_rs::flags::ErrorType;
+use ext_php_rs::types::{ZendCallable, Zval, ZendHashTable, ZendObject};
+use ext_php_rs::zend::{ce, ClassEntry, ExecutorGlobals};
+use std::cell::RefCell;
+use std::collections::HashMap;
 
+mod adapters;
+mod base64url;
+mod budget;
+mod cache;
+mod charset;
+mod checkpoint;
+mod columns;
+mod compression;
+mod cursor;
+mod diff;
+mod document;
+mod dump;
+mod duplicates;
+mod error;
+mod escape;
+mod file;
+mod filter;
+mod flags;
+mod functions;
+mod globals;
+mod hydrate;
+mod info;
+mod ini;
+mod inspect;
+mod ion;
+mod json_object;
+mod lines;
+mod lookup;
+mod merge;
+mod metrics;
+mod native;
+mod number;
+mod options;
+mod pacing;
+mod pointer;
+mod raw_number;
+mod reader;
+mod repair;
+mod schema;
+mod skip;
+mod smile;
+mod state;
+mod stream;
+mod template;
+mod tokens;
+mod truncated;
+mod ubjson;
+mod value;
+mod wrap;
+mod writer;
+
+use budget::Budget;
+use cursor::ArrayCursor;
+use document::Document;
+use error::JsonError;
+use escape::{CharPolicy, Escaping, HexCase};
+use options::{Callback, DecodeDefaults, DecoderOptions, DuplicateKeys, EmptyArrays, EncoderOptions, FloatPolicy, IntOverflow, NonFinite, ObjectMode, Pace};
+use pacing::Pacer;
+use pointer::{Pointer, PointerArg};
+use raw_number::Number;
+use reader::{decode_string, is_number, Event, Reader, MAX_NESTING};
+use schema::Schema;
+use skip::Skip;
+use ion::Annotated;
+use json_object::JsonObject;
+use lines::{LinesReader, LinesWriter};
+use template::Template;
+use tokens::Tokens;
+use truncated::Truncated;
+use value::Value;
+use wrap::{AsList, AsObject};
+use number::{FloatFormat, Numeric};
+use writer::{ArrayShape, JsonWriter, Palette, Style, WriterConfig};
+
+#[php_class]
+#[php(name = "Elephant\\Json\\Codec")]
+#[derive(Default)]
+pub struct Codec;
+
+/// The global `Json` class, kept as an empty subclass of
+/// `Elephant\Json\Codec` so existing `Json::...` call sites keep working.
 #[php_class]
+#[php(name = "Json")]
+#[php(extends(ce = codec_ce, stub = "\\Elephant\\Json\\Codec"))]
 #[derive(Default)]
 pub struct Json;
 
+fn codec_ce() -> &'static ClassEntry {
+    Codec::get_metadata().ce()
+}
+
 #[php_impl]
-impl Json {
-    pub fn decode(json: String, as_array: Option<bool>, depth: Option<i64>) -> PhpResult<Zval> {
-        let config = DecodeConfig {
-            as_array: as_array.unwrap_or(false),
-            max_depth: depth.unwrap_or(512),
+impl Codec {
+    const HEX_TAG: i64 = flags::HEX_TAG;
+    const HEX_AMP: i64 = flags::HEX_AMP;
+    const HEX_APOS: i64 = flags::HEX_APOS;
+    const HEX_QUOT: i64 = flags::HEX_QUOT;
+    const FORCE_OBJECT: i64 = flags::FORCE_OBJECT;
+    const NUMERIC_CHECK: i64 = flags::NUMERIC_CHECK;
+    const UNESCAPED_SLASHES: i64 = flags::UNESCAPED_SLASHES;
+    const PRETTY_PRINT: i64 = flags::PRETTY_PRINT;
+    const UNESCAPED_UNICODE: i64 = flags::UNESCAPED_UNICODE;
+    const PARTIAL_OUTPUT_ON_ERROR: i64 = flags::PARTIAL_OUTPUT_ON_ERROR;
+    const PRESERVE_ZERO_FRACTION: i64 = flags::PRESERVE_ZERO_FRACTION;
+    const UNESCAPED_LINE_TERMINATORS: i64 = flags::UNESCAPED_LINE_TERMINATORS;
+    const OBJECT_AS_ARRAY: i64 = flags::OBJECT_AS_ARRAY;
+    const BIGINT_AS_STRING: i64 = flags::BIGINT_AS_STRING;
+    const INVALID_UTF8_IGNORE: i64 = flags::INVALID_UTF8_IGNORE;
+    const INVALID_UTF8_SUBSTITUTE: i64 = flags::INVALID_UTF8_SUBSTITUTE;
+    const THROW_ON_ERROR: i64 = flags::THROW_ON_ERROR;
+    const BINARY_AS_BASE64: i64 = flags::BINARY_AS_BASE64;
+    const COMPAT: i64 = flags::COMPAT;
+
+    const ERROR_NONE: i64 = flags::ERROR_NONE;
+    const ERROR_DEPTH: i64 = flags::ERROR_DEPTH;
+    const ERROR_STATE_MISMATCH: i64 = flags::ERROR_STATE_MISMATCH;
+    const ERROR_CTRL_CHAR: i64 = flags::ERROR_CTRL_CHAR;
+    const ERROR_SYNTAX: i64 = flags::ERROR_SYNTAX;
+    const ERROR_UTF8: i64 = flags::ERROR_UTF8;
+    const ERROR_RECURSION: i64 = flags::ERROR_RECURSION;
+    const ERROR_INF_OR_NAN: i64 = flags::ERROR_INF_OR_NAN;
+    const ERROR_UNSUPPORTED_TYPE: i64 = flags::ERROR_UNSUPPORTED_TYPE;
+    const ERROR_INVALID_PROPERTY_NAME: i64 = flags::ERROR_INVALID_PROPERTY_NAME;
+    const ERROR_UTF16: i64 = flags::ERROR_UTF16;
+    const ERROR_INPUT_TOO_LARGE: i64 = flags::ERROR_INPUT_TOO_LARGE;
+    const ERROR_DUPLICATE_KEY: i64 = flags::ERROR_DUPLICATE_KEY;
+    const ERROR_INTEGER_OVERFLOW: i64 = flags::ERROR_INTEGER_OVERFLOW;
+    const ERROR_LIMIT_EXCEEDED: i64 = flags::ERROR_LIMIT_EXCEEDED;
+    const ERROR_TIME_BUDGET: i64 = flags::ERROR_TIME_BUDGET;
+    const ERROR_MEMORY_LIMIT: i64 = flags::ERROR_MEMORY_LIMIT;
+    const ERROR_SCHEMA_VIOLATION: i64 = flags::ERROR_SCHEMA_VIOLATION;
+    const ERROR_FLOAT_OVERFLOW: i64 = flags::ERROR_FLOAT_OVERFLOW;
+
+    /// `$as_array` takes a bool or an `Elephant\Json\DecoderOptions`
+    /// instance; `$flags` only applies without the latter.
+    ///
+    /// `$reviver` is called as `($key, $value, $pointer)` for every value,
+    /// children before their parents and the root last with key `""`; its
+    /// return value takes the place of `$value`.
+    #[php(defaults(as_array = None, depth = None, flags = None, reviver = None))]
+    pub fn decode(
+        json: Binary<u8>,
+        as_array: Option<&Zval>,
+        depth: Option<i64>,
+        flags: Option<i64>,
+        reviver: Option<&Zval>,
+    ) -> PhpResult<Zval> {
+        let options = DecoderOptions::from_arg(as_array, flags, 2, "as_array")?;
+        let mut config = DecodeConfig::new(options, depth);
+        config.reviver = callback(reviver, 5, "reviver")?;
+        Ok(metrics::measure("decode", || decode_bytes(&json, config), |_| json.len())?)
+    }
+
+    /// Decodes everything left in a stream resource or PSR-7 stream.
+    #[php(defaults(as_array = None, depth = None, flags = None))]
+    pub fn decode_stream(
+        stream: &Zval,
+        as_array: Option<&Zval>,
+        depth: Option<i64>,
+        flags: Option<i64>,
+    ) -> PhpResult<Zval> {
+        let options = DecoderOptions::from_arg(as_array, flags, 2, "as_array")?;
+        let bytes = stream::read_all(stream, 1)?;
+        Ok(decode_bytes(&bytes, DecodeConfig::new(options, depth))?)
+    }
+
+    /// Like `decode()`, but keeps the decoded value for later requests
+    /// served by the same worker, so decoding the same document again only
+    /// rebuilds the PHP value. `$ttl` is in seconds; `0` keeps the entry
+    /// until it is evicted or `clearCache()` is called. `$as_array` takes a
+    /// bool or an `Elephant\Json\DecoderOptions` instance, as for
+    /// `decode()`, and entries are kept per set of options. Request defaults
+    /// do not apply, so a cached document decodes the same in every request.
+    #[php(defaults(ttl = 0, as_array = None, depth = None, flags = None))]
+    pub fn decode_cached(
+        json: Binary<u8>,
+        ttl: i64,
+        as_array: Option<&Zval>,
+        depth: Option<i64>,
+        flags: Option<i64>,
+    ) -> PhpResult<Zval> {
+        let ttl = u64::try_from(ttl).map_err(|_| {
+            PhpException::new(
+                "Argument #2 ($ttl) must be greater than or equal to 0".to_string(),
+                0,
+                ce::value_error(),
+            )
+        })?;
+        let options = DecoderOptions::from_arg_without_defaults(as_array, flags, 3, "as_array")?;
+        Ok(cache::decode(&json, ttl, options, depth)?)
+    }
+
+    /// Drops every document `decodeCached()` kept in this worker.
+    pub fn clear_cache() {
+        cache::clear();
+    }
+
+    /// Iterates the elements of the top-level array of `$json`, decoding
+    /// each as the loop reaches it, so only one element is held as PHP
+    /// values at a time. `$json` may also be a stream, as for
+    /// `ArrayCursor::over()`, whose cursor this returns.
+    #[php(defaults(as_array = None))]
+    pub fn lazy_decode(json: &Zval, as_array: Option<&Zval>) -> PhpResult<ArrayCursor> {
+        ArrayCursor::over_root(json, DecoderOptions::from_arg(as_array, None, 2, "as_array")?)
+    }
+
+    /// Decodes a file. Local paths are memory-mapped on 64-bit Unix, so a
+    /// file truncated while it is decoded raises SIGBUS; stream wrapper paths
+    /// such as `php://input` or `compress.zlib://` go through PHP's stream
+    /// layer.
+    #[php(defaults(as_array = None, depth = None, flags = None))]
+    pub fn decode_file(
+        path: String,
+        as_array: Option<&Zval>,
+        depth: Option<i64>,
+        flags: Option<i64>,
+    ) -> PhpResult<Zval> {
+        let options = DecoderOptions::from_arg(as_array, flags, 2, "as_array")?;
+        let contents = file::read(&path)?;
+        Ok(decode_bytes(&contents, DecodeConfig::new(options, depth))?)
+    }
+
+    /// Streams NDJSON from `$input` to `$output`, both stream resources or
+    /// PSR-7 streams, one record at a time. Each line is decoded and passed
+    /// to `$callback` as `($record, $line)`; the return value is encoded as
+    /// an output line, or dropped if it is an `Elephant\Json\Skip`. Blank
+    /// lines are ignored and pretty printing is turned off. Returns the
+    /// number of records written. Records are handled one after another on
+    /// the calling thread; there are no parallel workers.
+    #[php(defaults(as_array = None, options = None))]
+    pub fn map_lines(
+        input: &Zval,
+        callback: &Zval,
+        output: &Zval,
+        as_array: Option<&Zval>,
+        options: Option<&Zval>,
+    ) -> PhpResult<i64> {
+        let mut decoder = DecoderOptions::from_arg(as_array, None, 4, "as_array")?;
+        let mut encoder = EncoderOptions::resolve(options, 5)?;
+        encoder.flags &= !flags::PRETTY_PRINT;
+        encoder.indent = None;
+        encoder.trailing_newline = false;
+        // Records are short, so yielding is paced over the input as a whole.
+        let pace = decoder.pace.take().or(encoder.pace.take());
+        lines::map(input, callback, output, decoder, &encoder, pace)
+    }
+
+    /// Checks every non-blank line of an NDJSON stream without decoding it,
+    /// returning `['line' => ..., 'offset' => ..., 'valid' => ..., 'error'
+    /// => ...]` for each; `error` is null for valid lines.
+    #[php(defaults(depth = None))]
+    pub fn validate_lines(input: &Zval, depth: Option<i64>) -> PhpResult<ZBox<ZendHashTable>> {
+        lines::validate(input, depth.unwrap_or_else(ini::default_depth))
+    }
+
+    /// Encodes every value of an array or `Traversable` in one call, joined
+    /// by `$separator`; the default gives NDJSON without a final line break.
+    #[php(defaults(separator = "\"\\n\"", options = None, depth = None))]
+    pub fn encode_many(
+        values: &Zval,
+        separator: String,
+        options: Option<&Zval>,
+        depth: Option<i64>,
+    ) -> PhpResult<String> {
+        let options = EncoderOptions::resolve(options, 3)?;
+        let encoder = JsonEncoder::new(EncodeConfig::new(&options, depth));
+        let Some(mut iterable) = values.iterable() else {
+            return Err(PhpException::new(
+                format!("Argument #1 ($values) must be of type iterable, {} given", values.get_type()),
+                0,
+                ce::type_error(),
+            ));
+        };
+        let values = iterable
+            .iter()
+            .ok_or_else(|| PhpException::default("Failed to rewind the iterator".to_string()))?;
+        Ok(encoder.encode_many(values.map(|(_, value)| value), &separator)?)
+    }
+
+    /// Encodes `$value` into a file, local or behind a stream wrapper, and
+    /// returns the number of bytes written.
+    #[php(defaults(options = None, depth = None))]
+    pub fn encode_file(
+        path: String,
+        value: &Zval,
+        options: Option<&Zval>,
+        depth: Option<i64>,
+    ) -> PhpResult<i64> {
+        let options = EncoderOptions::resolve(options, 3)?;
+        let json = JsonEncoder::new(EncodeConfig::new(&options, depth)).encode(value)?;
+        file::write(&path, &json)?;
+        Ok(json.len() as i64)
+    }
+
+    /// Encodes `$value` into a stream resource or PSR-7 stream and returns
+    /// the number of bytes written.
+    #[php(defaults(options = None, depth = None))]
+    pub fn encode_stream(
+        stream: &Zval,
+        value: &Zval,
+        options: Option<&Zval>,
+        depth: Option<i64>,
+    ) -> PhpResult<i64> {
+        let options = EncoderOptions::resolve(options, 3)?;
+        let json = JsonEncoder::new(EncodeConfig::new(&options, depth)).encode(value)?;
+        stream::write_all(stream, &json, 1)?;
+        Ok(json.len() as i64)
+    }
+
+    /// `$options` takes `JSON_*` flags or an `Elephant\Json\EncoderOptions`
+    /// instance.
+    ///
+    /// `$replacer` is called as `($key, $value, $pointer)` before each value
+    /// is written, parents before their children and the root first with
+    /// key `""`. It returns the value to write, which is encoded in turn, or
+    /// an `Elephant\Json\Skip` to leave the member out.
+    #[php(defaults(options = None, depth = None, replacer = None))]
+    pub fn encode(
+        value: &Zval,
+        options: Option<&Zval>,
+        depth: Option<i64>,
+        replacer: Option<&Zval>,
+    ) -> PhpResult<String> {
+        let options = EncoderOptions::resolve(options, 2)?;
+        let mut config = EncodeConfig::new(&options, depth);
+        config.replacer = callback(replacer, 4, "replacer")?;
+        Ok(metrics::measure("encode", || JsonEncoder::new(config).encode(value), String::len)?)
+    }
+
+    /// Reads the integer at `$pointer` in `$json`, scanning the document
+    /// only as far as that value. Throws a `TypeError` for any other type
+    /// and an exception when there is no such value.
+    #[php(defaults(options = None))]
+    pub fn get_int(json: Binary<u8>, pointer: PointerArg, options: Option<&DecoderOptions>) -> PhpResult<i64> {
+        lookup::get(&json, &pointer, options)?.as_int()
+    }
+
+    /// Like `getInt()`; integers are accepted too.
+    #[php(defaults(options = None))]
+    pub fn get_float(json: Binary<u8>, pointer: PointerArg, options: Option<&DecoderOptions>) -> PhpResult<f64> {
+        lookup::get(&json, &pointer, options)?.as_float()
+    }
+
+    /// Like `getInt()`, for strings.
+    #[php(defaults(options = None))]
+    pub fn get_string(json: Binary<u8>, pointer: PointerArg, options: Option<&DecoderOptions>) -> PhpResult<String> {
+        lookup::get(&json, &pointer, options)?.as_string()
+    }
+
+    /// Like `getInt()`, for `true` and `false`.
+    #[php(defaults(options = None))]
+    pub fn get_bool(json: Binary<u8>, pointer: PointerArg, options: Option<&DecoderOptions>) -> PhpResult<bool> {
+        lookup::get(&json, &pointer, options)?.as_bool()
+    }
+
+    /// The first `$n` elements of the array at `$pointer` in `$json`, as a
+    /// JSON array of their text as written. Reading stops after the last
+    /// one, so previewing a huge document costs only what is shown.
+    #[php(defaults(options = None))]
+    pub fn head(json: Binary<u8>, pointer: PointerArg, n: i64, options: Option<&DecoderOptions>) -> PhpResult<String> {
+        lookup::sample(&json, &pointer, n, false, options)
+    }
+
+    /// Like `head()`, for the last `$n` elements; the whole array is
+    /// scanned, but only those elements are kept.
+    #[php(defaults(options = None))]
+    pub fn tail(json: Binary<u8>, pointer: PointerArg, n: i64, options: Option<&DecoderOptions>) -> PhpResult<String> {
+        lookup::sample(&json, &pointer, n, true, options)
+    }
+
+    /// The JSON Pointers at which document `$b` differs from `$a`, each
+    /// mapped to `added`, `removed` or `modified`. Numbers are compared by
+    /// value, so `1` and `1.0` are the same; member order is ignored.
+    pub fn changed_paths(a: &str, b: &str) -> PhpResult<ZBox<ZendHashTable>> {
+        let mut changes = ZendHashTable::new();
+        for (path, change) in diff::changed_paths(&parse_value(a, 1)?, &parse_value(b, 2)?) {
+            changes.insert(path.as_str(), change.name())?;
+        }
+        Ok(changes)
+    }
+
+    /// Whether documents `$a` and `$b` are equal, with numbers allowed to
+    /// differ by `$abs_tol`, or by `$rel_tol` times the larger of the two,
+    /// whichever is more lenient. Everything else must match exactly;
+    /// member order is ignored.
+    #[php(defaults(abs_tol = 0.0, rel_tol = 1e-9))]
+    pub fn approx_equals(a: &str, b: &str, abs_tol: f64, rel_tol: f64) -> PhpResult<bool> {
+        Ok(Self::first_difference(a, b, abs_tol, rel_tol)?.is_none())
+    }
+
+    /// The JSON Pointer of the first value at which `approxEquals()` finds
+    /// `$a` and `$b` apart, or null if it finds them equal.
+    #[php(defaults(abs_tol = 0.0, rel_tol = 1e-9))]
+    pub fn first_difference(a: &str, b: &str, abs_tol: f64, rel_tol: f64) -> PhpResult<Option<String>> {
+        let tolerance = |value: f64, position: u8, name: &str| match value >= 0.0 {
+            true => Ok(value),
+            false => Err(PhpException::new(
+                format!("Argument #{} (${}) must be greater than or equal to 0", position, name),
+                0,
+                ce::value_error(),
+            )),
+        };
+        let tolerance = diff::Tolerance {
+            absolute: tolerance(abs_tol, 3, "abs_tol")?,
+            relative: tolerance(rel_tol, 4, "rel_tol")?,
+        };
+        Ok(diff::first_difference(&parse_value(a, 1)?, &parse_value(b, 2)?, tolerance))
+    }
+
+    /// Merges JSON documents the way `array_merge_recursive()` merges
+    /// their decoded arrays, so
+    /// `json_encode(array_merge_recursive(...array_map(fn ($d) => json_decode($d, true), $documents)))`
+    /// and this give the same result. Each document must hold an object
+    /// or an array.
+    #[php(defaults(options = None))]
+    pub fn merge_recursive(documents: &ZendHashTable, options: Option<&Zval>) -> PhpResult<String> {
+        let encoder_options = EncoderOptions::resolve(options, 2)?;
+        let mut decoded = Vec::with_capacity(documents.len());
+        for (i, document) in documents.values().enumerate() {
+            let json = document.binary::<u8>().filter(|_| document.is_string()).ok_or_else(|| {
+                PhpException::new(
+                    format!("Argument #1 ($documents) must contain only strings, {} given", document.get_type()),
+                    0,
+                    ce::type_error(),
+                )
+            })?;
+            let value = decode_bytes(&json, DecodeConfig::from_flags(Some(true), None, None))
+                .map_err(|err| JsonError::new(err.code, format!("Document {}: {}", i, err)))?;
+            if !value.is_array() {
+                return Err(PhpException::new(
+                    format!("Argument #1 ($documents) must contain only JSON objects and arrays, document {} is not", i),
+                    0,
+                    ce::type_error(),
+                ));
+            }
+            decoded.push(value);
+        }
+
+        let merged = merge::merge_recursive(decoded.iter().filter_map(Zval::array))?;
+        let mut value = Zval::new();
+        value.set_hashtable(merged);
+        Ok(JsonEncoder::new(EncodeConfig::new(&encoder_options, None)).encode(&value)?)
+    }
+
+    /// Encodes `$value` and checks the result against `$schema` before
+    /// returning it, failing with `ERROR_SCHEMA_VIOLATION` at the first
+    /// violation's path; `$schema->validate()` lists them all.
+    #[php(defaults(options = None, depth = None))]
+    pub fn encode_validated(
+        value: &Zval,
+        schema: &Schema,
+        options: Option<&Zval>,
+        depth: Option<i64>,
+    ) -> PhpResult<String> {
+        let options = EncoderOptions::resolve(options, 3)?;
+        let json = JsonEncoder::new(EncodeConfig::new(&options, depth)).encode(value)?;
+        schema.enforce(&json)?;
+        Ok(json)
+    }
+
+    /// Decodes a JOSE/JWT segment: strict unpadded base64url, then JSON.
+    #[php(defaults(as_array = None, depth = None, flags = None))]
+    pub fn decode_base64_url(
+        segment: String,
+        as_array: Option<&Zval>,
+        depth: Option<i64>,
+        flags: Option<i64>,
+    ) -> PhpResult<Zval> {
+        let options = DecoderOptions::from_arg(as_array, flags, 2, "as_array")?;
+        let bytes = base64url::decode(&segment).map_err(|message| JsonError::new(flags::ERROR_SYNTAX, message))?;
+        Ok(decode_bytes(&bytes, DecodeConfig::new(options, depth))?)
+    }
+
+    /// Encodes `$value` as a JOSE/JWT segment: JSON, then unpadded base64url.
+    #[php(defaults(options = None, depth = None))]
+    pub fn encode_base64_url(value: &Zval, options: Option<&Zval>, depth: Option<i64>) -> PhpResult<String> {
+        Ok(base64url::encode(Self::encode(value, options, depth, None)?.as_bytes()))
+    }
+
+    /// Encodes and gzip-compresses in one call, for caches storing JSON
+    /// blobs. The text is compressed as it is produced, so it is never held
+    /// in full. `$options` and `$depth` are as for `encode()`. Needs the
+    /// zlib extension.
+    #[php(defaults(level = 6, options = None, depth = None))]
+    pub fn encode_gzip(value: &Zval, level: i64, options: Option<&Zval>, depth: Option<i64>) -> PhpResult<Binary<u8>> {
+        encode_compressed(compression::Format::Gzip, value, level, options, depth)
+    }
+
+    /// Like `encodeGzip()`, with zstd; needs the zstd extension.
+    #[php(defaults(level = 3, options = None, depth = None))]
+    pub fn encode_zstd(value: &Zval, level: i64, options: Option<&Zval>, depth: Option<i64>) -> PhpResult<Binary<u8>> {
+        encode_compressed(compression::Format::Zstd, value, level, options, depth)
+    }
+
+    /// Encodes `$value` as UBJSON, with the same options as `encode()`;
+    /// layout options have no effect.
+    #[php(defaults(options = None, depth = None))]
+    pub fn encode_ubjson(value: &Zval, options: Option<&Zval>, depth: Option<i64>) -> PhpResult<Binary<u8>> {
+        let json = Self::encode(value, options, depth, None)?;
+        Ok(Binary::new(ubjson::from_json(&json)?))
+    }
+
+    /// Decodes UBJSON, with the same arguments as `decode()`. The input
+    /// size limit applies to the equivalent JSON text.
+    #[php(defaults(as_array = None, depth = None, flags = None))]
+    pub fn decode_ubjson(
+        data: Binary<u8>,
+        as_array: Option<&Zval>,
+        depth: Option<i64>,
+        flags: Option<i64>,
+    ) -> PhpResult<Zval> {
+        let config = DecodeConfig::new(DecoderOptions::from_arg(as_array, flags, 2, "as_array")?, depth);
+        let json = ubjson::to_json(&data, config.max_input_bytes.filter(|max| *max > 0))?;
+        Ok(JsonDecoder::new(config).decode(&json)?)
+    }
+
+    /// Encodes `$value` as Smile, with the same options as `encode()`;
+    /// layout options have no effect. Repeated keys are written as
+    /// back-references.
+    #[php(defaults(options = None, depth = None))]
+    pub fn encode_smile(value: &Zval, options: Option<&Zval>, depth: Option<i64>) -> PhpResult<Binary<u8>> {
+        let json = Self::encode(value, options, depth, None)?;
+        Ok(Binary::new(smile::from_json(&json)?))
+    }
+
+    /// Decodes Smile, with the same arguments as `decode()`. The input
+    /// size limit applies to the equivalent JSON text.
+    #[php(defaults(as_array = None, depth = None, flags = None))]
+    pub fn decode_smile(
+        data: Binary<u8>,
+        as_array: Option<&Zval>,
+        depth: Option<i64>,
+        flags: Option<i64>,
+    ) -> PhpResult<Zval> {
+        let config = DecodeConfig::new(DecoderOptions::from_arg(as_array, flags, 2, "as_array")?, depth);
+        let json = smile::to_json(&data, config.max_input_bytes.filter(|max| *max > 0))?;
+        Ok(JsonDecoder::new(config).decode(&json)?)
+    }
+
+    /// Encodes `$value` as Amazon Ion text, with the same options as
+    /// `encode()`; layout options have no effect. `Annotated` values are
+    /// written with their annotations.
+    #[php(defaults(options = None, depth = None))]
+    pub fn encode_ion(value: &Zval, options: Option<&Zval>, depth: Option<i64>) -> PhpResult<String> {
+        let options = EncoderOptions::resolve(options, 2)?;
+        let mut config = EncodeConfig::new(&options, depth);
+        config.annotated = true;
+        let json = JsonEncoder::new(config).encode(value)?;
+        Ok(ion::from_json(&json)?)
+    }
+
+    /// Decodes Amazon Ion text, with the same arguments as `decode()`.
+    /// Annotated values decode to `Annotated`. The input size limit
+    /// applies to the equivalent JSON text.
+    #[php(defaults(as_array = None, depth = None, flags = None))]
+    pub fn decode_ion(
+        ion: String,
+        as_array: Option<&Zval>,
+        depth: Option<i64>,
+        flags: Option<i64>,
+    ) -> PhpResult<Zval> {
+        let mut config = DecodeConfig::new(DecoderOptions::from_arg(as_array, flags, 2, "as_array")?, depth);
+        config.annotated = true;
+        let json = ion::to_json(&ion, config.max_input_bytes.filter(|max| *max > 0))?;
+        Ok(JsonDecoder::new(config).decode(&json)?)
+    }
+
+    /// Checks syntax, honouring the depth, input size and time limits of
+    /// `$options`.
+    #[php(defaults(options = None))]
+    pub fn validate(json: String, options: Option<&DecoderOptions>) -> bool {
+        let options = options
+            .cloned()
+            .unwrap_or_else(|| DecoderOptions::resolve(None, None).0);
+        JsonDecoder::new(DecodeConfig::new(options, None))
+            .validate(&json)
+            .is_ok()
+    }
+
+    /// Pretty-prints with ANSI syntax highlighting for terminal output.
+    ///
+    /// Strings are taken to be JSON documents and re-rendered; any other
+    /// value is encoded first. Colors are on unless `$color` is false, or it
+    /// is null and the `NO_COLOR` environment variable is set.
+    #[php(defaults(color = None))]
+    pub fn pretty_color(value: &Zval, color: Option<bool>) -> Result<String, JsonError> {
+        let use_color = color.unwrap_or_else(|| {
+            std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        });
+
+        let mut config = WriterConfig::pretty();
+        config.escaping.unescaped_unicode = true;
+        if use_color {
+            config.palette = Some(&Palette::TERMINAL);
+        }
+
+        if !value.is_string() {
+            return JsonEncoder::new(EncodeConfig::from_flags(Some(0), None)).encode_with(&config, value);
+        }
+
+        let json = value.str().ok_or_else(|| JsonError::syntax("input is not valid UTF-8"))?;
+        let mut reader = Reader::new(json);
+        let mut writer = JsonWriter::new(&config);
+        while let Some((_, event)) = reader.next_event().map_err(JsonError::read)? {
+            writer.event(event).map_err(JsonError::syntax)?;
+        }
+        Ok(writer.finish())
+    }
+
+    /// A pretty rendering of `$value` for logs and error reports, showing
+    /// `$max_depth` levels of nesting and the first `$max_items` elements of
+    /// each array or object. Deeper containers are collapsed to
+    /// `[… 12 items]` and longer ones end in `… 4,982 more items`, so the
+    /// output is not JSON. Values that cannot be encoded are shown as
+    /// `null` rather than failing, and non-finite floats as strings.
+    #[php(defaults(max_depth = 3, max_items = 20))]
+    pub fn dump(value: &Zval, max_depth: i64, max_items: i64) -> PhpResult<String> {
+        let limit = |n: i64, position: u8, name: &str| {
+            usize::try_from(n).map_err(|_| {
+                PhpException::new(
+                    format!("Argument #{} (${}) must be greater than or equal to 0", position, name),
+                    0,
+                    ce::value_error(),
+                )
+            })
+        };
+        let (max_depth, max_items) = (limit(max_depth, 2, "max_depth")?, limit(max_items, 3, "max_items")?);
+        let mut config = EncodeConfig::from_flags(Some(flags::PARTIAL_OUTPUT_ON_ERROR), None);
+        config.non_finite = NonFinite::String;
+        let json = JsonEncoder::new(config).encode(value)?;
+        Ok(dump::render(&json, max_depth, max_items)?)
+    }
+
+    /// Escapes a bare string (no surrounding quotes) the way `encode` would
+    /// with the same flags or options.
+    #[php(defaults(flags = None))]
+    pub fn escape_string(s: String, flags: Option<&Zval>) -> PhpResult<String> {
+        let escaping = EncoderOptions::from_arg(flags, 2)?
+            .map_or_else(|| Escaping::from_flags(0), |options| EncodeConfig::escaping(&options));
+        let mut out = String::with_capacity(s.len());
+        escape::escape_bare_into(&mut out, &s, &escaping);
+        Ok(out)
+    }
+
+    /// Resolves the escape sequences of a bare JSON string fragment.
+    pub fn unescape_string(s: String) -> Result<String, String> {
+        escape::unescape(&s)
+    }
+
+    /// Rewrites a document with uniform escaping, keeping structure and number
+    /// lexemes untouched.
+    ///
+    /// Options: `flags` (encoder escaping flags), `minimal` (escape only what
+    /// RFC 8259 requires), `hex_case` (`"lower"` or `"upper"`) and `nfc`
+    /// (bring keys and strings to Unicode Normalization Form C, through
+    /// ext/intl's `normalizer_normalize()`).
+    #[php(defaults(options = None))]
+    pub fn normalize(json: String, options: Option<&ZendHashTable>) -> Result<String, String> {
+        let NormalizeConfig { escaping, nfc } = NormalizeConfig::from_options(options)?;
+        let config = WriterConfig {
+            escaping,
+            ..WriterConfig::compact()
+        };
+
+        let mut reader = Reader::new(&json);
+        let mut writer = JsonWriter::new(&config);
+        while let Some((_, event)) = reader
+            .next_event()
+            .map_err(|e| format!("JSON syntax error: {}", e))?
+        {
+            match (&nfc, event) {
+                (Some(normalizer), Event::Key(raw)) => writer.key(&to_nfc(normalizer, raw)?),
+                (Some(normalizer), Event::String(raw)) => writer.string(&to_nfc(normalizer, raw)?),
+                (_, event) => writer.event(event)?,
+            }
+        }
+
+        Ok(writer.finish())
+    }
+
+    /// Registers, for the rest of the request, how instances of `$class`
+    /// and its subclasses encode: `$serialize` is called with the object
+    /// and its return value is encoded in its place. `$deserialize`, if
+    /// given, turns decoded data back into an object in `decodeAs()`.
+    /// Registering a class again replaces its adapter.
+    #[php(defaults(deserialize = None))]
+    pub fn register_adapter(class: String, serialize: &Zval, deserialize: Option<&Zval>) -> PhpResult<()> {
+        if ClassEntry::try_find(&class).is_none() {
+            return Err(PhpException::new(
+                "Argument #1 ($class) must be an existing class or interface name".to_string(),
+                0,
+                ce::value_error(),
+            ));
+        }
+        let Some(serialize) = callback(Some(serialize), 2, "serialize")? else {
+            return Err(PhpException::new(
+                "Argument #2 ($serialize) must be a valid callback".to_string(),
+                0,
+                ce::type_error(),
+            ));
         };
+        let deserialize = callback(deserialize, 3, "deserialize")?;
+        adapters::register(&class, Callback::new(serialize), deserialize.map(Callback::new));
+        Ok(())
+    }
+
+    /// Removes every adapter `registerAdapter()` added in this request.
+    pub fn reset_adapters() {
+        adapters::reset();
+    }
+
+    /// Decodes `$json` into an instance of `$class`. A deserializer
+    /// registered for `$class` is given the decoded value; otherwise the
+    /// object is built from its members, which are passed to the
+    /// constructor parameters and public properties of the same name.
+    /// Members declared with a class type are built the same way.
+    /// `$options` is as for `decode()`.
+    #[php(defaults(options = None, depth = None))]
+    pub fn decode_as(json: Binary<u8>, class: String, options: Option<&Zval>, depth: Option<i64>) -> PhpResult<Zval> {
+        let options = DecoderOptions::from_arg(options, None, 3, "options")?;
+        let value = decode_bytes(&json, DecodeConfig::new(options, depth))?;
+        hydrate::hydrate(&value, &class)
+    }
+
+    /// Applies a batch of edits to `$json` without decoding it into PHP
+    /// values. Each operation is an array with an `op` of `set`, `remove`
+    /// or `push`, a JSON Pointer `path` and, except for `remove`, a
+    /// `value`; they apply in order, as `Document::with()`, `without()`
+    /// and `push()` would. Bytes outside the edited values are kept as
+    /// written.
+    pub fn edit(json: String, operations: &ZendHashTable) -> PhpResult<String> {
+        document::edit(json, operations)
+    }
+
+    /// Copies `$json` without the members and array elements named by
+    /// `$exclude_pointers`, token by token and without building PHP values.
+    /// Pointers may use `*` for any one member name or index and `**` for
+    /// any number of levels, so `/**/password` drops every `password`
+    /// member. The output is compact; everything kept is copied as written.
+    pub fn filter_keys(json: &str, exclude_pointers: Vec<PointerArg>) -> PhpResult<String> {
+        let exclude = exclude_pointers
+            .iter()
+            .map(filter::Pattern::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(filter::filter_keys(json, &exclude)?)
+    }
+
+    /// Decodes an array of objects into per-member columns.
+    #[php(defaults(columns = None))]
+    pub fn decode_columns(json: String, columns: Option<Vec<String>>) -> PhpResult<ZBox<ZendHashTable>> {
+        columns::decode_columns(&json, columns)
+    }
+
+    /// Sets the encode options used for the rest of the request whenever
+    /// `$options` is omitted. Pass null to go back to the INI defaults.
+    #[php(defaults(options = None))]
+    pub fn set_encode_defaults(options: Option<&Zval>) -> PhpResult<()> {
+        state::set_encode_defaults(EncoderOptions::from_arg(options, 1)?);
+        Ok(())
+    }
+
+    /// Sets the decode flags or options used for the rest of the request
+    /// whenever `$as_array` and `$flags` are omitted. Pass null to go back to
+    /// the INI defaults.
+    #[php(defaults(options = None))]
+    pub fn set_decode_defaults(options: Option<&Zval>) -> PhpResult<()> {
+        state::set_decode_defaults(DecodeDefaults::from_arg(options, 1)?);
+        Ok(())
+    }
+
+    /// Calls `$hook` after every `decode()`, `encode()`, `json_decode()` and
+    /// `json_encode()` in this request with an array of `operation`,
+    /// `seconds`, `bytes` (the JSON text read or written, `0` on failure)
+    /// and `error` (the error code, `0` on success). `null` removes it.
+    pub fn on_metrics(hook: Option<&Zval>) -> PhpResult<()> {
+        metrics::set_hook(callback(hook, 1, "hook")?.map(Callback::new));
+        Ok(())
+    }
+
+    /// Extension version string.
+    pub fn version() -> &'static str {
+        info::VERSION
+    }
+
+    /// Names of the capabilities compiled into this build.
+    pub fn features() -> Vec<&'static str> {
+        info::features()
+    }
+
+    /// Reports the shape of a document without decoding it.
+    pub fn inspect(json: String) -> PhpResult<ZBox<ZendHashTable>> {
+        let stats = inspect::inspect(&json).map_err(|e| format!("JSON syntax error: {}", e))?;
+
+        let mut result = ZendHashTable::new();
+        result.insert("objects", stats.objects as i64)?;
+        result.insert("arrays", stats.arrays as i64)?;
+        result.insert("keys", stats.keys as i64)?;
+        result.insert("strings", stats.strings as i64)?;
+        result.insert("numbers", stats.numbers as i64)?;
+        result.insert("booleans", stats.booleans as i64)?;
+        result.insert("nulls", stats.nulls as i64)?;
+        result.insert("scalars", stats.scalars() as i64)?;
+        result.insert("max_depth", stats.max_depth as i64)?;
+        result.insert("longest_string", stats.longest_string as i64)?;
+        result.insert("largest_array", stats.largest_array as i64)?;
+        Ok(result)
+    }
+
+    /// Best-effort fix for damaged JSON: unquoted keys, single quotes,
+    /// comments, trailing or missing commas, unclosed strings and brackets,
+    /// and concatenated documents, which are wrapped in an array.
+    pub fn repair(broken: String) -> PhpResult<String> {
+        Ok(repair::repair(&broken)?.json)
+    }
+
+    /// `repair()` plus what it changed, as `['json' => ..., 'fixes' =>
+    /// [['fix' => kind, 'offset' => input byte offset], ...]]`.
+    pub fn repair_with_report(broken: String) -> PhpResult<ZBox<ZendHashTable>> {
+        let repaired = repair::repair(&broken)?;
+
+        let mut fixes = ZendHashTable::new();
+        for fix in repaired.fixes {
+            let mut entry = ZendHashTable::new();
+            entry.insert("fix", fix.kind)?;
+            entry.insert("offset", fix.offset as i64)?;
+            fixes.push(entry)?;
+        }
+
+        let mut result = ZendHashTable::new();
+        result.insert("json", repaired.json)?;
+        result.insert("fixes", fixes)?;
+        Ok(result)
+    }
 
-        JsonDecoder::new(config).decode(&json)
+    /// Splits a document into tokens for tools working below the value
+    /// level; see `Elephant\Json\Tokens`.
+    pub fn tokenize(json: String) -> PhpResult<Tokens> {
+        Tokens::new(json)
     }
 
-    pub fn encode(value: &mut Zval, options: Option<i64>) -> Result<String, String> {
-        let config = EncodeConfig::from_flags(options.unwrap_or(0));
-        JsonEncoder::new(config).encode(value)
+    /// Lists every object member name that occurs more than once, as
+    /// `['path' => JSON Pointer of the object, 'key' => name, 'offsets' =>
+    /// byte offsets]` entries. Parsers disagree on which duplicate wins, so
+    /// these are worth flagging in untrusted input.
+    pub fn find_duplicate_keys(json: String) -> PhpResult<ZBox<ZendHashTable>> {
+        let duplicates = duplicates::find_duplicate_keys(&json)
+            .map_err(|e| format!("JSON syntax error: {}", e))?;
+
+        let mut result = ZendHashTable::new();
+        for duplicate in duplicates {
+            let mut offsets = ZendHashTable::new();
+            for offset in duplicate.offsets {
+                offsets.push(offset as i64)?;
+            }
+
+            let mut entry = ZendHashTable::new();
+            entry.insert("path", duplicate.path)?;
+            entry.insert("key", duplicate.key)?;
+            entry.insert("offsets", offsets)?;
+            result.push(entry)?;
+        }
+        Ok(result)
     }
+}
+
+/// Encodes for `Codec::encodeGzip()` and `encodeZstd()`, whose options
+/// come after the compression level.
+/// Shared body of `Codec::encodeGzip()` and `Codec::encodeZstd()`.
+fn encode_compressed(
+    format: compression::Format,
+    value: &Zval,
+    level: i64,
+    options: Option<&Zval>,
+    depth: Option<i64>,
+) -> PhpResult<Binary<u8>> {
+    let mut compressor = compression::Compressor::new(format, level)?;
+    let options = EncoderOptions::resolve(options, 3)?;
+    let config = EncodeConfig::new(&options, depth);
+    let mut sink = |chunk: &str| compressor.write(chunk);
+    metrics::measure("encode", || JsonEncoder::encode_into(config, value, &mut sink), |len| *len)?;
+    Ok(compressor.finish()?)
+}
+
+/// Checks that an optional callback argument is callable.
+fn callback(arg: Option<&Zval>, position: u8, name: &str) -> PhpResult<Option<Zval>> {
+    match arg {
+        Some(zval) if !zval.is_null() => match ZendCallable::new(zval) {
+            Ok(_) => Ok(Some(zval.shallow_clone())),
+            Err(_) => Err(PhpException::new(
+                format!("Argument #{} (${}) must be a valid callback or null", position, name),
+                0,
+                ce::type_error(),
+            )),
+        },
+        _ => Ok(None),
+    }
+}
 
-    pub fn validate(json: String) -> bool {
-        serde_json::from_str::<Value>(&json).is_ok()
+/// Parses argument `position` into a `serde_json` tree, for methods that
+/// compare whole documents.
+fn parse_value(json: &str, position: u8) -> Result<serde_json::Value, JsonError> {
+    serde_json::from_str(json).map_err(|err| JsonError::syntax(format!("{} in argument #{}", err, position)))
+}
+
+/// Whether `arr` has the keys `0..n` in order and so encodes as a list.
+/// Packed arrays without holes, as most lists are, are recognized from the
+/// hash table's metadata without reading any keys, as `array_is_list()`
+/// does.
+fn is_list(arr: &ZendHashTable) -> bool {
+    const HASH_FLAG_PACKED: u32 = 1 << 2;
+    let packed = unsafe { arr.u.flags } & HASH_FLAG_PACKED != 0;
+    if arr.nNumOfElements == 0 || (packed && arr.nNumUsed == arr.nNumOfElements) {
+        return true;
     }
+    arr.has_sequential_keys()
 }
 
-#[php_function]
-pub fn json_decode(json: String, as_array: Option<bool>, depth: Option<i64>) -> PhpResult<Zval> {
-    Json::decode(json, as_array, depth)
+/// Shared tail of the byte-oriented decode entry points.
+fn decode_bytes(bytes: &[u8], config: DecodeConfig) -> Result<Zval, JsonError> {
+    let inflated = if config.options.decompress {
+        compression::decompress(bytes, config.max_input_bytes)?
+    } else {
+        None
+    };
+    let bytes = inflated.as_ref().and_then(Zval::zend_str).map_or(bytes, |inflated| inflated.as_bytes());
+    let transcoded = match config.options.detect_encoding {
+        true => charset::transcode(bytes)?,
+        false => None,
+    };
+    let bytes = charset::strip_bom(transcoded.as_ref().map_or(bytes, |text| text.as_bytes()), config.options.bom);
+    let json = charset::to_utf8(bytes, config.options.invalid_utf8)?;
+    JsonDecoder::new(config).decode(&json)
 }
 
-#[php_function]
-pub fn json_encode(value: &mut Zval, options: Option<i64>) -> Result<String, String> {
-    Json::encode(value, options)
+struct NormalizeConfig {
+    escaping: Escaping,
+    /// `normalizer_normalize()`, when the `nfc` option is set.
+    nfc: Option<ZendCallable<'static>>,
 }
 
-#[php_function]
-pub fn json_validate(json: String) -> bool {
-    Json::validate(json)
+impl NormalizeConfig {
+    fn from_options(options: Option<&ZendHashTable>) -> Result<Self, String> {
+        let Some(options) = options else {
+            return Ok(Self {
+                escaping: Escaping::default(),
+                nfc: None,
+            });
+        };
+
+        let flags = options.get("flags").and_then(Zval::long).unwrap_or(0);
+        let minimal = options.get("minimal").and_then(Zval::bool).unwrap_or(false);
+        let mut escaping = if minimal {
+            Escaping::minimal()
+        } else {
+            Escaping::from_flags(flags)
+        };
+
+        escaping.hex_case = match options.get("hex_case").and_then(Zval::str) {
+            None => HexCase::Native,
+            Some("lower") => HexCase::Lower,
+            Some("upper") => HexCase::Upper,
+            Some(other) => return Err(format!("Unknown hex_case '{}'", other)),
+        };
+
+        let nfc = match options.get("nfc").and_then(Zval::bool).unwrap_or(false) {
+            true => Some(
+                ZendCallable::try_from_name("normalizer_normalize")
+                    .map_err(|_| "NFC normalization requires the intl extension".to_string())?,
+            ),
+            false => None,
+        };
+
+        Ok(Self { escaping, nfc })
+    }
+}
+
+/// The content of the string token `raw` in Normalization Form C. ASCII
+/// text is already normalized and skips the call.
+fn to_nfc(normalizer: &ZendCallable, raw: &str) -> Result<String, String> {
+    let content = reader::string_content(raw)?;
+    if content.is_ascii() {
+        return Ok(content);
+    }
+    let normalized = normalizer
+        .try_call(vec![&content])
+        .map_err(|e| format!("NFC normalization failed: {}", e))?;
+    normalized
+        .str()
+        .map(str::to_string)
+        .ok_or_else(|| "NFC normalization failed: normalizer_normalize() returned false".to_string())
 }
 
 struct DecodeConfig {
-    as_array: bool,
+    options: DecoderOptions,
     max_depth: i64,
+    max_input_bytes: Option<usize>,
+    /// In bytes, after unescaping; applies to keys as well.
+    max_string_length: Option<usize>,
+    max_array_items: Option<usize>,
+    max_object_members: Option<usize>,
+    time_budget_ms: Option<u64>,
+    memory_guard: bool,
+    /// Called on every decoded value; see `Codec::decode()`.
+    reviver: Option<Zval>,
+    /// Whether Ion annotation envelopes decode to `Annotated`, see
+    /// `Codec::decodeIon()`.
+    annotated: bool,
+}
+
+impl DecodeConfig {
+    /// Resolves the limits: an explicit argument first, then the options'
+    /// own, then the INI defaults.
+    fn new(options: DecoderOptions, depth: Option<i64>) -> Self {
+        Self {
+            max_depth: depth.or(options.max_depth).unwrap_or_else(ini::default_depth),
+            max_input_bytes: options.max_input_bytes.or_else(ini::max_input_bytes),
+            max_string_length: options.max_string_length,
+            max_array_items: options.max_array_items,
+            max_object_members: options.max_object_members,
+            time_budget_ms: options.time_budget_ms,
+            memory_guard: options.memory_guard.unwrap_or_else(ini::memory_guard),
+            reviver: None,
+            annotated: false,
+            options,
+        }
+    }
+
+    fn from_flags(as_array: Option<bool>, depth: Option<i64>, flags: Option<i64>) -> Self {
+        let flags = flags.unwrap_or_else(ini::default_decode_flags);
+        Self::new(DecoderOptions::from_flags(as_array, flags), depth)
+    }
 }
 
+/// Builds PHP values straight from [`Reader`] events, so key order,
+/// duplicate keys and number lexemes are all visible to the policies in
+/// [`DecoderOptions`].
 struct JsonDecoder {
     config: DecodeConfig,
+    budget: Budget,
+    pacer: Option<Pacer>,
+    /// JSON Pointer of the value being read; only kept up with a reviver.
+    pointer: RefCell<String>,
 }
 
 impl JsonDecoder {
+    /// Also starts the time budget, so create one decoder per document.
     fn new(config: DecodeConfig) -> Self {
-        Self { config }
+        Self {
+            budget: Budget::start(config.time_budget_ms, config.memory_guard),
+            pacer: config.options.pace.clone().map(Pacer::new),
+            pointer: RefCell::new(String::new()),
+            config,
+        }
     }
 
-    fn decode(&self, json: &str) -> PhpResult<Zval> {
-        let value: Value = serde_json::from_str(json)
-            .map_err(|e| format!("JSON syntax error: {}", e))?;
+    fn decode(&self, json: &str) -> Result<Zval, JsonError> {
+        self.check_input_size(json)?;
 
-        self.convert(value, 0)
+        let mut reader = self.reader(json);
+        let event = self.next_event(&mut reader)?;
+        let value = self.read_value(&mut reader, event)?;
+        self.finish(&mut reader)?;
+        self.revive("", value)
     }
 
-    fn convert(&self, value: Value, depth: i64) -> PhpResult<Zval> {
-        if depth > self.config.max_depth {
-            return Err("Maximum nesting depth exceeded".into());
+    /// Checks a document against the grammar and the structural limits
+    /// without building any PHP values.
+    fn validate(&self, json: &str) -> Result<(), JsonError> {
+        self.check_input_size(json)?;
+
+        let mut reader = self.reader(json);
+        while reader.next_event().map_err(JsonError::read)?.is_some() {
+            self.budget.tick(0)?;
+            self.check_depth(&reader)?;
         }
+        Ok(())
+    }
+
+    fn reader<'a>(&self, json: &'a str) -> Reader<'a> {
+        Reader::new(json)
+            .with_control_chars(self.config.options.control_chars != CharPolicy::Reject)
+            .with_container_root(self.config.options.container_root)
+    }
 
-        match value {
-            Value::Null => Ok(self.make_null()),
-            Value::Bool(b) => {
+    /// Rejects oversized documents before any parsing work is done.
+    fn check_input_size(&self, json: &str) -> Result<(), JsonError> {
+        if let Some(max) = self.config.max_input_bytes.filter(|max| *max > 0 && json.len() > *max) {
+            return Err(JsonError::new(
+                flags::ERROR_INPUT_TOO_LARGE,
+                format!("Input of {} bytes exceeds the maximum of {}", json.len(), max),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Reads the value that begins with `event`.
+    fn read_value(&self, reader: &mut Reader, event: Event) -> Result<Zval, JsonError> {
+        match event {
+            Event::Null => Ok(self.make_null()),
+            Event::Bool(b) => {
                 let mut zval = Zval::new();
                 zval.set_bool(b);
                 Ok(zval)
             }
-            Value::Number(n) => Ok(self.convert_number(n)),
-            Value::String(s) => Ok(self.make_string(&s)),
-            Value::Array(arr) => self.convert_array(arr, depth),
-            Value::Object(obj) => self.convert_object(obj, depth),
+            Event::Number(raw) => self.read_number(raw),
+            Event::String(raw) => Ok(self.make_string(&self.read_string(raw)?)),
+            Event::BeginArray | Event::BeginObject
+                if self.config.options.truncate_depth.is_some_and(|max| reader.depth() > max) =>
+            {
+                let start = reader.token_start();
+                self.skip_until(reader, reader.depth() - 1)?;
+                self.make_truncated(start, reader.position())
+            }
+            Event::BeginArray => {
+                self.check_depth(reader)?;
+                self.read_array(reader)
+            }
+            Event::BeginObject => {
+                self.check_depth(reader)?;
+                self.read_object(reader)
+            }
+            Event::EndArray | Event::EndObject | Event::Key(_) => {
+                Err(JsonError::syntax("unexpected token"))
+            }
+        }
+    }
+
+    /// Reads an element or member value, passing it through the reviver.
+    /// `name` is the member name in the document, `key` the one it is
+    /// decoded under.
+    fn read_child(
+        &self,
+        reader: &mut Reader,
+        event: Event,
+        name: &str,
+        key: &str,
+    ) -> Result<Zval, JsonError> {
+        if self.config.reviver.is_none() {
+            return self.read_value(reader, event).map_err(|err| err.at(name));
+        }
+
+        let len = self.pointer.borrow().len();
+        {
+            let mut pointer = self.pointer.borrow_mut();
+            pointer.push('/');
+            pointer.push_str(&pointer::escape(name));
+        }
+        let result = self
+            .read_value(reader, event)
+            .and_then(|value| self.revive(key, value));
+        self.pointer.borrow_mut().truncate(len);
+        result.map_err(|err| err.at(name))
+    }
+
+    fn revive(&self, key: &str, value: Zval) -> Result<Zval, JsonError> {
+        let Some(reviver) = &self.config.reviver else {
+            return Ok(value);
+        };
+        let pointer = self.pointer.borrow().clone();
+        ZendCallable::new(reviver)
+            .and_then(|reviver| reviver.try_call(vec![&key, &value, &pointer]))
+            .map_err(JsonError::engine)
+    }
+
+    fn next_event<'a>(&self, reader: &mut Reader<'a>) -> Result<Event<'a>, JsonError> {
+        self.budget.tick(reader.remaining())?;
+        if let Some(pacer) = &self.pacer {
+            pacer.advance(reader.position())?;
+        }
+        reader
+            .next_event()
+            .map_err(JsonError::read)?
+            .map(|(_, event)| event)
+            .ok_or_else(|| JsonError::syntax("Unexpected end of input"))
+    }
+
+    /// Consumes events until the reader is back at `depth`.
+    fn skip_until(&self, reader: &mut Reader, depth: usize) -> Result<(), JsonError> {
+        while reader.depth() > depth {
+            self.next_event(reader)?;
+        }
+        Ok(())
+    }
+
+    fn make_truncated(&self, start: usize, end: usize) -> Result<Zval, JsonError> {
+        Truncated::new(start as i64, end as i64)
+            .into_zval(false)
+            .map_err(JsonError::engine)
+    }
+
+    /// Makes sure nothing but whitespace follows the document.
+    fn finish(&self, reader: &mut Reader) -> Result<(), JsonError> {
+        reader.next_event().map_err(JsonError::read)?;
+        Ok(())
+    }
+
+    fn check_depth(&self, reader: &Reader) -> Result<(), JsonError> {
+        if reader.depth() as i64 > self.config.max_depth {
+            return Err(JsonError::depth());
+        }
+        if reader.depth() > MAX_NESTING {
+            return Err(JsonError::new(
+                flags::ERROR_DEPTH,
+                format!("Nesting deeper than {} levels is not supported, whatever $depth allows", MAX_NESTING),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Decodes a string lexeme, enforcing `max_string_length` before the
+    /// string is copied into a PHP value.
+    fn read_string(&self, raw: &str) -> Result<String, JsonError> {
+        if let Some(max) = self.config.max_string_length {
+            // Only escapes shrink a string, by at most five bytes in six
+            // (`\u0041` to `A`), so oversized strings fail before they are
+            // copied.
+            let content = raw.len() - 2;
+            let at_least = match raw.contains('\\') {
+                true => content.div_ceil(6),
+                false => content,
+            };
+            if at_least > max {
+                return Err(JsonError::new(
+                    flags::ERROR_LIMIT_EXCEEDED,
+                    format!("String of at least {} bytes exceeds the maximum length of {}", at_least, max),
+                ));
+            }
+        }
+        let options = &self.config.options;
+        let s = decode_string(raw, options.surrogates, options.control_chars).map_err(JsonError::read)?;
+        if let Some(max) = self.config.max_string_length.filter(|max| s.len() > *max) {
+            return Err(JsonError::new(
+                flags::ERROR_LIMIT_EXCEEDED,
+                format!("String of {} bytes exceeds the maximum length of {}", s.len(), max),
+            ));
+        }
+        Ok(s)
+    }
+
+    fn check_count(&self, count: usize, max: Option<usize>, what: &str) -> Result<(), JsonError> {
+        match max {
+            Some(max) if count > max => Err(JsonError::new(
+                flags::ERROR_LIMIT_EXCEEDED,
+                format!("More than {} {}", max, what),
+            )),
+            _ => Ok(()),
         }
     }
 
@@ -91,31 +1269,100 @@ impl JsonDecoder {
 
     fn make_string(&self, s: &str) -> Zval {
         let mut zval = Zval::new();
-        zval.set_string(s, false);
+        zval.set_zend_string(s.into());
         zval
     }
 
-    fn convert_number(&self, n: serde_json::Number) -> Zval {
-        if let Some(i) = n.as_i64() {
-            let mut zval = Zval::new();
-            zval.set_long(i);
-            zval
-        } else if let Some(f) = n.as_f64() {
-            Zval::from(f)
-        } else {
-            let s = n.to_string();
-            let mut zval = Zval::new();
-            zval.set_string(&s, false);
-            zval
+    fn read_number(&self, raw: &str) -> Result<Zval, JsonError> {
+        if self.config.options.raw_numbers {
+            return Number::new(raw).into_zval(false).map_err(JsonError::engine);
+        }
+        let is_integer = !raw.contains(['.', 'e', 'E']);
+
+        if is_integer {
+            if let Ok(i) = raw.parse::<i64>() {
+                let mut zval = Zval::new();
+                zval.set_long(i);
+                return Ok(zval);
+            }
+            return match self.config.options.int_overflow {
+                IntOverflow::Float => self.make_double(raw),
+                IntOverflow::Decimal => self.make_decimal(raw),
+                IntOverflow::String => Ok(self.make_string(raw)),
+                IntOverflow::Error => Err(JsonError::new(
+                    flags::ERROR_INTEGER_OVERFLOW,
+                    format!("Integer {} is out of range", raw),
+                )),
+                IntOverflow::Gmp => self.make_gmp(raw),
+            };
+        }
+
+        match self.config.options.floats {
+            FloatPolicy::Float => self.make_double(raw),
+            FloatPolicy::String => Ok(self.make_string(raw)),
+            FloatPolicy::Decimal => self.make_decimal(raw),
+        }
+    }
+
+    fn make_gmp(&self, raw: &str) -> Result<Zval, JsonError> {
+        let gmp_init = ZendCallable::try_from_name("gmp_init").map_err(|_| {
+            JsonError::new(
+                flags::ERROR_INTEGER_OVERFLOW,
+                format!("Integer {} is out of range and the gmp extension is not loaded", raw),
+            )
+        })?;
+        gmp_init.try_call(vec![&raw]).map_err(JsonError::engine)
+    }
+
+    /// Hydrates an ext-decimal `Decimal` with enough precision to hold every
+    /// digit of the lexeme.
+    fn make_decimal(&self, raw: &str) -> Result<Zval, JsonError> {
+        let ce = ClassEntry::try_find(DECIMAL_CLASS).ok_or_else(|| {
+            JsonError::new(flags::ERROR_STATE_MISMATCH, "The decimal extension is not loaded")
+        })?;
+        let mantissa = raw.split(['e', 'E']).next().unwrap_or(raw);
+        let digits = mantissa.bytes().filter(u8::is_ascii_digit).count() as i64;
+
+        let result = ZendObject::new(ce);
+        result
+            .try_call_method("__construct", vec![&raw, &digits.max(DECIMAL_DEFAULT_PRECISION)])
+            .map_err(JsonError::engine)?;
+        result.into_zval(false).map_err(JsonError::engine)
+    }
+
+    fn make_double(&self, raw: &str) -> Result<Zval, JsonError> {
+        // The reader only yields well-formed lexemes, which always parse,
+        // though possibly to infinity.
+        let value = raw.parse::<f64>().unwrap_or(f64::NAN);
+        if self.config.options.finite_floats && !value.is_finite() {
+            return Err(JsonError::new(
+                flags::ERROR_FLOAT_OVERFLOW,
+                format!("Number {} is out of float range", raw),
+            ));
         }
+        Ok(Zval::from(value))
     }
 
-    fn convert_array(&self, arr: Vec<Value>, depth: i64) -> PhpResult<Zval> {
+    fn read_array(&self, reader: &mut Reader) -> Result<Zval, JsonError> {
         let mut result = ZendHashTable::new();
+        let depth = reader.depth();
 
-        for (i, item) in arr.into_iter().enumerate() {
-            let php_val = self.convert(item, depth + 1)?;
-            result.insert_at_index(i as i64, php_val)?;
+        loop {
+            let event = self.next_event(reader)?;
+            if event == Event::EndArray {
+                break;
+            }
+            if self.config.options.truncate_items.is_some_and(|max| result.len() >= max) {
+                let start = reader.token_start();
+                self.skip_until(reader, depth - 1)?;
+                let end = reader.token_start();
+                result.push(self.make_truncated(start, end)?).map_err(JsonError::engine)?;
+                break;
+            }
+            self.check_count(result.len() + 1, self.config.max_array_items, "array items")?;
+            let index = result.len().to_string();
+            let php_val = self.read_child(reader, event, &index, &index)?;
+            result.push(php_val).map_err(JsonError::engine)?;
         }
 
         let mut zval = Zval::new();
@@ -123,176 +1370,771 @@ impl JsonDecoder {
         Ok(zval)
     }
 
-    fn convert_object(&self, obj: Map<String, Value>, depth: i64) -> PhpResult<Zval> {
+    fn read_object(&self, reader: &mut Reader) -> Result<Zval, JsonError> {
+        let options = &self.config.options;
+        let mut members: Vec<(String, Zval)> = Vec::new();
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        let mut count = 0;
+        // Whether every member so far is named like a binary envelope.
+        let mut envelope = options.binary_envelope.is_some();
+
+        loop {
+            let raw = match self.next_event(reader)? {
+                Event::EndObject => break,
+                Event::Key(raw) => raw,
+                _ => return Err(JsonError::syntax("expected object key")),
+            };
+            // Repeated keys count too: each one costs a parse.
+            count += 1;
+            self.check_count(count, self.config.max_object_members, "object members")?;
+            let name = self.read_string(raw)?;
+            if count == 1 && self.config.annotated && name == ion::ANNOTATIONS {
+                return self.read_annotated(reader);
+            }
+            envelope &= options.binary_envelope.as_deref() == Some(name.as_str());
+            let key = self.transform_key(&name)?;
+            let event = self.next_event(reader)?;
+            let php_val = self.read_child(reader, event, &name, &key)?;
+
+            match (seen.get(&key), options.duplicate_keys) {
+                (None, _) => {
+                    seen.insert(key.clone(), members.len());
+                    members.push((key, php_val));
+                }
+                (Some(&i), DuplicateKeys::Last) => members[i].1 = php_val,
+                (Some(_), DuplicateKeys::First) => {}
+                (Some(_), DuplicateKeys::Error) => {
+                    return Err(JsonError::new(
+                        flags::ERROR_DUPLICATE_KEY,
+                        format!("Duplicate key \"{}\"", key.escape_default()),
+                    ));
+                }
+            }
+        }
+
+        let wrapped = members.first().filter(|_| envelope && count == 1);
+        if let Some(encoded) = wrapped.and_then(|(_, value)| value.str()) {
+            let bytes = base64url::decode_standard(encoded).map_err(|e| {
+                JsonError::new(flags::ERROR_SYNTAX, format!("{} in binary envelope", e))
+            })?;
+            let mut zval = Zval::new();
+            zval.set_binary(bytes);
+            return Ok(zval);
+        }
+
+        match options.mode {
+            ObjectMode::Object => self.make_object(members),
+            ObjectMode::Array => {
+                let mut zval = Zval::new();
+                zval.set_hashtable(self.make_table(members)?);
+                Ok(zval)
+            }
+            ObjectMode::Map => self.make_map(members),
+            ObjectMode::Node => JsonObject::new(self.make_table(members)?)
+                .into_zval(false)
+                .map_err(JsonError::engine),
+        }
+    }
+
+    /// Reads the rest of an annotation envelope written by [`ion::to_json`].
+    fn read_annotated(&self, reader: &mut Reader) -> Result<Zval, JsonError> {
+        let malformed = || JsonError::syntax("malformed Ion annotations");
+        if self.next_event(reader)? != Event::BeginArray {
+            return Err(malformed());
+        }
+        let mut annotations = Vec::new();
+        loop {
+            match self.next_event(reader)? {
+                Event::String(raw) => annotations.push(self.read_string(raw)?),
+                Event::EndArray => break,
+                _ => return Err(malformed()),
+            }
+        }
+        match self.next_event(reader)? {
+            Event::Key(raw) if self.read_string(raw)? == ion::VALUE => {}
+            _ => return Err(malformed()),
+        }
+        let event = self.next_event(reader)?;
+        let value = self.read_value(reader, event)?;
+        if self.next_event(reader)? != Event::EndObject {
+            return Err(malformed());
+        }
+        Annotated::new(annotations, value).into_zval(false).map_err(JsonError::engine)
+    }
+
+    /// Applies the prefix, case and callback key rewrites, in that order.
+    fn transform_key(&self, name: &str) -> Result<String, JsonError> {
+        let options = &self.config.options;
+        let stripped = match &options.key_prefix {
+            Some(prefix) => name.strip_prefix(prefix.as_str()).unwrap_or(name),
+            None => name,
+        };
+        let key = options.key_case.apply(stripped.to_string());
+        let Some(transform) = &options.key_transform else {
+            return Ok(key);
+        };
+
+        let result = transform.call(vec![&key])?;
+        result.string().filter(|_| result.is_string()).ok_or_else(|| {
+            JsonError::new(
+                flags::ERROR_STATE_MISMATCH,
+                format!("Key transform must return a string for key \"{}\"", key.escape_default()),
+            )
+        })
+    }
+
+    fn make_table(&self, members: Vec<(String, Zval)>) -> Result<ZBox<ZendHashTable>, JsonError> {
         let mut result = ZendHashTable::new();
+        for (key, val) in members {
+            result.insert(key.as_str(), val).map_err(JsonError::engine)?;
+        }
+        Ok(result)
+    }
+
+    fn make_object(&self, members: Vec<(String, Zval)>) -> Result<Zval, JsonError> {
+        let mut result = ZendObject::new_stdclass();
 
-        for (key, val) in obj {
-            let php_val = self.convert(val, depth + 1)?;
-            result.insert(&*key, php_val)?;
+        for (key, val) in members {
+            if key.starts_with('\0') {
+                return Err(JsonError::new(
+                    flags::ERROR_INVALID_PROPERTY_NAME,
+                    format!("Cannot decode key \"{}\" as an object property", key.escape_default()),
+                ));
+            }
+            result.set_property(&key, val).map_err(JsonError::engine)?;
         }
 
-        let mut zval = Zval::new();
-        zval.set_hashtable(result);
-        Ok(zval)
+        result.into_zval(false).map_err(JsonError::engine)
     }
+
+    fn make_map(&self, members: Vec<(String, Zval)>) -> Result<Zval, JsonError> {
+        let ce = ClassEntry::try_find("ArrayObject")
+            .ok_or_else(|| JsonError::new(flags::ERROR_STATE_MISMATCH, "ArrayObject is not available"))?;
+        let result = ZendObject::new(ce);
+        let mut storage = Zval::new();
+        storage.set_hashtable(self.make_table(members)?);
+        result.try_call_method("__construct", vec![&storage]).map_err(JsonError::engine)?;
+
+        result.into_zval(false).map_err(JsonError::engine)
+    }
+}
+
+/// Default member name for `BINARY_AS_BASE64` envelopes.
+const BINARY_ENVELOPE: &str = "$binary";
+
+/// ext-decimal's arbitrary-precision number class.
+const DECIMAL_CLASS: &str = "Decimal\\Decimal";
+const CLOSURE_CLASS: &str = "Closure";
+/// What `EncoderOptions::valueMethods()` looks for without arguments.
+const DEFAULT_VALUE_METHODS: &[&str] = &["toJson", "jsonValue"];
+/// `Decimal::DEFAULT_PRECISION`.
+const DECIMAL_DEFAULT_PRECISION: i64 = 28;
+
+/// ext/json's output rules, followed under `COMPAT`.
+#[derive(Clone, Copy)]
+struct NativeFormat {
+    serialize_precision: i64,
+    zero_fraction: bool,
+    force_object: bool,
+    numeric_check: bool,
 }
 
 struct EncodeConfig {
-    pretty: bool,
-    unescaped_unicode: bool,
+    indent: Option<usize>,
+    style: Style,
+    trailing_newline: bool,
+    escaping: Escaping,
+    sort_keys: bool,
+    skip_nulls: bool,
+    max_depth: i64,
+    buffer_size: usize,
+    non_finite: NonFinite,
+    empty_arrays: EmptyArrays,
+    float_format: FloatFormat,
+    /// Called on every value before it is written; see `Codec::encode()`.
+    replacer: Option<Zval>,
+    /// Member name of the object invalid UTF-8 strings are wrapped in.
+    binary_envelope: Option<String>,
+    pace: Option<Pace>,
+    /// See `EncoderOptions::debugInfo()`.
+    debug_info: bool,
+    value_methods: Vec<String>,
+    /// Whether `Annotated` values keep their annotations, see
+    /// `Codec::encodeIon()`.
+    annotated: bool,
+    /// See `Codec::registerAdapter()`.
+    adapters: Vec<(&'static ClassEntry, Callback)>,
+    /// `JSON_PARTIAL_OUTPUT_ON_ERROR`: values of unsupported types are
+    /// written as `null` instead of failing.
+    partial_output: bool,
+    native: Option<NativeFormat>,
 }
 
 impl EncodeConfig {
-    fn from_flags(flags: i64) -> Self {
+    /// The escapes `options` select; `COMPAT` keeps ext/json's.
+    fn escaping(options: &EncoderOptions) -> Escaping {
+        match (options.flags & flags::COMPAT != 0, options.minimal_escapes) {
+            (true, _) => Escaping::from_flags(options.flags),
+            (false, true) => Escaping { hex_case: options.hex_case, ..Escaping::minimal() },
+            (false, false) => Escaping { hex_case: options.hex_case, ..Escaping::from_flags(options.flags) },
+        }
+    }
+
+    /// Resolves omitted settings from the INI defaults. An explicit `depth`
+    /// argument takes precedence over the options' own limit.
+    ///
+    /// `COMPAT` overrides the indentation, escaping and float options with
+    /// ext/json's layout, escapes and `serialize_precision`.
+    fn new(options: &EncoderOptions, depth: Option<i64>) -> Self {
+        let has = |flag: i64| options.flags & flag != 0;
+        let pretty = has(flags::PRETTY_PRINT);
+        let native = has(flags::COMPAT).then(|| NativeFormat {
+            serialize_precision: ini::serialize_precision(),
+            zero_fraction: has(flags::PRESERVE_ZERO_FRACTION),
+            force_object: has(flags::FORCE_OBJECT),
+            numeric_check: has(flags::NUMERIC_CHECK),
+        });
+        let (indent, style) = match native {
+            Some(_) => (pretty.then_some(4), Style::Expanded),
+            None => (
+                options.indent.or((pretty || options.style.is_some()).then_some(2)),
+                options.style.unwrap_or_default(),
+            ),
+        };
         Self {
-            pretty: (flags & 128) != 0,
-            unescaped_unicode: (flags & 256) != 0,
+            indent,
+            style,
+            trailing_newline: options.trailing_newline,
+            escaping: Self::escaping(options),
+            sort_keys: options.sort_keys,
+            skip_nulls: options.skip_nulls,
+            max_depth: depth.or(options.max_depth).unwrap_or_else(ini::default_depth),
+            buffer_size: ini::buffer_size(),
+            non_finite: options.non_finite,
+            empty_arrays: options.empty_arrays,
+            float_format: options.float_format,
+            replacer: None,
+            binary_envelope: options.binary_envelope.clone().or_else(|| {
+                (options.flags & flags::BINARY_AS_BASE64 != 0).then(|| BINARY_ENVELOPE.to_string())
+            }),
+            pace: options.pace.clone(),
+            debug_info: options.debug_info,
+            value_methods: options.value_methods.clone(),
+            annotated: false,
+            adapters: adapters::serializers(),
+            partial_output: has(flags::PARTIAL_OUTPUT_ON_ERROR),
+            native,
+        }
+    }
+
+    fn from_flags(flags: Option<i64>, depth: Option<i64>) -> Self {
+        let flags = flags.unwrap_or_else(ini::default_encode_flags);
+        Self::new(&EncoderOptions::from_flags(flags), depth)
+    }
+
+    fn writer_config(&self) -> WriterConfig {
+        WriterConfig {
+            indent: self.indent.map(|width| " ".repeat(width)),
+            style: self.style,
+            escaping: self.escaping,
+            palette: None,
+            capacity: self.buffer_size,
         }
     }
 }
 
-struct JsonEncoder {
+/// Output is handed to an encoder's sink once this much is pending.
+const SINK_CHUNK: usize = 64 * 1024;
+
+type Sink<'s> = RefCell<&'s mut dyn FnMut(&str) -> Result<(), JsonError>>;
+
+struct JsonEncoder<'s> {
     config: EncodeConfig,
+    /// JSON Pointer of the value being written; only kept up with a
+    /// replacer.
+    pointer: RefCell<String>,
+    /// The first error `JSON_PARTIAL_OUTPUT_ON_ERROR` wrote `null` for.
+    partial_error: RefCell<Option<JsonError>>,
+    pacer: Option<Pacer>,
+    /// Where `encode_into()` hands the output as it accumulates.
+    sink: Option<Sink<'s>>,
 }
 
-impl JsonEncoder {
+impl<'s> JsonEncoder<'s> {
     fn new(config: EncodeConfig) -> Self {
-        Self { config }
+        Self {
+            pacer: config.pace.clone().map(Pacer::new),
+            config,
+            pointer: RefCell::new(String::new()),
+            partial_error: RefCell::new(None),
+            sink: None,
+        }
     }
 
-    fn encode(&self, value: &mut Zval) -> Result<String, String> {
-        let json_value = self.convert(value)?;
-        self.serialize(json_value)
+    /// Encodes `value` into `sink` a chunk at a time instead of building the
+    /// whole text, and returns its length.
+    fn encode_into(
+        config: EncodeConfig,
+        value: &Zval,
+        sink: &'s mut dyn FnMut(&str) -> Result<(), JsonError>,
+    ) -> Result<usize, JsonError> {
+        let encoder = Self {
+            sink: Some(RefCell::new(sink)),
+            ..Self::new(config)
+        };
+        let writer_config = encoder.config.writer_config();
+        let mut writer = JsonWriter::new(&writer_config);
+        encoder.write_root(&mut writer, value)?;
+
+        let newline = if encoder.config.trailing_newline { "\n" } else { "" };
+        if let Some(sink) = &encoder.sink {
+            let mut sink = sink.borrow_mut();
+            writer.flush(|chunk| sink(chunk))?;
+            sink(newline)?;
+        }
+        Ok(writer.len() + newline.len())
     }
 
-    fn convert(&self, value: &mut Zval) -> Result<Value, String> {
-        if value.is_null() {
-            return Ok(Value::Null);
+    /// The error the output would have failed with without
+    /// `JSON_PARTIAL_OUTPUT_ON_ERROR`, for `json_last_error()`.
+    fn take_partial_error(&self) -> Option<JsonError> {
+        self.partial_error.borrow_mut().take()
+    }
+
+    fn encode(&self, value: &Zval) -> Result<String, JsonError> {
+        let mut json = self.encode_with(&self.config.writer_config(), value)?;
+        if self.config.trailing_newline {
+            json.push('\n');
         }
-        if value.is_true() {
-            return Ok(Value::Bool(true));
+        Ok(json)
+    }
+
+    /// Encodes with layout and coloring taken from `writer_config`.
+    fn encode_with(&self, writer_config: &WriterConfig, value: &Zval) -> Result<String, JsonError> {
+        let mut writer = JsonWriter::new(writer_config);
+        self.write_root(&mut writer, value)?;
+        Ok(writer.finish())
+    }
+
+    /// Encodes each value after the previous one, `separator` in between,
+    /// growing a single output buffer.
+    fn encode_many<'z>(
+        &self,
+        values: impl Iterator<Item = &'z Zval>,
+        separator: &str,
+    ) -> Result<String, JsonError> {
+        let writer_config = self.config.writer_config();
+        let mut out = String::with_capacity(writer_config.capacity);
+        for (i, value) in values.enumerate() {
+            if i > 0 {
+                out.push_str(separator);
+            }
+            let mut writer = JsonWriter::appending(&writer_config, out);
+            self.write_root(&mut writer, value)?;
+            out = writer.finish();
+        }
+        Ok(out)
+    }
+
+    fn write_root(&self, writer: &mut JsonWriter, value: &Zval) -> Result<(), JsonError> {
+        match self.replace("", value)? {
+            Replaced::Unchanged => self.write(writer, value, 0),
+            Replaced::With(value) => self.write(writer, &value, 0),
+            Replaced::Skip => {
+                writer.null();
+                Ok(())
+            }
+        }
+    }
+
+    fn write(&self, writer: &mut JsonWriter, value: &Zval, depth: i64) -> Result<(), JsonError> {
+        if depth > self.config.max_depth {
+            return Err(JsonError::depth());
         }
-        if value.is_false() {
-            return Ok(Value::Bool(false));
+        if let Some(pacer) = &self.pacer {
+            pacer.advance(writer.len())?;
+        }
+        if let Some(sink) = self.sink.as_ref().filter(|_| writer.pending() >= SINK_CHUNK) {
+            writer.flush(|chunk| (*sink.borrow_mut())(chunk))?;
+        }
+        if value.is_null() {
+            writer.null();
+            return Ok(());
         }
-        if value.is_long() {
-            return self.convert_long(value);
+        if let Some(b) = value.bool() {
+            writer.bool(b);
+            return Ok(());
+        }
+        if let Some(n) = value.long() {
+            writer.number(&n.to_string());
+            return Ok(());
         }
         if value.is_double() {
-            return self.convert_double(value);
+            return self.write_double(writer, value);
         }
         if value.is_string() {
-            return self.convert_string(value);
+            if let (None, Some(envelope)) = (value.str(), &self.config.binary_envelope) {
+                let bytes = value.zend_str().map(|s| s.as_bytes()).unwrap_or_default();
+                writer.begin_object();
+                writer.key(envelope);
+                writer.string(&base64url::encode_standard(bytes));
+                writer.end_object();
+                return Ok(());
+            }
+            let s = value.str()
+                .ok_or_else(|| JsonError::new(flags::ERROR_UTF8, "Malformed UTF-8 in string"))?;
+            match self.config.native.filter(|native| native.numeric_check) {
+                Some(native) => self.write_numeric_check(writer, s, native),
+                None => writer.string(s),
+            }
+            return Ok(());
+        }
+        if let Some(arr) = value.array() {
+            let force_object = self.config.native.is_some_and(|native| native.force_object);
+            let empty_object = arr.is_empty() && self.config.empty_arrays == EmptyArrays::Object;
+            if !force_object && !empty_object && is_list(arr) {
+                return self.write_list(writer, arr, depth);
+            }
+            return self.write_members(writer, arr, false, depth);
+        }
+        if let Some(number) = value.extract::<&Number>() {
+            writer.number(number.lexeme());
+            return Ok(());
         }
-        if value.is_array() {
-            return self.convert_array(value);
+        if let Some(arr) = value.extract::<&AsObject>().and_then(AsObject::array) {
+            return self.write_members(writer, arr, false, depth);
         }
-        if value.is_object() {
-            return self.convert_object(value);
+        if let Some(arr) = value.extract::<&AsList>().and_then(AsList::array) {
+            return self.write_list(writer, arr, depth);
+        }
+        if let Some(object) = value.extract::<&JsonObject>() {
+            return self.write_members(writer, object.members(), false, depth);
+        }
+        if let Some(annotated) = value.extract::<&Annotated>() {
+            return self.write_annotated(writer, annotated, depth);
+        }
+        if let Some(obj) = value.object() {
+            if let Some((_, serialize)) = self.config.adapters.iter().find(|(ce, _)| obj.instance_of(ce)) {
+                // One level deeper, so an adapter returning its own class
+                // runs into the depth limit rather than looping.
+                return self.write(writer, &serialize.call(vec![value])?, depth + 1);
+            }
+            match obj.get_class_name() {
+                Ok(name) if name == DECIMAL_CLASS => return self.write_decimal(writer, obj),
+                Ok(name) if name == CLOSURE_CLASS => return self.write_unsupported(writer, &name),
+                _ => {}
+            }
+            for method in &self.config.value_methods {
+                match obj.try_call_method(method, vec![]) {
+                    Ok(result) => return self.write(writer, &result, depth + 1),
+                    Err(ext_php_rs::error::Error::Callable) => {}
+                    Err(err) => return Err(JsonError::engine(err)),
+                }
+            }
+            if self.config.debug_info {
+                match obj.try_call_method("__debugInfo", vec![]) {
+                    Ok(info) => return self.write_debug_info(writer, &info, depth),
+                    Err(ext_php_rs::error::Error::Callable) => {}
+                    Err(err) => return Err(JsonError::engine(err)),
+                }
+            }
+            let properties = obj.get_properties().map_err(JsonError::engine)?;
+            return self.write_members(writer, properties, true, depth);
+        }
+        if value.is_reference() {
+            return self.write(writer, value.dereference(), depth);
         }
 
-        Err("Unsupported PHP type".to_string())
+        self.write_unsupported(writer, &value.get_type().to_string().to_lowercase())
     }
 
-    fn convert_long(&self, value: &mut Zval) -> Result<Value, String> {
-        value.long()
-            .map(Value::from)
-            .ok_or_else(|| "Failed to read integer".to_string())
+    /// The value alone, or in an envelope [`ion::from_json`] turns back into
+    /// annotations.
+    fn write_annotated(&self, writer: &mut JsonWriter, annotated: &Annotated, depth: i64) -> Result<(), JsonError> {
+        let annotations = annotated.annotations_ref();
+        if !self.config.annotated || annotations.is_empty() {
+            return self.write(writer, annotated.value_ref(), depth);
+        }
+        writer.begin_object();
+        writer.key(ion::ANNOTATIONS);
+        writer.begin_array();
+        for annotation in annotations {
+            writer.string(annotation);
+        }
+        writer.end_array();
+        writer.key(ion::VALUE);
+        self.write(writer, annotated.value_ref(), depth + 1)?;
+        writer.end_object();
+        Ok(())
     }
 
-    fn convert_double(&self, value: &mut Zval) -> Result<Value, String> {
-        value.double()
-            .and_then(|f| {
-                if f.is_finite() {
-                    serde_json::Number::from_f64(f).map(Value::Number)
-                } else {
-                    Some(Value::Null)
-                }
-            })
-            .ok_or_else(|| "Failed to read float".to_string())
+    /// Closures, resources and the like: `JSON_ERROR_UNSUPPORTED_TYPE`, or
+    /// `null` under `JSON_PARTIAL_OUTPUT_ON_ERROR` as in ext/json.
+    fn write_unsupported(&self, writer: &mut JsonWriter, type_name: &str) -> Result<(), JsonError> {
+        let err = JsonError::new(
+            flags::ERROR_UNSUPPORTED_TYPE,
+            format!("Unsupported PHP type ({})", type_name),
+        );
+        if !self.config.partial_output {
+            return Err(err);
+        }
+        self.partial_error.borrow_mut().get_or_insert(err);
+        writer.null();
+        Ok(())
     }
 
-    fn convert_string(&self, value: &mut Zval) -> Result<Value, String> {
-        value.str()
-            .map(|s| Value::String(s.to_string()))
-            .ok_or_else(|| "Failed to read string".to_string())
+    /// Writes what an object's `__debugInfo()` returned in place of its
+    /// properties.
+    fn write_debug_info(&self, writer: &mut JsonWriter, info: &Zval, depth: i64) -> Result<(), JsonError> {
+        let info = info.array().ok_or_else(|| {
+            JsonError::new(flags::ERROR_UNSUPPORTED_TYPE, "__debugInfo() must return an array")
+        })?;
+        self.write_members(writer, info, true, depth)
     }
 
-    fn convert_array(&self, value: &mut Zval) -> Result<Value, String> {
-        let arr = value.array()
-            .ok_or_else(|| "Failed to read array".to_string())?;
+    fn write_double(&self, writer: &mut JsonWriter, value: &Zval) -> Result<(), JsonError> {
+        let f = value.double()
+            .ok_or_else(|| JsonError::new(flags::ERROR_UNSUPPORTED_TYPE, "Failed to read float"))?;
 
-        if self.is_sequential_array(&arr) {
-            self.array_to_json_array(&arr)
+        let Some(n) = serde_json::Number::from_f64(f) else {
+            return self.write_non_finite(writer, f);
+        };
+        if let Some(native) = self.config.native {
+            writer.number(&number::format_native(f, native.serialize_precision, native.zero_fraction));
+        } else if self.config.float_format == FloatFormat::default() {
+            writer.number(&n.to_string());
         } else {
-            self.array_to_json_object(&arr)
+            writer.number(&number::format_float(f, &self.config.float_format));
         }
+        Ok(())
     }
 
-    fn convert_object(&self, value: &mut Zval) -> Result<Value, String> {
-        let arr = value.array()
-            .ok_or_else(|| "Failed to read object properties".to_string())?;
+    /// `JSON_NUMERIC_CHECK`: numeric strings are written as numbers, except
+    /// those overflowing to `INF`.
+    fn write_numeric_check(&self, writer: &mut JsonWriter, s: &str, native: NativeFormat) {
+        match number::parse_numeric(s) {
+            Some(Numeric::Int(i)) => writer.number(&i.to_string()),
+            Some(Numeric::Float(f)) if f.is_finite() => {
+                writer.number(&number::format_native(f, native.serialize_precision, native.zero_fraction))
+            }
+            _ => writer.string(s),
+        }
+    }
 
-        self.array_to_json_object(&arr)
+    fn write_non_finite(&self, writer: &mut JsonWriter, f: f64) -> Result<(), JsonError> {
+        match self.config.non_finite {
+            NonFinite::Error => {
+                return Err(JsonError::new(
+                    flags::ERROR_INF_OR_NAN,
+                    "Inf and NaN cannot be JSON encoded",
+                ));
+            }
+            NonFinite::Null => writer.null(),
+            NonFinite::String if f.is_nan() => writer.string("NaN"),
+            NonFinite::String if f > 0.0 => writer.string("Infinity"),
+            NonFinite::String => writer.string("-Infinity"),
+        }
+        Ok(())
     }
 
-    fn is_sequential_array(&self, arr: &ZendHashTable) -> bool {
-        let mut expected_index = 0i64;
+    /// Writes an ext-decimal `Decimal` as the exact number it holds.
+    fn write_decimal(&self, writer: &mut JsonWriter, obj: &ZendObject) -> Result<(), JsonError> {
+        let value = obj.try_call_method("toString", vec![]).map_err(JsonError::engine)?;
+        let s = value.str()
+            .ok_or_else(|| JsonError::new(flags::ERROR_UNSUPPORTED_TYPE, "Failed to read Decimal"))?;
 
-        for (key, _) in arr.iter() {
-            if !key.is_long() {
-                return false;
-            }
+        if is_number(s) {
+            writer.number(s);
+            return Ok(());
+        }
+        // `NAN`, `INF` and `-INF`, which Rust parses case-insensitively.
+        self.write_non_finite(writer, s.parse().unwrap_or(f64::NAN))
+    }
 
-            let key_str = key.to_string();
-            if let Ok(index) = key_str.parse::<i64>() {
-                if index != expected_index {
-                    return false;
-                }
-                expected_index += 1;
-            } else {
-                return false;
-            }
+    /// What `arr` holds, for styles that lay lists out by their contents.
+    fn shape(&self, arr: &ZendHashTable) -> ArrayShape {
+        if self.config.style == Style::Expanded || self.config.indent.is_none() {
+            return ArrayShape::Mixed;
         }
+        let containers = arr.values().filter(|val| val.is_array() || val.is_object()).count();
+        match containers {
+            0 => ArrayShape::Scalars,
+            n if n == arr.len() => ArrayShape::Containers,
+            _ => ArrayShape::Mixed,
+        }
+    }
 
-        true
+    fn write_list(&self, writer: &mut JsonWriter, arr: &ZendHashTable, depth: i64) -> Result<(), JsonError> {
+        writer.begin_array_of(self.shape(arr));
+        for (i, (_, val)) in arr.iter().enumerate() {
+            if self.config.replacer.is_none() {
+                self.write(writer, val, depth + 1).map_err(|err| err.at(&i.to_string()))?;
+                continue;
+            }
+            let index = i.to_string();
+            self.write_child(writer, &index, val, depth + 1, |writer, written| {
+                if !written {
+                    writer.null();
+                }
+            })?;
+        }
+        writer.end_array();
+        Ok(())
     }
 
-    fn array_to_json_array(&self, arr: &ZendHashTable) -> Result<Value, String> {
-        let mut result = Vec::new();
+    /// Writes a hash table as a JSON object. For object property tables,
+    /// mangled (non-public) names are skipped as ext/json does.
+    fn write_members(
+        &self,
+        writer: &mut JsonWriter,
+        arr: &ZendHashTable,
+        properties: bool,
+        depth: i64,
+    ) -> Result<(), JsonError> {
+        let mut members: Vec<(String, &Zval)> = arr
+            .iter()
+            .map(|(key, val)| (key.to_string(), val))
+            .filter(|(key, _)| !(properties && key.starts_with('\0')))
+            .filter(|(_, val)| !(self.config.skip_nulls && val.is_null()))
+            .collect();
 
-        for (_, val) in arr.iter() {
-            let mut val_copy = val.shallow_clone();
-            result.push(self.convert(&mut val_copy)?);
+        if self.config.sort_keys {
+            members.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+
+        writer.begin_object();
+        for (key, val) in members {
+            if self.config.replacer.is_none() {
+                writer.key(&key);
+                self.write(writer, val, depth + 1).map_err(|err| err.at(&key))?;
+                continue;
+            }
+            self.write_child(writer, &key, val, depth + 1, |writer, written| {
+                if written {
+                    writer.key(&key);
+                }
+            })?;
         }
+        writer.end_object();
+        Ok(())
+    }
 
-        Ok(Value::Array(result))
+    /// Writes an element or member through the replacer. `before` runs
+    /// first, told whether the value is written, i.e. not skipped.
+    fn write_child(
+        &self,
+        writer: &mut JsonWriter,
+        key: &str,
+        value: &Zval,
+        depth: i64,
+        before: impl FnOnce(&mut JsonWriter, bool),
+    ) -> Result<(), JsonError> {
+        let len = self.pointer.borrow().len();
+        {
+            let mut pointer = self.pointer.borrow_mut();
+            pointer.push('/');
+            pointer.push_str(&pointer::escape(key));
+        }
+        let result = self.replace(key, value).and_then(|replaced| {
+            before(writer, !matches!(replaced, Replaced::Skip));
+            match replaced {
+                Replaced::Unchanged => self.write(writer, value, depth),
+                Replaced::With(value) => self.write(writer, &value, depth),
+                Replaced::Skip => Ok(()),
+            }
+        });
+        self.pointer.borrow_mut().truncate(len);
+        result.map_err(|err| err.at(key))
     }
 
-    fn array_to_json_object(&self, arr: &ZendHashTable) -> Result<Value, String> {
-        let mut result = Map::new();
+    fn replace(&self, key: &str, value: &Zval) -> Result<Replaced, JsonError> {
+        let Some(replacer) = &self.config.replacer else {
+            return Ok(Replaced::Unchanged);
+        };
+        let pointer = self.pointer.borrow().clone();
+        let result = ZendCallable::new(replacer)
+            .and_then(|replacer| replacer.try_call(vec![&key, value, &pointer]))
+            .map_err(JsonError::engine)?;
 
-        for (key, val) in arr.iter() {
-            let key_str = key.to_string();
-            let mut val_copy = val.shallow_clone();
-            result.insert(key_str, self.convert(&mut val_copy)?);
+        if result.object().is_some_and(|obj| obj.is_instance::<Skip>()) {
+            return Ok(Replaced::Skip);
         }
+        Ok(Replaced::With(result))
+    }
+}
+
+/// What a replacer made of a value.
+enum Replaced {
+    /// No replacer is set.
+    Unchanged,
+    With(Zval),
+    Skip,
+}
 
-        Ok(Value::Object(result))
+pub fn startup(_ty: i32, module_number: i32) -> i32 {
+    // The globals are laid out for what build.rs found; loading into a PHP
+    // that differs would corrupt them.
+    if cfg!(php_zts) != ext_php_rs::PHP_ZTS {
+        php_error(
+            &ErrorType::CoreWarning,
+            "elephant_json was built for a different thread safety mode than this PHP; rebuild it against this PHP",
+        );
+        return -1;
     }
+    globals::startup();
+    ini::register(module_number);
+    register_missing_constants(module_number);
+    0
+}
 
-    fn serialize(&self, value: Value) -> Result<String, String> {
-        let result = if self.config.pretty {
-            serde_json::to_string_pretty(&value)
-        } else {
-            serde_json::to_string(&value)
-        };
+pub extern "C" fn shutdown(_ty: i32, _module_number: i32) -> i32 {
+    globals::shutdown();
+    0
+}
+
+/// Defines the global `JSON_*` constants when ext/json is not loaded.
+fn register_missing_constants(module_number: i32) {
+    let defined = |name: &str| {
+        ExecutorGlobals::get()
+            .constants()
+            .is_some_and(|constants| constants.get(name).is_some())
+    };
 
-        result.map_err(|e| format!("JSON serialization error: {}", e))
+    for (name, value) in flags::GLOBAL_CONSTANTS {
+        if !defined(name) {
+            let _ = value.register_constant(name, module_number);
+        }
     }
 }
 
 #[php_module]
+#[php(startup = "startup")]
 pub fn get_module(module: ModuleBuilder) -> ModuleBuilder {
-    module
-}
\ No newline at end of file
+    let module = module
+        .class::<Codec>()
+        .class::<Json>()
+        .class::<EncoderOptions>()
+        .class::<DecoderOptions>()
+        .class::<Truncated>()
+        .class::<Number>()
+        .class::<Document>()
+        .class::<Value>()
+        .class::<Pointer>()
+        .class::<Tokens>()
+        .class::<LinesReader>()
+        .class::<LinesWriter>()
+        .class::<ArrayCursor>()
+        .class::<Skip>()
+        .class::<AsObject>()
+        .class::<AsList>()
+        .class::<JsonObject>()
+        .class::<Annotated>()
+        .class::<Schema>()
+        .class::<Template>()
+        .shutdown_function(shutdown)
+        .request_startup_function(state::request_startup)
+        .request_shutdown_function(state::request_shutdown)
+        .info_function(info::php_module_info);
+
+    native::register(functions::register(module))
+}
@@ -0,0 +1,509 @@
+//! Newline-delimited JSON (NDJSON, JSON Lines) over streams, one record at
+//! a time so memory use is bounded by the longest line.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+
+use ext_php_rs::binary::Binary;
+use ext_php_rs::boxed::ZBox;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ZendCallable, ZendClassObject, ZendHashTable, Zval};
+use ext_php_rs::zend::ce;
+
+use crate::checkpoint::Checkpoint;
+use crate::error::JsonError;
+use crate::options::{DecoderOptions, EncoderOptions, Pace};
+use crate::pacing::Pacer;
+use crate::schema::Schema;
+use crate::skip::Skip;
+use crate::{decode_bytes, file, flags, ini, native, stream, DecodeConfig, EncodeConfig, JsonEncoder};
+
+/// One line of input, without its line break.
+pub(crate) struct Line {
+    /// 1-based line number.
+    pub number: usize,
+    /// Byte offset of the line's start in the input.
+    pub offset: usize,
+    pub bytes: Vec<u8>,
+}
+
+impl Line {
+    /// NDJSON readers conventionally skip empty and whitespace-only lines.
+    pub fn is_blank(&self) -> bool {
+        self.bytes.iter().all(|b| matches!(b, b' ' | b'\t' | b'\r'))
+    }
+}
+
+/// Splits a stream resource or PSR-7 stream into lines, reading a chunk at
+/// a time. `\n` ends a line; a `\r` before it is dropped.
+pub(crate) struct Lines {
+    stream: Zval,
+    position: u8,
+    buffer: Vec<u8>,
+    /// Start of the unconsumed part of `buffer`.
+    start: usize,
+    eof: bool,
+    number: usize,
+    /// Input offset of `buffer[start]`.
+    offset: usize,
+}
+
+impl Lines {
+    pub fn new(stream: &Zval, position: u8) -> Self {
+        Self {
+            stream: stream.shallow_clone(),
+            position,
+            buffer: Vec::new(),
+            start: 0,
+            eof: false,
+            number: 0,
+            offset: 0,
+        }
+    }
+
+    /// Carries on from `offset` bytes past the stream's current position,
+    /// after line `number`.
+    pub fn resume(stream: &Zval, position: u8, offset: usize, number: usize) -> PhpResult<Self> {
+        let origin = stream::tell(stream)?.unwrap_or(0);
+        if !stream::seek(stream, origin + offset as i64)? {
+            return Err("The stream is not seekable".into());
+        }
+        Ok(Self {
+            number,
+            offset,
+            ..Self::new(stream, position)
+        })
+    }
+
+    pub fn next_line(&mut self) -> PhpResult<Option<Line>> {
+        loop {
+            let rest = &self.buffer[self.start..];
+            let end = match rest.iter().position(|&b| b == b'\n') {
+                Some(newline) => Some((newline, newline + 1)),
+                None if self.eof && !rest.is_empty() => Some((rest.len(), rest.len())),
+                None if self.eof => return Ok(None),
+                None => None,
+            };
+
+            if let Some((len, consumed)) = end {
+                let mut bytes = rest[..len].to_vec();
+                if bytes.last() == Some(&b'\r') {
+                    bytes.pop();
+                }
+                self.number += 1;
+                let line = Line {
+                    number: self.number,
+                    offset: self.offset,
+                    bytes,
+                };
+                self.start += consumed;
+                self.offset += consumed;
+                return Ok(Some(line));
+            }
+
+            self.buffer.drain(..self.start);
+            self.start = 0;
+            match stream::read_chunk(&self.stream, self.position)? {
+                Some(chunk) => self.buffer.extend(chunk),
+                None => self.eof = true,
+            }
+        }
+    }
+}
+
+/// Backs `Codec::mapLines()`: decodes each record of `input`, passes it to
+/// `callback` with its line number and writes the result to `output`.
+/// Output is flushed whenever `elephant_json.buffer_size` bytes are pending.
+/// `pace` counts input bytes across lines. Records stay on this thread, as
+/// PHP values and the callback belong to the request that made them.
+pub(crate) fn map(
+    input: &Zval,
+    callback: &Zval,
+    output: &Zval,
+    decoder: DecoderOptions,
+    encoder: &EncoderOptions,
+    pace: Option<Pace>,
+) -> PhpResult<i64> {
+    let callback = ZendCallable::new(callback).map_err(|_| {
+        PhpException::new(
+            "Argument #2 ($callback) must be a valid callback".to_string(),
+            0,
+            ce::type_error(),
+        )
+    })?;
+    let flush_at = ini::buffer_size().max(1);
+    let pacer = pace.map(Pacer::new);
+
+    let mut lines = Lines::new(input, 1);
+    let mut pending = String::new();
+    let mut written = 0;
+    while let Some(line) = lines.next_line()? {
+        if let Some(pacer) = &pacer {
+            pacer.advance(line.offset + line.bytes.len())?;
+        }
+        if line.is_blank() {
+            continue;
+        }
+        let record = decode_bytes(&line.bytes, DecodeConfig::new(decoder.clone(), None))
+            .map_err(|err| at_line(err, line.number))?;
+
+        let number = line.number as i64;
+        let result = callback.try_call(vec![&record, &number])?;
+        if result.object().is_some_and(|obj| obj.is_instance::<Skip>()) {
+            continue;
+        }
+
+        let json = JsonEncoder::new(EncodeConfig::new(encoder, None))
+            .encode(&result)
+            .map_err(|err| at_line(err, line.number))?;
+        pending.push_str(&json);
+        pending.push('\n');
+        written += 1;
+
+        if pending.len() >= flush_at {
+            stream::write_all(output, &pending, 3)?;
+            pending.clear();
+        }
+    }
+    if !pending.is_empty() {
+        stream::write_all(output, &pending, 3)?;
+    }
+    Ok(written)
+}
+
+fn at_line(err: JsonError, number: usize) -> JsonError {
+    JsonError::new(err.code, format!("Line {}: {}", number, err))
+}
+
+/// Backs `Codec::validateLines()`: one `['line', 'offset', 'valid',
+/// 'error']` entry per non-blank line, `error` being null for valid ones.
+pub(crate) fn validate(input: &Zval, depth: i64) -> PhpResult<ZBox<ZendHashTable>> {
+    let mut lines = Lines::new(input, 1);
+    let mut report = ZendHashTable::new();
+    while let Some(line) = lines.next_line()? {
+        if line.is_blank() {
+            continue;
+        }
+        let result = std::str::from_utf8(&line.bytes)
+            .map_err(|_| JsonError::new(flags::ERROR_UTF8, JsonError::native_message(flags::ERROR_UTF8)))
+            .and_then(|json| native::validate(json, depth));
+
+        let mut entry = ZendHashTable::new();
+        entry.insert("line", line.number as i64)?;
+        entry.insert("offset", line.offset as i64)?;
+        entry.insert("valid", result.is_ok())?;
+        entry.insert("error", result.err().map(|err| err.to_string()))?;
+        report.push(entry)?;
+    }
+    Ok(report)
+}
+
+/// A line `LinesReader` passed over in `skipMalformed()` mode.
+struct Malformed {
+    line: Line,
+    error: JsonError,
+}
+
+/// Reads the records of an NDJSON stream one by one, keyed by line number:
+///
+/// ```php
+/// foreach (new LinesReader(fopen('import.ndjson', 'rb')) as $line => $record) { ... }
+/// ```
+///
+/// The stream is only read forward, so a reader can be iterated once;
+/// `checkpoint()` and `resume()` let a later process carry on instead.
+/// Blank lines are skipped. A malformed line throws unless
+/// `skipMalformed()` is on, in which case it is kept for `errors()`.
+#[php_class]
+#[php(name = "Elephant\\Json\\LinesReader")]
+#[php(implements(ce = ce::iterator, stub = "\\Iterator"))]
+pub struct LinesReader {
+    lines: Lines,
+    options: DecoderOptions,
+    skip_malformed: bool,
+    started: bool,
+    current: Option<(usize, Zval)>,
+    errors: Vec<Malformed>,
+}
+
+#[php_impl]
+impl LinesReader {
+    /// `$stream` is a stream resource or a PSR-7 stream; `$as_array` takes
+    /// a bool or an `Elephant\Json\DecoderOptions` instance.
+    #[php(defaults(as_array = None))]
+    pub fn __construct(stream: &Zval, as_array: Option<&Zval>) -> PhpResult<Self> {
+        check_stream(stream)?;
+        Ok(Self::new(Lines::new(stream, 1), DecoderOptions::from_arg(as_array, None, 2, "as_array")?))
+    }
+
+    /// Continues after the line a `checkpoint()` was taken at. `$stream`
+    /// must be positioned where the original reader started, usually at
+    /// the beginning of the file, and be seekable.
+    #[php(defaults(as_array = None))]
+    pub fn resume(stream: &Zval, checkpoint: &str, as_array: Option<&Zval>) -> PhpResult<LinesReader> {
+        check_stream(stream)?;
+        let Checkpoint::Lines { offset, line } = Checkpoint::decode(checkpoint, 2)? else {
+            return Err(Checkpoint::invalid(2));
+        };
+        let options = DecoderOptions::from_arg(as_array, None, 3, "as_array")?;
+        Ok(Self::new(Lines::resume(stream, 1, offset, line)?, options))
+    }
+
+    /// An opaque token for `resume()`, pointing after the current record.
+    pub fn checkpoint(&self) -> String {
+        Checkpoint::Lines {
+            offset: self.lines.offset,
+            line: self.lines.number,
+        }
+        .encode()
+    }
+
+    /// Skips malformed lines instead of throwing, collecting them for
+    /// `errors()`, e.g. for a dead-letter report.
+    #[php(defaults(enabled = true))]
+    pub fn skip_malformed(
+        self_: &mut ZendClassObject<LinesReader>,
+        enabled: bool,
+    ) -> &mut ZendClassObject<LinesReader> {
+        self_.skip_malformed = enabled;
+        self_
+    }
+
+    /// The lines skipped so far, as `['line', 'offset', 'code', 'error',
+    /// 'raw']` entries; `raw` is the line as read, without its line break.
+    pub fn errors(&self) -> PhpResult<ZBox<ZendHashTable>> {
+        let mut errors = ZendHashTable::new();
+        for malformed in &self.errors {
+            let mut entry = ZendHashTable::new();
+            entry.insert("line", malformed.line.number as i64)?;
+            entry.insert("offset", malformed.line.offset as i64)?;
+            entry.insert("code", malformed.error.code)?;
+            entry.insert("error", malformed.error.to_string())?;
+            entry.insert("raw", Binary::new(malformed.line.bytes.clone()))?;
+            errors.push(entry)?;
+        }
+        Ok(errors)
+    }
+
+    /// Reads the first record; later calls do nothing, as the stream
+    /// cannot be rewound.
+    pub fn rewind(&mut self) -> PhpResult<()> {
+        if self.started {
+            return Ok(());
+        }
+        self.started = true;
+        self.advance()
+    }
+
+    pub fn valid(&self) -> bool {
+        self.current.is_some()
+    }
+
+    pub fn current(&self) -> Zval {
+        match &self.current {
+            Some((_, record)) => record.shallow_clone(),
+            None => Zval::new(),
+        }
+    }
+
+    pub fn key(&self) -> Option<i64> {
+        self.current.as_ref().map(|(line, _)| *line as i64)
+    }
+
+    pub fn next(&mut self) -> PhpResult<()> {
+        self.started = true;
+        self.advance()
+    }
+}
+
+impl LinesReader {
+    fn new(lines: Lines, options: DecoderOptions) -> Self {
+        Self {
+            lines,
+            options,
+            skip_malformed: false,
+            started: false,
+            current: None,
+            errors: Vec::new(),
+        }
+    }
+
+    fn advance(&mut self) -> PhpResult<()> {
+        self.current = None;
+        while let Some(line) = self.lines.next_line()? {
+            if line.is_blank() {
+                continue;
+            }
+            match decode_bytes(&line.bytes, DecodeConfig::new(self.options.clone(), None)) {
+                Ok(record) => {
+                    self.current = Some((line.number, record));
+                    return Ok(());
+                }
+                Err(error) if self.skip_malformed => self.errors.push(Malformed { line, error }),
+                Err(error) => return Err(at_line(error, line.number).into()),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_stream(stream: &Zval) -> PhpResult<()> {
+    if stream.is_resource() || stream.object().is_some() {
+        return Ok(());
+    }
+    Err(PhpException::new(
+        format!(
+            "Argument #1 ($stream) must be a stream resource or a Psr\\Http\\Message\\StreamInterface, {} given",
+            stream.get_type()
+        ),
+        0,
+        ce::type_error(),
+    ))
+}
+
+/// Appends records to an NDJSON file, e.g. an event log:
+///
+/// ```php
+/// $log = LinesWriter::append('/var/lib/app/events.ndjson', schema: $eventSchema, index: true);
+/// $log->writeBatch($events);
+/// ```
+///
+/// Each `write()` or `writeBatch()` call is one batch: every record is
+/// encoded and checked against the schema before anything is written, the
+/// batch goes out in a single write, and the file is synced to disk.
+#[php_class]
+#[php(name = "Elephant\\Json\\LinesWriter")]
+pub struct LinesWriter {
+    path: String,
+    file: File,
+    /// The side index: one little-endian `u64` offset and `u64` length
+    /// pair per record.
+    index: Option<File>,
+    options: EncoderOptions,
+    schema: Option<Schema>,
+    sync: bool,
+}
+
+#[php_impl]
+impl LinesWriter {
+    /// Opens `$path` for appending, creating it if needed. `$options`
+    /// takes `JSON_*` flags or an `Elephant\Json\EncoderOptions` instance;
+    /// pretty printing is turned off. With `$index`, the offset and length
+    /// of each record are also appended to `$path . '.idx'`. `$sync: false`
+    /// leaves flushing to the operating system.
+    ///
+    /// Only local files can be synced, so stream wrappers are rejected, as
+    /// is everything while `open_basedir` is in effect.
+    #[php(defaults(options = None, schema = None, index = false, sync = true))]
+    pub fn append(
+        path: String,
+        options: Option<&Zval>,
+        schema: Option<&Schema>,
+        index: bool,
+        sync: bool,
+    ) -> PhpResult<LinesWriter> {
+        let mut options = EncoderOptions::resolve(options, 2)?;
+        options.flags &= !flags::PRETTY_PRINT;
+        options.indent = None;
+        options.trailing_newline = false;
+
+        let Some(local) = file::local_path(&path) else {
+            return Err(PhpException::new(
+                format!("Argument #1 ($path) must be a local file path, {} given", path),
+                0,
+                ce::value_error(),
+            ));
+        };
+        let open = |path: &str| OpenOptions::new().append(true).create(true).open(path);
+        let data = open(local).map_err(|e| file::io_error(&path, e))?;
+        let index = match index {
+            true => {
+                let index_path = format!("{}.idx", local);
+                Some(open(&index_path).map_err(|e| file::io_error(&index_path, e))?)
+            }
+            false => None,
+        };
+        Ok(Self {
+            path,
+            file: data,
+            index,
+            options,
+            schema: schema.cloned(),
+            sync,
+        })
+    }
+
+    /// Appends one record and returns the byte offset it starts at.
+    pub fn write(&mut self, record: &Zval) -> PhpResult<i64> {
+        let (_, start) = self.append_batch([record].into_iter())?;
+        Ok(start as i64)
+    }
+
+    /// Appends every record of `$records` as one batch and returns how
+    /// many were written. A record that fails to encode or validate fails
+    /// the whole batch before anything is written.
+    pub fn write_batch(&mut self, records: &ZendHashTable) -> PhpResult<i64> {
+        Ok(self.append_batch(records.values())?.0)
+    }
+
+    /// Current size of the file, including what other processes appended.
+    pub fn size(&self) -> PhpResult<i64> {
+        Ok(self.len()? as i64)
+    }
+}
+
+impl LinesWriter {
+    /// Writes the records and returns how many there were and the offset
+    /// the first one landed at.
+    fn append_batch<'z>(&mut self, records: impl Iterator<Item = &'z Zval>) -> PhpResult<(i64, u64)> {
+        let encoder = JsonEncoder::new(EncodeConfig::new(&self.options, None));
+        let mut data = String::new();
+        let mut entries = Vec::new();
+        let mut count = 0;
+        for (i, record) in records.enumerate() {
+            let at_record = |err: JsonError| JsonError::new(err.code, format!("Record {}: {}", i, err));
+            let json = encoder.encode(record).map_err(at_record)?;
+            if let Some(schema) = &self.schema {
+                schema.enforce(&json).map_err(at_record)?;
+            }
+            entries.push((data.len() as u64, json.len() as u64));
+            data.push_str(&json);
+            data.push('\n');
+            count += 1;
+        }
+        if count == 0 {
+            return Ok((0, self.len()?));
+        }
+
+        let io_error = |e| file::io_error(&self.path, e);
+        self.file.write_all(data.as_bytes()).map_err(io_error)?;
+        if self.sync {
+            self.file.sync_data().map_err(io_error)?;
+        }
+        // O_APPEND puts the batch at whatever the end is when it is written,
+        // which moves when another process appends to the same file, so
+        // the offsets come from the file rather than from earlier batches.
+        // They are exact unless another append lands in between.
+        let start = self.len()?.saturating_sub(data.len() as u64);
+
+        // The index is written after the records it points to, so after a
+        // crash it never refers past the end of the file.
+        if let Some(index) = &mut self.index {
+            let mut bytes = Vec::with_capacity(entries.len() * 16);
+            for (offset, len) in entries {
+                bytes.extend_from_slice(&(start + offset).to_le_bytes());
+                bytes.extend_from_slice(&len.to_le_bytes());
+            }
+            index.write_all(&bytes).map_err(io_error)?;
+            if self.sync {
+                index.sync_data().map_err(io_error)?;
+            }
+        }
+        Ok((count, start))
+    }
+
+    fn len(&self) -> PhpResult<u64> {
+        let metadata = self.file.metadata().map_err(|e| file::io_error(&self.path, e))?;
+        Ok(metadata.len())
+    }
+}
@@ -0,0 +1,221 @@
+//! Lookups in raw JSON, for reading one field or a few elements of a large
+//! payload without decoding the rest.
+//!
+//! The document is scanned with the [`Reader`] only as far as the value,
+//! skipping the subtrees in between; nothing after the value is read, so
+//! a malformed tail goes unnoticed.
+
+use std::collections::VecDeque;
+
+use ext_php_rs::prelude::*;
+use ext_php_rs::zend::ce;
+
+use crate::error::JsonError;
+use crate::escape::CharPolicy;
+use crate::options::{DecoderOptions, DuplicateKeys};
+use crate::reader::{string_content, Event, ReadError, Reader};
+use crate::value::{location, Value};
+use crate::pointer::{self, PointerArg};
+use crate::{flags, DecodeConfig, JsonDecoder};
+
+/// Byte span of the value at `json_pointer`, or `None` if there is none.
+pub(crate) fn find(
+    json: &str,
+    json_pointer: &PointerArg,
+    options: &DecoderOptions,
+) -> Result<Option<(usize, usize)>, JsonError> {
+    let Some(start) = locate(json, json_pointer, options)? else {
+        return Ok(None);
+    };
+    let (from, to) = reader(&json[start..], options)
+        .skip_value()
+        .map_err(|err| shifted(err, start))?;
+    Ok(Some((start + from, start + to)))
+}
+
+/// Where the value at `json_pointer` starts, possibly after whitespace,
+/// without reading the value itself. Repeated member names are resolved
+/// the way `options` would resolve them when decoding.
+fn locate(json: &str, json_pointer: &PointerArg, options: &DecoderOptions) -> Result<Option<usize>, JsonError> {
+    let tokens = json_pointer
+        .tokens()
+        .map_err(|message| JsonError::new(flags::ERROR_SYNTAX, message))?;
+    let mut start = 0;
+    for token in tokens.iter() {
+        match child(json, start, token, options)? {
+            Some(offset) => start = offset,
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(start))
+}
+
+/// Decodes the value at `json_pointer` alone, wrapped for its typed
+/// accessors.
+pub(crate) fn get(json: &[u8], json_pointer: &PointerArg, options: Option<&DecoderOptions>) -> PhpResult<Value> {
+    let options = options
+        .cloned()
+        .unwrap_or_else(|| DecoderOptions::resolve(None, None).0);
+    let json = std::str::from_utf8(json)
+        .map_err(|_| JsonError::new(flags::ERROR_UTF8, JsonError::native_message(flags::ERROR_UTF8)))?;
+    let (start, end) = find(json, json_pointer, &options)?.ok_or_else(|| Value::missing(json_pointer.as_str()))?;
+    let value = JsonDecoder::new(DecodeConfig::new(options, None)).decode(&json[start..end])?;
+    Ok(Value::new(value, json_pointer.as_str().to_string()))
+}
+
+/// Backs `Json::head()` and `Json::tail()`: the first or last `n`
+/// elements of the array at `json_pointer`, as a JSON array of their raw
+/// text. Taking the head stops reading after the `n`th element.
+pub(crate) fn sample(
+    json: &[u8],
+    json_pointer: &PointerArg,
+    n: i64,
+    from_end: bool,
+    options: Option<&DecoderOptions>,
+) -> PhpResult<String> {
+    let n = usize::try_from(n).map_err(|_| {
+        PhpException::new(
+            "Argument #3 ($n) must be greater than or equal to 0".to_string(),
+            0,
+            ce::value_error(),
+        )
+    })?;
+    let options = options
+        .cloned()
+        .unwrap_or_else(|| DecoderOptions::resolve(None, None).0);
+    let json = std::str::from_utf8(json)
+        .map_err(|_| JsonError::new(flags::ERROR_UTF8, JsonError::native_message(flags::ERROR_UTF8)))?;
+    let start = locate(json, json_pointer, &options)?.ok_or_else(|| Value::missing(json_pointer.as_str()))?;
+
+    let mut reader = reader(&json[start..], &options);
+    let event = next(&mut reader, start)?;
+    if event != Event::BeginArray {
+        return Err(PhpException::new(
+            format!("Expected array at {}, got {}", location(json_pointer.as_str()), type_of(event)),
+            0,
+            ce::type_error(),
+        ));
+    }
+    let mut elements: VecDeque<&str> = VecDeque::new();
+    while from_end || elements.len() < n {
+        let event = next(&mut reader, start)?;
+        if event == Event::EndArray {
+            break;
+        }
+        let from = reader.token_start();
+        skip_children(&mut reader, event, start)?;
+        elements.push_back(&json[start + from..start + reader.position()]);
+        if elements.len() > n {
+            elements.pop_front();
+        }
+    }
+
+    let mut out = String::with_capacity(elements.iter().map(|raw| raw.len() + 1).sum::<usize>() + 1);
+    out.push('[');
+    for (i, raw) in elements.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str(raw);
+    }
+    out.push(']');
+    Ok(out)
+}
+
+/// The `Value::type()` name of the value an event starts.
+fn type_of(event: Event) -> &'static str {
+    match event {
+        Event::BeginArray => "array",
+        Event::BeginObject => "object",
+        Event::String(_) => "string",
+        Event::Number(raw) if raw.contains(['.', 'e', 'E']) => "float",
+        Event::Number(_) => "int",
+        Event::Bool(_) => "bool",
+        _ => "null",
+    }
+}
+
+fn reader<'a>(json: &'a str, options: &DecoderOptions) -> Reader<'a> {
+    Reader::new(json).with_control_chars(options.control_chars != CharPolicy::Reject)
+}
+
+/// Offset of the child named by `token` of the value at `start`.
+fn child(json: &str, start: usize, token: &str, options: &DecoderOptions) -> Result<Option<usize>, JsonError> {
+    let mut reader = reader(&json[start..], options);
+    match next(&mut reader, start)? {
+        Event::BeginObject => {
+            let mut found = None;
+            loop {
+                let name = match next(&mut reader, start)? {
+                    Event::Key(raw) => raw,
+                    _ => return Ok(found),
+                };
+                let matched = match name[1..name.len() - 1].contains('\\') {
+                    true => {
+                        let unescaped = string_content(name)
+                            .map_err(|message| shifted(ReadError::new(message, reader.token_start()), start))?;
+                        unescaped == token
+                    }
+                    false => &name[1..name.len() - 1] == token,
+                };
+                let (value, _) = reader.skip_value().map_err(|err| shifted(err, start))?;
+                match (matched, found, options.duplicate_keys) {
+                    (false, _, _) => {}
+                    (true, _, DuplicateKeys::First) => return Ok(Some(start + value)),
+                    (true, Some(_), DuplicateKeys::Error) => {
+                        return Err(JsonError::new(
+                            flags::ERROR_DUPLICATE_KEY,
+                            format!("Duplicate key \"{}\"", token.escape_default()),
+                        ));
+                    }
+                    (true, _, _) => found = Some(start + value),
+                }
+            }
+        }
+        Event::BeginArray => {
+            let Some(index) = pointer::array_index(token) else {
+                return Ok(None);
+            };
+            for i in 0.. {
+                let event = next(&mut reader, start)?;
+                if event == Event::EndArray {
+                    break;
+                }
+                if i == index {
+                    return Ok(Some(start + reader.token_start()));
+                }
+                skip_children(&mut reader, event, start)?;
+            }
+            Ok(None)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Reads to the end of the value `event` started, if it is a container.
+fn skip_children(reader: &mut Reader, event: Event, base: usize) -> Result<(), JsonError> {
+    if matches!(event, Event::BeginArray | Event::BeginObject) {
+        let depth = reader.depth();
+        while reader.depth() >= depth {
+            next(reader, base)?;
+        }
+    }
+    Ok(())
+}
+
+/// The next event of a reader over the input from `base` on.
+fn next<'a>(reader: &mut Reader<'a>, base: usize) -> Result<Event<'a>, JsonError> {
+    reader
+        .next_event()
+        .map_err(|err| shifted(err, base))?
+        .map(|(_, event)| event)
+        .ok_or_else(|| JsonError::syntax("Unexpected end of input"))
+}
+
+/// Reports a reader error at its offset in the whole input.
+fn shifted(err: ReadError, base: usize) -> JsonError {
+    JsonError::read(ReadError {
+        offset: err.offset + base,
+        ..err
+    })
+}
@@ -0,0 +1,64 @@
+//! `array_merge_recursive()` over decoded documents, for configuration
+//! code moving from PHP arrays to JSON without changing results.
+
+use ext_php_rs::boxed::ZBox;
+use ext_php_rs::types::{ArrayKey, ZendHashTable};
+
+use crate::error::JsonError;
+
+/// Merges `arrays` left to right as `array_merge_recursive()` does: lists
+/// are appended, and a name present on both sides ends up holding an array
+/// of both values, merged in turn when they are arrays themselves.
+pub(crate) fn merge_recursive<'a>(
+    arrays: impl IntoIterator<Item = &'a ZendHashTable>,
+) -> Result<ZBox<ZendHashTable>, JsonError> {
+    let mut arrays = arrays.into_iter();
+    let mut merged = ZendHashTable::new();
+    // The first array is copied with its integer keys renumbered, as
+    // `array_merge()` would.
+    if let Some(first) = arrays.next() {
+        for (key, value) in first {
+            match key {
+                ArrayKey::Long(_) => merged.push(value.shallow_clone()),
+                key => merged.insert(key, value.shallow_clone()),
+            }
+            .map_err(JsonError::engine)?;
+        }
+    }
+    for array in arrays {
+        merge_into(&mut merged, array)?;
+    }
+    Ok(merged)
+}
+
+fn merge_into(dest: &mut ZendHashTable, src: &ZendHashTable) -> Result<(), JsonError> {
+    for (key, value) in src {
+        let name = match key {
+            ArrayKey::Long(_) => {
+                dest.push(value.shallow_clone()).map_err(JsonError::engine)?;
+                continue;
+            }
+            key => key.to_string(),
+        };
+        let Some(existing) = dest.get(name.as_str()) else {
+            dest.insert(name.as_str(), value.shallow_clone()).map_err(JsonError::engine)?;
+            continue;
+        };
+
+        // A scalar, `null` included, becomes the first element of a list.
+        let mut combined = match existing.array() {
+            Some(array) => array.to_owned(),
+            None => {
+                let mut list = ZendHashTable::new();
+                list.push(existing.shallow_clone()).map_err(JsonError::engine)?;
+                list
+            }
+        };
+        match value.array() {
+            Some(array) => merge_into(&mut combined, array)?,
+            None => combined.push(value.shallow_clone()).map_err(JsonError::engine)?,
+        }
+        dest.insert(name.as_str(), combined).map_err(JsonError::engine)?;
+    }
+    Ok(())
+}
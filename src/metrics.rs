@@ -0,0 +1,51 @@
+//! Per-call measurements reported to the hook set with
+//! `Codec::onMetrics()`, so time spent on JSON can be attributed without
+//! wrapping every call site. The hook lasts for the current request only.
+
+use std::time::Instant;
+
+use ext_php_rs::types::ZendHashTable;
+
+use crate::error::JsonError;
+use crate::globals;
+use crate::options::Callback;
+
+pub(crate) fn set_hook(hook: Option<Callback>) {
+    globals::replace(|g| &mut g.metrics_hook, hook);
+}
+
+/// Runs `f`, the body of entry point `operation`, and reports how long it
+/// took, the size of the JSON text it read or wrote and its error code.
+/// Without a hook this costs one look at the globals.
+pub(crate) fn measure<T>(
+    operation: &str,
+    f: impl FnOnce() -> Result<T, JsonError>,
+    bytes: impl FnOnce(&T) -> usize,
+) -> Result<T, JsonError> {
+    // Calls the hook itself makes are not reported, or it would recurse.
+    let Some(hook) = globals::with(|g| g.metrics_hook.clone().filter(|_| !g.in_metrics_hook)) else {
+        return f();
+    };
+    let started = Instant::now();
+    let result = f();
+    let seconds = started.elapsed().as_secs_f64();
+
+    let mut metrics = ZendHashTable::new();
+    let (bytes, error) = match &result {
+        Ok(value) => (bytes(value), 0),
+        Err(err) => (0, err.code),
+    };
+    let reported = metrics
+        .insert("operation", operation)
+        .and_then(|_| metrics.insert("seconds", seconds))
+        .and_then(|_| metrics.insert("bytes", bytes as i64))
+        .and_then(|_| metrics.insert("error", error));
+    if reported.is_ok() {
+        globals::with(|g| g.in_metrics_hook = true);
+        // An exception thrown by the hook stays pending and surfaces once
+        // the entry point returns.
+        let _ = hook.call(vec![&metrics]);
+        globals::with(|g| g.in_metrics_hook = false);
+    }
+    result
+}
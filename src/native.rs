@@ -0,0 +1,174 @@
+//! ext/json-compatible global functions.
+//!
+//! These are only registered under their canonical names when
+//! `elephant_json.replace_native` is enabled in php.ini and ext/json is not
+//! loaded, so existing applications can switch without code changes.
+
+use std::ffi::{c_char, c_int, CString};
+
+use ext_php_rs::binary::Binary;
+use ext_php_rs::class::RegisteredClass;
+use ext_php_rs::convert::IntoZval;
+use ext_php_rs::ffi::zend_long;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ZendHashTable, Zval};
+use ext_php_rs::zend::ce;
+
+use crate::charset::{self, BomPolicy, InvalidUtf8};
+use crate::error::JsonError;
+use crate::options::{DecoderOptions, EncoderOptions};
+use crate::reader::Reader;
+use crate::{decode_bytes, flags, ini, metrics, state, DecodeConfig, EncodeConfig, JsonEncoder};
+
+unsafe extern "C" {
+    fn cfg_get_long(varname: *const c_char, result: *mut zend_long) -> c_int;
+    static module_registry: ZendHashTable;
+}
+
+#[php_class]
+#[php(name = "JsonException")]
+#[php(extends(ce = ce::exception, stub = "\\Exception"))]
+#[derive(Default)]
+pub struct JsonException;
+
+pub(crate) fn register(module: ModuleBuilder) -> ModuleBuilder {
+    if !replacement_enabled() {
+        return module;
+    }
+
+    module
+        .class::<JsonException>()
+        .function(wrap_function!(json_encode))
+        .function(wrap_function!(json_decode))
+        .function(wrap_function!(json_validate))
+        .function(wrap_function!(json_last_error))
+        .function(wrap_function!(json_last_error_msg))
+}
+
+/// Evaluated while the module is loaded, before our own INI entries exist, so
+/// the raw php.ini value is read directly.
+fn replacement_enabled() -> bool {
+    let Ok(name) = CString::new(ini::REPLACE_NATIVE) else {
+        return false;
+    };
+
+    let mut value: zend_long = 0;
+    let configured = unsafe { cfg_get_long(name.as_ptr(), &mut value) } == 0;
+    let native_loaded = unsafe { (*std::ptr::addr_of!(module_registry)).get("json").is_some() };
+
+    configured && value != 0 && !native_loaded
+}
+
+#[php_function]
+#[php(defaults(flags = None, depth = None))]
+pub fn json_encode(value: &Zval, flags: Option<&Zval>, depth: Option<i64>) -> PhpResult<Zval> {
+    let options = EncoderOptions::resolve(flags, 2)?;
+    let depth = depth.map(|depth| positive_depth(Some(depth), 3)).transpose()?;
+    let encoder = JsonEncoder::new(EncodeConfig::new(&options, depth));
+    let result = metrics::measure("json_encode", || encoder.encode(value), String::len);
+
+    // ext/json reports what partial output papered over, even when it
+    // would otherwise throw.
+    if let (Ok(json), Some(err)) = (&result, encoder.take_partial_error()) {
+        state::set_last_error(Some(err));
+        return Ok(json.as_str().into_zval(false)?);
+    }
+    complete(result, options.flags, false)
+}
+
+#[php_function]
+#[php(defaults(associative = None, depth = None, flags = None))]
+pub fn json_decode(
+    json: Binary<u8>,
+    associative: Option<bool>,
+    depth: Option<i64>,
+    flags: Option<i64>,
+) -> PhpResult<Zval> {
+    let depth = depth.map(|depth| positive_depth(Some(depth), 3)).transpose()?;
+    let (mut options, flags) = DecoderOptions::resolve(associative, flags);
+    // ext/json has no notion of a byte order mark.
+    options.bom = BomPolicy::Reject;
+    let config = DecodeConfig::new(options, depth);
+
+    let result = metrics::measure("json_decode", || decode_bytes(&json, config), |_| json.len());
+    complete(result, flags, ())
+}
+
+#[php_function]
+#[php(defaults(depth = None, flags = None))]
+pub fn json_validate(json: Binary<u8>, depth: Option<i64>, flags: Option<i64>) -> PhpResult<bool> {
+    let flags = flags.unwrap_or(0);
+    if flags != 0 && flags != flags::INVALID_UTF8_IGNORE {
+        return Err(PhpException::new(
+            "Argument #3 ($flags) must be a valid flag (allowed flags: JSON_INVALID_UTF8_IGNORE)".to_string(),
+            0,
+            ce::value_error(),
+        ));
+    }
+    let depth = positive_depth(depth, 2)?;
+    let result = charset::to_utf8(&json, InvalidUtf8::from_flags(flags)).and_then(|json| validate(&json, depth));
+    let valid = result.is_ok();
+
+    state::set_last_error(result.err());
+    Ok(valid)
+}
+
+#[php_function]
+pub fn json_last_error() -> i64 {
+    state::last_error_code()
+}
+
+#[php_function]
+pub fn json_last_error_msg() -> &'static str {
+    JsonError::native_message(state::last_error_code())
+}
+
+pub(crate) fn validate(json: &str, depth: i64) -> Result<(), JsonError> {
+    let mut reader = Reader::new(json);
+    while reader.next_event().map_err(JsonError::read)?.is_some() {
+        if reader.depth() as i64 > depth {
+            return Err(JsonError::depth());
+        }
+    }
+    Ok(())
+}
+
+fn positive_depth(depth: Option<i64>, position: u8) -> PhpResult<i64> {
+    let depth = depth.unwrap_or_else(ini::default_depth);
+    if depth <= 0 {
+        return Err(PhpException::new(
+            format!("Argument #{} ($depth) must be greater than 0", position),
+            0,
+            ce::value_error(),
+        ));
+    }
+    Ok(depth)
+}
+
+/// Applies ext/json's error protocol: throw `JsonException` under
+/// `JSON_THROW_ON_ERROR`, otherwise record the error and return `on_error`.
+fn complete<T: IntoZval>(
+    result: Result<T, JsonError>,
+    flags: i64,
+    on_error: impl IntoZval,
+) -> PhpResult<Zval> {
+    let throw = flags & flags::THROW_ON_ERROR != 0;
+
+    match result {
+        Ok(value) => {
+            if !throw {
+                state::set_last_error(None);
+            }
+            Ok(value.into_zval(false)?)
+        }
+        Err(err) if throw => Err(PhpException::new(
+            err.to_string(),
+            err.code as i32,
+            JsonException::get_metadata().ce(),
+        )),
+        Err(err) => {
+            state::set_last_error(Some(err));
+            Ok(on_error.into_zval(false)?)
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! Float formatting beyond the shortest round-trip default.
+//!
+//! Like all number handling here it relies on Rust's formatting, which never
+//! consults the C locale, so `setlocale()` cannot introduce `,` separators.
+
+use crate::error::JsonError;
+use crate::flags;
+
+/// When to switch from fixed-point to exponent notation.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub(crate) enum Notation {
+    /// The shortest round-trip form, as ext/json writes it.
+    #[default]
+    Default,
+    /// Never use an exponent.
+    Fixed,
+    /// Use an exponent once the decimal exponent reaches `n` or `-n`.
+    Threshold(i32),
+    /// JavaScript's cut-offs, as `Default` has them, even without a
+    /// precision; see `EncoderOptions::stable()`.
+    JavaScript,
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub(crate) struct FloatFormat {
+    /// Maximum significant digits, 1 to 17; `None` keeps as many as needed
+    /// to round-trip.
+    pub significant_digits: Option<usize>,
+    pub notation: Notation,
+}
+
+/// JavaScript's cut-offs: `1e21` and `1e-7` are the first values its
+/// `Number#toString()` writes with an exponent.
+const DEFAULT_THRESHOLD: (i32, i32) = (21, -7);
+
+/// Formats a finite float as a JSON number.
+pub(crate) fn format_float(f: f64, format: &FloatFormat) -> String {
+    let scientific = match format.significant_digits {
+        Some(digits) => format!("{:.*e}", digits.saturating_sub(1), f),
+        None => format!("{:e}", f),
+    };
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let negative = mantissa.starts_with('-');
+
+    let mut digits: String = mantissa.chars().filter(char::is_ascii_digit).collect();
+    while digits.len() > 1 && digits.ends_with('0') {
+        digits.pop();
+    }
+
+    let use_exponent = match format.notation {
+        Notation::Fixed => false,
+        Notation::Threshold(n) => exponent >= n || exponent <= -n,
+        Notation::Default | Notation::JavaScript => {
+            exponent >= DEFAULT_THRESHOLD.0 || exponent <= DEFAULT_THRESHOLD.1
+        }
+    };
+
+    let mut out = String::with_capacity(digits.len() + 8);
+    if negative {
+        out.push('-');
+    }
+    if use_exponent {
+        out.push_str(&digits[..1]);
+        if digits.len() > 1 {
+            out.push('.');
+            out.push_str(&digits[1..]);
+        }
+        out.push('e');
+        out.push_str(&exponent.to_string());
+    } else if exponent < 0 {
+        out.push_str("0.");
+        out.extend(std::iter::repeat_n('0', (-exponent - 1) as usize));
+        out.push_str(&digits);
+    } else {
+        let int_len = exponent as usize + 1;
+        if digits.len() > int_len {
+            out.push_str(&digits[..int_len]);
+            out.push('.');
+            out.push_str(&digits[int_len..]);
+        } else {
+            out.push_str(&digits);
+            out.extend(std::iter::repeat_n('0', int_len - digits.len()));
+            // Keep the value a float on decode, as the default format does.
+            out.push_str(".0");
+        }
+    }
+    out
+}
+
+/// Formats a finite float as ext/json does, following `php_gcvt()`:
+/// `serialize_precision` significant digits, or the shortest round-trip
+/// digits for `-1`, with an exponent below `1e-4` and once the integer part
+/// has more digits than that precision (17 for `-1`).
+pub(crate) fn format_native(f: f64, serialize_precision: i64, zero_fraction: bool) -> String {
+    let (scientific, precision) = match usize::try_from(serialize_precision) {
+        Ok(digits) => {
+            let digits = digits.max(1);
+            (format!("{:.*e}", digits - 1, f), digits as i32)
+        }
+        Err(_) => (format!("{:e}", f), 17),
+    };
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    // Position of the decimal point relative to the digits, as dtoa reports it.
+    let decpt = exponent.parse::<i32>().unwrap_or(0) + 1;
+    let negative = mantissa.starts_with('-');
+
+    let mut digits: String = mantissa.chars().filter(char::is_ascii_digit).collect();
+    while digits.len() > 1 && digits.ends_with('0') {
+        digits.pop();
+    }
+
+    let mut out = String::with_capacity(digits.len() + 8);
+    if negative {
+        out.push('-');
+    }
+    if decpt < -3 || decpt > precision {
+        let exponent = decpt - 1;
+        out.push_str(&digits[..1]);
+        out.push('.');
+        out.push_str(if digits.len() > 1 { &digits[1..] } else { "0" });
+        out.push('e');
+        out.push(if exponent < 0 { '-' } else { '+' });
+        out.push_str(&exponent.abs().to_string());
+    } else if decpt <= 0 {
+        out.push_str("0.");
+        out.extend(std::iter::repeat_n('0', -decpt as usize));
+        out.push_str(&digits);
+    } else {
+        let int_len = decpt as usize;
+        if digits.len() > int_len {
+            out.push_str(&digits[..int_len]);
+            out.push('.');
+            out.push_str(&digits[int_len..]);
+        } else {
+            out.push_str(&digits);
+            out.extend(std::iter::repeat_n('0', int_len - digits.len()));
+        }
+    }
+    // `JSON_PRESERVE_ZERO_FRACTION`; exponent forms always have a `.`.
+    if zero_fraction && !out.contains('.') {
+        out.push_str(".0");
+    }
+    out
+}
+
+/// A string that PHP's `is_numeric()` accepts, as `JSON_NUMERIC_CHECK`
+/// writes it.
+pub(crate) enum Numeric {
+    Int(i64),
+    Float(f64),
+}
+
+/// Parses a numeric string the way `is_numeric_string()` does: optional
+/// surrounding whitespace, a sign, decimal digits with an optional fraction
+/// and exponent. Integers too large for an `int` become floats.
+pub(crate) fn parse_numeric(s: &str) -> Option<Numeric> {
+    let is_space = |c: char| matches!(c, ' ' | '\t' | '\n' | '\r' | '\u{0b}' | '\u{0c}');
+    let s = s.trim_matches(is_space);
+    let unsigned = s.strip_prefix(['+', '-']).unwrap_or(s);
+
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(i) => (&unsigned[..i], Some(&unsigned[i + 1..])),
+        None => (unsigned, None),
+    };
+    let (int, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let all_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if int.len() + fraction.len() == 0 || !all_digits(int) || !all_digits(fraction) {
+        return None;
+    }
+    if let Some(exponent) = exponent {
+        let digits = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        if digits.is_empty() || !all_digits(digits) {
+            return None;
+        }
+    }
+
+    let integral = exponent.is_none() && !mantissa.contains('.');
+    match s.parse::<i64>() {
+        Ok(i) if integral => Some(Numeric::Int(i)),
+        _ => s.parse().ok().map(Numeric::Float),
+    }
+}
+
+/// The lexeme of a float read from a binary format, with a fraction added
+/// where needed so that it stays a float once decoded. `None` stands for
+/// an infinity or NaN, which fails naming the `format` and byte `pos`.
+pub(crate) fn float_lexeme(lexeme: Option<String>, format: &str, pos: usize) -> Result<String, JsonError> {
+    let Some(mut lexeme) = lexeme else {
+        return Err(JsonError::new(
+            flags::ERROR_INF_OR_NAN,
+            format!("Inf and NaN cannot be JSON encoded ({} byte {})", format, pos),
+        ));
+    };
+    if !lexeme.contains(['.', 'e', 'E']) {
+        lexeme.push_str(".0");
+    }
+    Ok(lexeme)
+}
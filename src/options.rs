@@ -0,0 +1,1138 @@
+//! Object-based alternatives to the `JSON_*` bitflags.
+
+use ext_php_rs::convert::IntoZvalDyn;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ZendCallable, ZendClassObject, Zval};
+use ext_php_rs::zend::ce;
+
+use crate::charset::{BomPolicy, InvalidUtf8};
+use crate::error::JsonError;
+use crate::escape::{CharPolicy, HexCase};
+use crate::number::{FloatFormat, Notation};
+use crate::writer::Style;
+use crate::{flags, ini, state, BINARY_ENVELOPE, DEFAULT_VALUE_METHODS};
+
+/// Encoder settings, accepted wherever encode flags are.
+///
+/// ```php
+/// Json::encode($data, EncoderOptions::new()->indent(4)->sortKeys());
+/// ```
+#[php_class]
+#[php(name = "Elephant\\Json\\EncoderOptions")]
+#[derive(Clone, Default)]
+pub struct EncoderOptions {
+    pub(crate) flags: i64,
+    /// Indentation width; implies pretty printing.
+    pub(crate) indent: Option<usize>,
+    /// Pretty-printing layout; implies pretty printing.
+    pub(crate) style: Option<Style>,
+    pub(crate) sort_keys: bool,
+    pub(crate) skip_nulls: bool,
+    pub(crate) max_depth: Option<i64>,
+    pub(crate) non_finite: NonFinite,
+    pub(crate) empty_arrays: EmptyArrays,
+    pub(crate) float_format: FloatFormat,
+    pub(crate) trailing_newline: bool,
+    /// See `binaryBase64()`; `BINARY_AS_BASE64` means the default name.
+    pub(crate) binary_envelope: Option<String>,
+    pub(crate) hex_case: HexCase,
+    /// Escape only what RFC 8259 requires, whatever the flags say.
+    pub(crate) minimal_escapes: bool,
+    pub(crate) debug_info: bool,
+    /// See `valueMethods()`; tried in order.
+    pub(crate) value_methods: Vec<String>,
+    pub(crate) pace: Option<Pace>,
+}
+
+#[php_impl]
+impl EncoderOptions {
+    pub fn __construct() -> Self {
+        Self::default()
+    }
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A preset for files kept under version control: sorted keys,
+    /// two-space indentation, LF line breaks, a trailing newline and
+    /// canonical floats (shortest round-trip digits, an exponent only from
+    /// `1e21` up or `1e-7` down). Chain further calls to adjust it.
+    pub fn stable() -> Self {
+        Self {
+            indent: Some(2),
+            sort_keys: true,
+            trailing_newline: true,
+            float_format: FloatFormat {
+                significant_digits: None,
+                notation: Notation::JavaScript,
+            },
+            ..Self::default()
+        }
+    }
+
+    #[php(defaults(enabled = true))]
+    pub fn pretty(
+        self_: &mut ZendClassObject<EncoderOptions>,
+        enabled: bool,
+    ) -> &mut ZendClassObject<EncoderOptions> {
+        self_.set_flag(flags::PRETTY_PRINT, enabled);
+        self_
+    }
+
+    pub fn indent(
+        self_: &mut ZendClassObject<EncoderOptions>,
+        width: i64,
+    ) -> PhpResult<&mut ZendClassObject<EncoderOptions>> {
+        let width = usize::try_from(width)
+            .map_err(|_| value_error("Argument #1 ($width) must be greater than or equal to 0"))?;
+        self_.indent = Some(width);
+        Ok(self_)
+    }
+
+    /// Pretty-printing layout: `"expanded"` (one element per line, the
+    /// default), `"compact-arrays"` (lists of scalars on one line), `"k&r"`
+    /// (lists of objects or arrays as `[{`, `}, {`, `}]`) or
+    /// `"k&r-compact-arrays"` (both).
+    pub fn style(
+        self_: &mut ZendClassObject<EncoderOptions>,
+        style: String,
+    ) -> PhpResult<&mut ZendClassObject<EncoderOptions>> {
+        self_.style = Some(choice("style", &style, Style::NAMES)?);
+        Ok(self_)
+    }
+
+    /// Letter case of the hex digits in `\uXXXX` escapes: `"native"`
+    /// (default, lowercase except ext/json's uppercase `JSON_HEX_TAG`
+    /// escapes), `"lower"` or `"upper"`.
+    pub fn hex_case(
+        self_: &mut ZendClassObject<EncoderOptions>,
+        case: String,
+    ) -> PhpResult<&mut ZendClassObject<EncoderOptions>> {
+        self_.hex_case = choice(
+            "case",
+            &case,
+            &[
+                ("native", HexCase::Native),
+                ("lower", HexCase::Lower),
+                ("upper", HexCase::Upper),
+            ],
+        )?;
+        Ok(self_)
+    }
+
+    /// Escapes only what RFC 8259 requires: quotes, backslashes and control
+    /// characters. Overrides the `JSON_HEX_*` and `JSON_UNESCAPED_*` flags.
+    #[php(defaults(enabled = true))]
+    pub fn minimal_escapes(
+        self_: &mut ZendClassObject<EncoderOptions>,
+        enabled: bool,
+    ) -> &mut ZendClassObject<EncoderOptions> {
+        self_.minimal_escapes = enabled;
+        self_
+    }
+
+    /// Ends the output with `\n`, as text files conventionally do.
+    #[php(defaults(enabled = true))]
+    pub fn trailing_newline(
+        self_: &mut ZendClassObject<EncoderOptions>,
+        enabled: bool,
+    ) -> &mut ZendClassObject<EncoderOptions> {
+        self_.trailing_newline = enabled;
+        self_
+    }
+
+    #[php(defaults(enabled = true))]
+    pub fn sort_keys(
+        self_: &mut ZendClassObject<EncoderOptions>,
+        enabled: bool,
+    ) -> &mut ZendClassObject<EncoderOptions> {
+        self_.sort_keys = enabled;
+        self_
+    }
+
+    /// Omits null-valued members of objects and string-keyed arrays. List
+    /// elements are kept so indices do not shift.
+    #[php(defaults(enabled = true))]
+    pub fn skip_nulls(
+        self_: &mut ZendClassObject<EncoderOptions>,
+        enabled: bool,
+    ) -> &mut ZendClassObject<EncoderOptions> {
+        self_.skip_nulls = enabled;
+        self_
+    }
+
+    #[php(defaults(enabled = true))]
+    pub fn unescaped_unicode(
+        self_: &mut ZendClassObject<EncoderOptions>,
+        enabled: bool,
+    ) -> &mut ZendClassObject<EncoderOptions> {
+        self_.set_flag(flags::UNESCAPED_UNICODE, enabled);
+        self_
+    }
+
+    pub fn max_depth(
+        self_: &mut ZendClassObject<EncoderOptions>,
+        depth: i64,
+    ) -> PhpResult<&mut ZendClassObject<EncoderOptions>> {
+        if depth <= 0 {
+            return Err(value_error("Argument #1 ($depth) must be greater than 0"));
+        }
+        self_.max_depth = Some(depth);
+        Ok(self_)
+    }
+
+    /// Rounds floats to at most `digits` significant digits (1 to 17).
+    pub fn precision(
+        self_: &mut ZendClassObject<EncoderOptions>,
+        digits: i64,
+    ) -> PhpResult<&mut ZendClassObject<EncoderOptions>> {
+        if !(1..=17).contains(&digits) {
+            return Err(value_error("Argument #1 ($digits) must be between 1 and 17"));
+        }
+        self_.float_format.significant_digits = Some(digits as usize);
+        Ok(self_)
+    }
+
+    /// Writes floats without exponent notation, e.g. `1000000000000000000000.0`
+    /// rather than `1e21`.
+    #[php(defaults(enabled = true))]
+    pub fn fixed_point(
+        self_: &mut ZendClassObject<EncoderOptions>,
+        enabled: bool,
+    ) -> &mut ZendClassObject<EncoderOptions> {
+        self_.float_format.notation = if enabled { Notation::Fixed } else { Notation::Default };
+        self_
+    }
+
+    /// Uses exponent notation only for floats whose decimal exponent is at
+    /// least `exponent` or at most `-exponent`.
+    pub fn exponent_threshold(
+        self_: &mut ZendClassObject<EncoderOptions>,
+        exponent: i64,
+    ) -> PhpResult<&mut ZendClassObject<EncoderOptions>> {
+        if !(1..=400).contains(&exponent) {
+            return Err(value_error("Argument #1 ($exponent) must be between 1 and 400"));
+        }
+        self_.float_format.notation = Notation::Threshold(exponent as i32);
+        Ok(self_)
+    }
+
+    /// How to encode `NAN` and `INF`: `"error"` (default,
+    /// `JSON_ERROR_INF_OR_NAN` as in ext/json), `"null"`, or `"string"` for
+    /// JavaScript's `"NaN"`, `"Infinity"` and `"-Infinity"`.
+    pub fn non_finite(
+        self_: &mut ZendClassObject<EncoderOptions>,
+        policy: String,
+    ) -> PhpResult<&mut ZendClassObject<EncoderOptions>> {
+        self_.non_finite = choice(
+            "policy",
+            &policy,
+            &[
+                ("error", NonFinite::Error),
+                ("null", NonFinite::Null),
+                ("string", NonFinite::String),
+            ],
+        )?;
+        Ok(self_)
+    }
+
+    /// How to encode empty arrays: `"list"` (default, `[]` as in ext/json)
+    /// or `"object"` (`{}`). `AsList` and `AsObject` override it per value.
+    pub fn empty_arrays(
+        self_: &mut ZendClassObject<EncoderOptions>,
+        shape: String,
+    ) -> PhpResult<&mut ZendClassObject<EncoderOptions>> {
+        self_.empty_arrays = choice(
+            "shape",
+            &shape,
+            &[("list", EmptyArrays::List), ("object", EmptyArrays::Object)],
+        )?;
+        Ok(self_)
+    }
+
+    /// Output byte-identical to ext/json's `json_encode()` with the same
+    /// flags; see `Json::COMPAT`.
+    #[php(defaults(enabled = true))]
+    pub fn compat(
+        self_: &mut ZendClassObject<EncoderOptions>,
+        enabled: bool,
+    ) -> &mut ZendClassObject<EncoderOptions> {
+        self_.set_flag(flags::COMPAT, enabled);
+        self_
+    }
+
+    /// Writes strings that are not valid UTF-8 as `{"$envelope": "<base64>"}`
+    /// instead of failing with `JSON_ERROR_UTF8`.
+    #[php(defaults(envelope = None))]
+    pub fn binary_base64(
+        self_: &mut ZendClassObject<EncoderOptions>,
+        envelope: Option<String>,
+    ) -> &mut ZendClassObject<EncoderOptions> {
+        self_.binary_envelope = Some(envelope.unwrap_or_else(|| BINARY_ENVELOPE.to_string()));
+        self_
+    }
+
+    /// Writes objects that define `__debugInfo()` as the array it returns
+    /// instead of their public properties, e.g. for log context.
+    #[php(defaults(enabled = true))]
+    pub fn debug_info(
+        self_: &mut ZendClassObject<EncoderOptions>,
+        enabled: bool,
+    ) -> &mut ZendClassObject<EncoderOptions> {
+        self_.debug_info = enabled;
+        self_
+    }
+
+    /// Writes an object that has one of the methods named in `methods` as
+    /// whatever the first of them returns, instead of its public
+    /// properties. The result is encoded like any other value; `[]` turns
+    /// the lookup off again.
+    #[php(defaults(methods = None))]
+    pub fn value_methods(
+        self_: &mut ZendClassObject<EncoderOptions>,
+        methods: Option<Vec<String>>,
+    ) -> &mut ZendClassObject<EncoderOptions> {
+        self_.value_methods =
+            methods.unwrap_or_else(|| DEFAULT_VALUE_METHODS.iter().map(|name| name.to_string()).collect());
+        self_
+    }
+
+    /// Hands control back to PHP every `kilobytes` KiB of output; see
+    /// `DecoderOptions::yieldEvery()`.
+    #[php(defaults(callback = None))]
+    pub fn yield_every<'a>(
+        self_: &'a mut ZendClassObject<EncoderOptions>,
+        kilobytes: i64,
+        callback: Option<&Zval>,
+    ) -> PhpResult<&'a mut ZendClassObject<EncoderOptions>> {
+        self_.pace = Some(Pace::new(kilobytes, callback)?);
+        Ok(self_)
+    }
+}
+
+impl EncoderOptions {
+    pub(crate) fn from_flags(flags: i64) -> Self {
+        Self {
+            flags,
+            ..Self::default()
+        }
+    }
+
+    /// Reads an encode `$options` argument, falling back to the request
+    /// defaults and then the INI default flags.
+    pub(crate) fn resolve(arg: Option<&Zval>, position: u8) -> PhpResult<Self> {
+        Ok(Self::from_arg(arg, position)?
+            .or_else(state::encode_defaults)
+            .unwrap_or_else(|| Self::from_flags(ini::default_encode_flags())))
+    }
+
+    /// Reads an `int|Elephant\Json\EncoderOptions|null` argument. Returns
+    /// `None` when it is omitted or null so the caller can pick its own
+    /// default.
+    pub(crate) fn from_arg(arg: Option<&Zval>, position: u8) -> PhpResult<Option<Self>> {
+        let Some(arg) = arg.filter(|arg| !arg.is_null()) else {
+            return Ok(None);
+        };
+        if let Some(flags) = arg.long() {
+            return Ok(Some(Self::from_flags(flags)));
+        }
+        if let Some(options) = arg.extract::<&Self>() {
+            return Ok(Some(options.clone()));
+        }
+
+        Err(PhpException::new(
+            format!(
+                "Argument #{} ($options) must be of type Elephant\\Json\\EncoderOptions|int|null, {} given",
+                position,
+                arg.get_type()
+            ),
+            0,
+            ce::type_error(),
+        ))
+    }
+
+    fn set_flag(&mut self, flag: i64, enabled: bool) {
+        if enabled {
+            self.flags |= flag;
+        } else {
+            self.flags &= !flag;
+        }
+    }
+}
+
+fn value_error(message: &str) -> PhpException {
+    PhpException::new(message.to_string(), 0, ce::value_error())
+}
+
+/// How JSON objects are materialized.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum ObjectMode {
+    #[default]
+    Object,
+    Array,
+    /// `ArrayObject` instances, which keep `{}` and `[]` apart while still
+    /// supporting array access.
+    Map,
+    /// `JsonObject` instances, readable as arrays and as objects.
+    Node,
+}
+
+/// Treatment of `NAN` and `INF` on encode.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub(crate) enum NonFinite {
+    #[default]
+    Error,
+    Null,
+    String,
+}
+
+/// What an empty PHP array encodes as.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub(crate) enum EmptyArrays {
+    #[default]
+    List,
+    Object,
+}
+
+/// Treatment of integers above `PHP_INT_MAX` or below `PHP_INT_MIN`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum IntOverflow {
+    #[default]
+    Float,
+    /// The exact lexeme.
+    String,
+    Error,
+    /// A `GMP` object built from the exact lexeme.
+    Gmp,
+    /// An ext-decimal `Decimal` built from the exact lexeme.
+    Decimal,
+}
+
+/// Treatment of numbers with a fraction or exponent.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum FloatPolicy {
+    #[default]
+    Float,
+    /// The number's lexeme, unchanged.
+    String,
+    /// An ext-decimal `Decimal` holding the exact value.
+    Decimal,
+}
+
+/// Which value wins when an object repeats a key.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum DuplicateKeys {
+    #[default]
+    Last,
+    First,
+    Error,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum KeyCase {
+    #[default]
+    Preserve,
+    Camel,
+    Snake,
+    Lower,
+    Upper,
+}
+
+impl KeyCase {
+    const NAMES: &'static [(&'static str, KeyCase)] = &[
+        ("preserve", KeyCase::Preserve),
+        ("camel", KeyCase::Camel),
+        ("snake", KeyCase::Snake),
+        ("lower", KeyCase::Lower),
+        ("upper", KeyCase::Upper),
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES.iter().find(|(n, _)| *n == name).map(|(_, case)| *case)
+    }
+
+    pub(crate) fn apply(self, key: String) -> String {
+        match self {
+            KeyCase::Preserve => key,
+            KeyCase::Lower => key.to_lowercase(),
+            KeyCase::Upper => key.to_uppercase(),
+            KeyCase::Camel => {
+                let mut out = String::with_capacity(key.len());
+                for (i, word) in key.split(['_', '-', ' ']).filter(|w| !w.is_empty()).enumerate() {
+                    let mut chars = word.chars();
+                    if let Some(first) = chars.next() {
+                        if i == 0 {
+                            out.extend(first.to_lowercase());
+                        } else {
+                            out.extend(first.to_uppercase());
+                        }
+                        out.push_str(chars.as_str());
+                    }
+                }
+                out
+            }
+            KeyCase::Snake => {
+                let mut out = String::with_capacity(key.len() + 4);
+                let mut prev_lower = false;
+                for c in key.chars() {
+                    if matches!(c, '-' | ' ') {
+                        out.push('_');
+                        prev_lower = false;
+                    } else if c.is_uppercase() {
+                        if prev_lower {
+                            out.push('_');
+                        }
+                        out.extend(c.to_lowercase());
+                        prev_lower = false;
+                    } else {
+                        out.push(c);
+                        prev_lower = c.is_lowercase() || c.is_ascii_digit();
+                    }
+                }
+                out
+            }
+        }
+    }
+}
+
+/// A PHP callable held by an options object.
+pub(crate) struct Callback(Zval);
+
+impl Callback {
+    pub(crate) fn new(callable: Zval) -> Self {
+        Self(callable)
+    }
+
+    pub(crate) fn call(&self, args: Vec<&dyn IntoZvalDyn>) -> Result<Zval, JsonError> {
+        ZendCallable::new(&self.0)
+            .and_then(|callable| callable.try_call(args))
+            .map_err(JsonError::engine)
+    }
+}
+
+impl Clone for Callback {
+    fn clone(&self) -> Self {
+        Self(self.0.shallow_clone())
+    }
+}
+
+/// How often to yield to PHP; see `DecoderOptions::yieldEvery()`.
+#[derive(Clone)]
+pub(crate) struct Pace {
+    pub bytes: usize,
+    /// Called instead of suspending the current Fiber.
+    pub callback: Option<Callback>,
+}
+
+impl Pace {
+    fn new(kilobytes: i64, callback: Option<&Zval>) -> PhpResult<Self> {
+        if kilobytes <= 0 {
+            return Err(value_error("Argument #1 ($kilobytes) must be greater than 0"));
+        }
+        let callback = callback.filter(|callback| !callback.is_null());
+        if callback.is_some_and(|callback| ZendCallable::new(callback).is_err()) {
+            return Err(PhpException::new(
+                "Argument #2 ($callback) must be a valid callback or null".to_string(),
+                0,
+                ce::type_error(),
+            ));
+        }
+        Ok(Self {
+            bytes: (kilobytes as usize).saturating_mul(1024),
+            callback: callback.map(|callback| Callback(callback.shallow_clone())),
+        })
+    }
+}
+
+/// Decoder settings, accepted in place of `$associative`.
+///
+/// ```php
+/// Json::decode($json, DecoderOptions::new()->assoc()->intOverflow('string'));
+/// ```
+#[php_class]
+#[php(name = "Elephant\\Json\\DecoderOptions")]
+#[derive(Clone, Default)]
+pub struct DecoderOptions {
+    pub(crate) mode: ObjectMode,
+    pub(crate) int_overflow: IntOverflow,
+    pub(crate) floats: FloatPolicy,
+    /// Numbers that only fit a float as infinity fail, see `finiteFloats()`.
+    pub(crate) finite_floats: bool,
+    pub(crate) duplicate_keys: DuplicateKeys,
+    pub(crate) max_depth: Option<i64>,
+    pub(crate) max_input_bytes: Option<usize>,
+    pub(crate) max_string_length: Option<usize>,
+    pub(crate) max_array_items: Option<usize>,
+    pub(crate) max_object_members: Option<usize>,
+    pub(crate) time_budget_ms: Option<u64>,
+    /// `None` defers to `elephant_json.memory_guard`.
+    pub(crate) memory_guard: Option<bool>,
+    pub(crate) truncate_depth: Option<usize>,
+    pub(crate) truncate_items: Option<usize>,
+    pub(crate) decompress: bool,
+    pub(crate) bom: BomPolicy,
+    /// Transcode UTF-16 and UTF-32 input, see `detectEncoding()`.
+    pub(crate) detect_encoding: bool,
+    pub(crate) key_case: KeyCase,
+    /// Removed from the start of keys before `key_case` applies.
+    pub(crate) key_prefix: Option<String>,
+    /// Called with each key after `key_case`; returns its replacement.
+    pub(crate) key_transform: Option<Callback>,
+    /// Objects with this single member and a base64 string value decode to
+    /// the raw bytes.
+    pub(crate) binary_envelope: Option<String>,
+    pub(crate) surrogates: CharPolicy,
+    pub(crate) control_chars: CharPolicy,
+    /// Set from `JSON_INVALID_UTF8_IGNORE` and `JSON_INVALID_UTF8_SUBSTITUTE`.
+    pub(crate) invalid_utf8: InvalidUtf8,
+    /// Every number becomes an `Elephant\Json\Number`.
+    pub(crate) raw_numbers: bool,
+    /// Only an object or array may be the document, see `strictSyntax()`.
+    pub(crate) container_root: bool,
+    pub(crate) pace: Option<Pace>,
+}
+
+#[php_impl]
+impl DecoderOptions {
+    pub fn __construct() -> Self {
+        Self::default()
+    }
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A locked-down starting point for untrusted input: duplicate keys,
+    /// out-of-range integers and numbers that overflow to an infinite float
+    /// are errors, nesting is capped at 64,
+    /// documents at 1 MiB, strings at 64 KiB, arrays at 10000 items and
+    /// objects at 1000 members. Input must be valid UTF-8 and lone
+    /// surrogate escapes are rejected. Chain further calls to adjust it.
+    pub fn strict_security() -> Self {
+        Self {
+            int_overflow: IntOverflow::Error,
+            finite_floats: true,
+            duplicate_keys: DuplicateKeys::Error,
+            max_depth: Some(64),
+            max_input_bytes: Some(1 << 20),
+            max_string_length: Some(1 << 16),
+            max_array_items: Some(10_000),
+            max_object_members: Some(1_000),
+            ..Self::default()
+        }
+    }
+
+    /// Decodes objects as associative arrays.
+    pub fn assoc(self_: &mut ZendClassObject<DecoderOptions>) -> &mut ZendClassObject<DecoderOptions> {
+        self_.mode = ObjectMode::Array;
+        self_
+    }
+
+    /// Decodes objects as `stdClass` instances (the default).
+    pub fn objects(self_: &mut ZendClassObject<DecoderOptions>) -> &mut ZendClassObject<DecoderOptions> {
+        self_.mode = ObjectMode::Object;
+        self_
+    }
+
+    /// Decodes objects as `ArrayObject` instances.
+    pub fn map(self_: &mut ZendClassObject<DecoderOptions>) -> &mut ZendClassObject<DecoderOptions> {
+        self_.mode = ObjectMode::Map;
+        self_
+    }
+
+    /// Decodes objects as `Elephant\Json\JsonObject` instances, which
+    /// support both `$object['name']` and `$object->name`.
+    pub fn json_objects(self_: &mut ZendClassObject<DecoderOptions>) -> &mut ZendClassObject<DecoderOptions> {
+        self_.mode = ObjectMode::Node;
+        self_
+    }
+
+    /// Integers beyond the PHP int range, in either direction: `"float"`
+    /// (default), `"string"`, `"gmp"` or `"decimal"` to keep the exact
+    /// value, or `"error"`. Takes precedence over `JSON_BIGINT_AS_STRING`, which only
+    /// applies when no options object is given.
+    pub fn int_overflow(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        policy: String,
+    ) -> PhpResult<&mut ZendClassObject<DecoderOptions>> {
+        self_.int_overflow = choice(
+            "policy",
+            &policy,
+            &[
+                ("float", IntOverflow::Float),
+                ("string", IntOverflow::String),
+                ("error", IntOverflow::Error),
+                ("gmp", IntOverflow::Gmp),
+                ("decimal", IntOverflow::Decimal),
+            ],
+        )?;
+        Ok(self_)
+    }
+
+    /// Older name for `intOverflow()`.
+    pub fn bigint(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        policy: String,
+    ) -> PhpResult<&mut ZendClassObject<DecoderOptions>> {
+        Self::int_overflow(self_, policy)
+    }
+
+    /// Fails with `Json::ERROR_FLOAT_OVERFLOW` when a number is too large
+    /// for a float, such as `1e400`, instead of decoding it to `INF`.
+    #[php(defaults(enabled = true))]
+    pub fn finite_floats(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        enabled: bool,
+    ) -> &mut ZendClassObject<DecoderOptions> {
+        self_.finite_floats = enabled;
+        self_
+    }
+
+    /// `"float"` (default), `"string"` to keep the exact lexeme or
+    /// `"decimal"` for an exact ext-decimal `Decimal`.
+    pub fn floats(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        policy: String,
+    ) -> PhpResult<&mut ZendClassObject<DecoderOptions>> {
+        self_.floats = choice(
+            "policy",
+            &policy,
+            &[
+                ("float", FloatPolicy::Float),
+                ("string", FloatPolicy::String),
+                ("decimal", FloatPolicy::Decimal),
+            ],
+        )?;
+        Ok(self_)
+    }
+
+    /// Decodes every number, whatever its size, as an `Elephant\Json\Number`
+    /// holding the exact lexeme, which encodes back unchanged. Takes
+    /// precedence over `intOverflow()` and `floats()`.
+    #[php(defaults(enabled = true))]
+    pub fn raw_numbers(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        enabled: bool,
+    ) -> &mut ZendClassObject<DecoderOptions> {
+        self_.raw_numbers = enabled;
+        self_
+    }
+
+    /// `"last"` (default), `"first"` or `"error"`.
+    pub fn duplicate_keys(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        policy: String,
+    ) -> PhpResult<&mut ZendClassObject<DecoderOptions>> {
+        self_.duplicate_keys = choice(
+            "policy",
+            &policy,
+            &[
+                ("last", DuplicateKeys::Last),
+                ("first", DuplicateKeys::First),
+                ("error", DuplicateKeys::Error),
+            ],
+        )?;
+        Ok(self_)
+    }
+
+    pub fn max_depth(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        depth: i64,
+    ) -> PhpResult<&mut ZendClassObject<DecoderOptions>> {
+        if depth <= 0 {
+            return Err(value_error("Argument #1 ($depth) must be greater than 0"));
+        }
+        self_.max_depth = Some(depth);
+        Ok(self_)
+    }
+
+    /// Rejects documents longer than `bytes` before parsing starts. `0`
+    /// lifts the limit, including one set in php.ini.
+    pub fn max_input_bytes(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        bytes: i64,
+    ) -> PhpResult<&mut ZendClassObject<DecoderOptions>> {
+        self_.max_input_bytes = Some(limit("bytes", bytes)?);
+        Ok(self_)
+    }
+
+    /// Longest string or key allowed, in bytes after unescaping.
+    pub fn max_string_length(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        bytes: i64,
+    ) -> PhpResult<&mut ZendClassObject<DecoderOptions>> {
+        self_.max_string_length = Some(limit("bytes", bytes)?);
+        Ok(self_)
+    }
+
+    pub fn max_array_items(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        items: i64,
+    ) -> PhpResult<&mut ZendClassObject<DecoderOptions>> {
+        self_.max_array_items = Some(limit("items", items)?);
+        Ok(self_)
+    }
+
+    /// Limit on members per object, counting repeated keys.
+    pub fn max_object_members(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        members: i64,
+    ) -> PhpResult<&mut ZendClassObject<DecoderOptions>> {
+        self_.max_object_members = Some(limit("members", members)?);
+        Ok(self_)
+    }
+
+    /// Aborts decoding or validation after `ms` milliseconds with
+    /// `Json::ERROR_TIME_BUDGET`.
+    pub fn time_budget(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        ms: i64,
+    ) -> PhpResult<&mut ZendClassObject<DecoderOptions>> {
+        self_.time_budget_ms = Some(limit("ms", ms)? as u64);
+        Ok(self_)
+    }
+
+    /// Replaces arrays and objects nested deeper than `depth` with
+    /// `Truncated` placeholders instead of failing; `0` truncates even the
+    /// top-level container.
+    pub fn truncate_depth(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        depth: i64,
+    ) -> PhpResult<&mut ZendClassObject<DecoderOptions>> {
+        self_.truncate_depth = Some(limit("depth", depth)?);
+        Ok(self_)
+    }
+
+    /// Keeps the first `items` elements of each array and replaces the rest
+    /// with a single `Truncated` placeholder.
+    pub fn truncate_items(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        items: i64,
+    ) -> PhpResult<&mut ZendClassObject<DecoderOptions>> {
+        self_.truncate_items = Some(limit("items", items)?);
+        Ok(self_)
+    }
+
+    /// Decompresses gzip, zlib (HTTP `deflate`) or zstd input before
+    /// decoding, detected by its header. Needs the zlib or zstd extension.
+    /// `maxInputBytes()` then limits the decompressed size, stopping
+    /// decompression with `Json::ERROR_INPUT_TOO_LARGE` once it is exceeded.
+    /// For zstd that needs `zstd_uncompress_add()`; without it, limited
+    /// zstd input is refused.
+    #[php(defaults(enabled = true))]
+    pub fn decompress(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        enabled: bool,
+    ) -> &mut ZendClassObject<DecoderOptions> {
+        self_.decompress = enabled;
+        self_
+    }
+
+    /// What to do with a UTF-8 byte order mark before the document:
+    /// `"skip"` (default), `"warn"` to skip it with an `E_WARNING`, or
+    /// `"reject"` as ext/json does.
+    pub fn bom(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        policy: String,
+    ) -> PhpResult<&mut ZendClassObject<DecoderOptions>> {
+        self_.bom = choice(
+            "policy",
+            &policy,
+            &[
+                ("skip", BomPolicy::Skip),
+                ("warn", BomPolicy::Warn),
+                ("reject", BomPolicy::Reject),
+            ],
+        )?;
+        Ok(self_)
+    }
+
+    /// Detects UTF-16 and UTF-32 input, with or without a byte order mark,
+    /// following RFC 4627, and transcodes it to UTF-8 before decoding.
+    #[php(defaults(enabled = true))]
+    pub fn detect_encoding(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        enabled: bool,
+    ) -> &mut ZendClassObject<DecoderOptions> {
+        self_.detect_encoding = enabled;
+        self_
+    }
+
+    /// Accepts only what RFC 8259 allows, for gateways that must agree with
+    /// other strict parsers: a byte order mark, UTF-16 or UTF-32 input, lone
+    /// surrogate escapes and raw control characters all fail, whatever
+    /// `bom()`, `detectEncoding()`, `loneSurrogates()` and `controlChars()`
+    /// said before. With `$require_container`, a scalar document fails too,
+    /// as RFC 4627 required.
+    #[php(defaults(require_container = false))]
+    pub fn strict_syntax(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        require_container: bool,
+    ) -> &mut ZendClassObject<DecoderOptions> {
+        self_.bom = BomPolicy::Reject;
+        self_.detect_encoding = false;
+        self_.surrogates = CharPolicy::Reject;
+        self_.control_chars = CharPolicy::Reject;
+        self_.container_root = require_container;
+        self_
+    }
+
+    /// Checks, while decoding, whether the rest of the document is likely to
+    /// fit in `memory_limit` and fails with `Json::ERROR_MEMORY_LIMIT` if
+    /// not. Off unless `elephant_json.memory_guard` is set.
+    #[php(defaults(enabled = true))]
+    pub fn memory_guard(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        enabled: bool,
+    ) -> &mut ZendClassObject<DecoderOptions> {
+        self_.memory_guard = Some(enabled);
+        self_
+    }
+
+    /// What to do with `\uD800`-style escapes that are not part of a pair:
+    /// `"reject"` (default, `JSON_ERROR_UTF16`), `"replace"` with U+FFFD or
+    /// `"pass"` to keep the six characters of the escape as written.
+    pub fn lone_surrogates(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        policy: String,
+    ) -> PhpResult<&mut ZendClassObject<DecoderOptions>> {
+        self_.surrogates = char_policy(&policy)?;
+        Ok(self_)
+    }
+
+    /// What to do with raw control characters inside strings: `"reject"`
+    /// (default, `JSON_ERROR_CTRL_CHAR`), `"replace"` with U+FFFD or `"pass"`.
+    pub fn control_chars(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        policy: String,
+    ) -> PhpResult<&mut ZendClassObject<DecoderOptions>> {
+        self_.control_chars = char_policy(&policy)?;
+        Ok(self_)
+    }
+
+    /// Rewrites object keys: `"camel"`, `"snake"`, `"lower"`, `"upper"` or
+    /// `"preserve"` (default).
+    pub fn key_case(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        case: String,
+    ) -> PhpResult<&mut ZendClassObject<DecoderOptions>> {
+        self_.key_case = choice("case", &case, KeyCase::NAMES)?;
+        Ok(self_)
+    }
+
+    /// Rewrites object keys with a built-in `keyCase()` strategy name or a
+    /// `callable(string $key): string`, run as each object is built.
+    pub fn transform_keys<'a>(
+        self_: &'a mut ZendClassObject<DecoderOptions>,
+        transform: &Zval,
+    ) -> PhpResult<&'a mut ZendClassObject<DecoderOptions>> {
+        if let Some(case) = transform.str().and_then(KeyCase::from_name) {
+            self_.key_case = case;
+            self_.key_transform = None;
+            return Ok(self_);
+        }
+        if ZendCallable::new(transform).is_err() {
+            return Err(PhpException::new(
+                "Argument #1 ($transform) must be a key case name or a valid callback".to_string(),
+                0,
+                ce::type_error(),
+            ));
+        }
+        self_.key_transform = Some(Callback(transform.shallow_clone()));
+        Ok(self_)
+    }
+
+    /// Restores the strings `EncoderOptions::binaryBase64()` wrapped: objects
+    /// whose only member is `$envelope` decode to the bytes its base64 value
+    /// holds.
+    #[php(defaults(envelope = None))]
+    pub fn binary_base64(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        envelope: Option<String>,
+    ) -> &mut ZendClassObject<DecoderOptions> {
+        self_.binary_envelope = Some(envelope.unwrap_or_else(|| BINARY_ENVELOPE.to_string()));
+        self_
+    }
+
+    /// Removes `$prefix` from keys that start with it, e.g. a vendor prefix.
+    pub fn strip_key_prefix(
+        self_: &mut ZendClassObject<DecoderOptions>,
+        prefix: String,
+    ) -> &mut ZendClassObject<DecoderOptions> {
+        self_.key_prefix = Some(prefix).filter(|prefix| !prefix.is_empty());
+        self_
+    }
+
+    /// Hands control back to PHP every `kilobytes` KiB of input, so an event
+    /// loop is not blocked for the whole document: `$callback` is called
+    /// with the number of bytes read so far, or without one the current
+    /// Fiber is suspended. Outside a Fiber and without a callback, nothing
+    /// happens.
+    #[php(defaults(callback = None))]
+    pub fn yield_every<'a>(
+        self_: &'a mut ZendClassObject<DecoderOptions>,
+        kilobytes: i64,
+        callback: Option<&Zval>,
+    ) -> PhpResult<&'a mut ZendClassObject<DecoderOptions>> {
+        self_.pace = Some(Pace::new(kilobytes, callback)?);
+        Ok(self_)
+    }
+}
+
+impl DecoderOptions {
+    /// The options ext/json's `$associative` and `$flags` arguments describe.
+    /// As there, a null `as_array` defers to `JSON_OBJECT_AS_ARRAY`.
+    pub(crate) fn from_flags(as_array: Option<bool>, flags: i64) -> Self {
+        let as_array = as_array.unwrap_or((flags & flags::OBJECT_AS_ARRAY) != 0);
+        Self {
+            mode: if as_array { ObjectMode::Array } else { ObjectMode::Object },
+            int_overflow: if (flags & flags::BIGINT_AS_STRING) != 0 {
+                IntOverflow::String
+            } else {
+                IntOverflow::Float
+            },
+            invalid_utf8: InvalidUtf8::from_flags(flags),
+            ..Self::default()
+        }
+    }
+
+    /// Everything in these options that can change what a document decodes
+    /// to, for keying `Codec::decodeCached()` entries. Limits that can only
+    /// make decoding fail are left out, as failures are not cached. `None`
+    /// when a key transform takes part, whose results need not repeat.
+    pub(crate) fn fingerprint(&self) -> Option<String> {
+        if self.key_transform.is_some() {
+            return None;
+        }
+        let values = (self.mode, self.int_overflow, self.floats, self.duplicate_keys, self.key_case, self.raw_numbers);
+        let input = (self.decompress, self.bom, self.detect_encoding);
+        let strings = (self.surrogates, self.control_chars, self.invalid_utf8);
+        let shape = (self.truncate_depth, self.truncate_items, &self.key_prefix, &self.binary_envelope);
+        Some(format!("{:?}{:?}{:?}{:?}", values, input, strings, shape))
+    }
+
+    /// Applies ext/json's `$associative` and `$flags` arguments. When both are
+    /// omitted the request defaults apply; an omitted `$flags` otherwise falls
+    /// back to default flags. Returns the options with the flags in effect.
+    pub(crate) fn resolve(as_array: Option<bool>, flags: Option<i64>) -> (Self, i64) {
+        let defaults = state::decode_defaults();
+        if let (None, None, Some(DecodeDefaults::Options(options))) = (as_array, flags, &defaults) {
+            return ((**options).clone(), 0);
+        }
+
+        let flags = flags.unwrap_or_else(|| match defaults {
+            Some(DecodeDefaults::Flags(flags)) => flags,
+            _ => ini::default_decode_flags(),
+        });
+        (Self::from_flags(as_array, flags), flags)
+    }
+
+    /// Reads a `bool|Elephant\Json\DecoderOptions|null` argument, the
+    /// `name` parameter at `position`. `flags` only applies when no options
+    /// object is given.
+    pub(crate) fn from_arg(arg: Option<&Zval>, flags: Option<i64>, position: u8, name: &str) -> PhpResult<Self> {
+        Self::read_arg(arg, position, name, |as_array| Self::resolve(as_array, flags).0)
+    }
+
+    /// Like `from_arg()`, but the request defaults never apply, only
+    /// `flags` or the ini default flags.
+    pub(crate) fn from_arg_without_defaults(
+        arg: Option<&Zval>,
+        flags: Option<i64>,
+        position: u8,
+        name: &str,
+    ) -> PhpResult<Self> {
+        let flags = flags.unwrap_or_else(ini::default_decode_flags);
+        Self::read_arg(arg, position, name, |as_array| Self::from_flags(as_array, flags))
+    }
+
+    /// The options object `arg` holds, or `from_bool` applied to the bool
+    /// or null it holds instead.
+    fn read_arg(
+        arg: Option<&Zval>,
+        position: u8,
+        name: &str,
+        from_bool: impl FnOnce(Option<bool>) -> Self,
+    ) -> PhpResult<Self> {
+        let Some(arg) = arg.filter(|arg| !arg.is_null()) else {
+            return Ok(from_bool(None));
+        };
+        if let Some(as_array) = arg.bool() {
+            return Ok(from_bool(Some(as_array)));
+        }
+        if let Some(options) = arg.extract::<&Self>() {
+            return Ok(options.clone());
+        }
+
+        Err(PhpException::new(
+            format!(
+                "Argument #{} (${}) must be of type Elephant\\Json\\DecoderOptions|bool|null, {} given",
+                position,
+                name,
+                arg.get_type()
+            ),
+            0,
+            ce::type_error(),
+        ))
+    }
+}
+
+/// What `Json::setDecodeDefaults()` was given.
+#[derive(Clone)]
+pub(crate) enum DecodeDefaults {
+    Flags(i64),
+    Options(Box<DecoderOptions>),
+}
+
+impl DecodeDefaults {
+    /// Reads an `int|Elephant\Json\DecoderOptions|null` argument.
+    pub(crate) fn from_arg(arg: Option<&Zval>, position: u8) -> PhpResult<Option<Self>> {
+        let Some(arg) = arg.filter(|arg| !arg.is_null()) else {
+            return Ok(None);
+        };
+        if let Some(flags) = arg.long() {
+            return Ok(Some(Self::Flags(flags)));
+        }
+        if let Some(options) = arg.extract::<&DecoderOptions>() {
+            return Ok(Some(Self::Options(Box::new(options.clone()))));
+        }
+
+        Err(PhpException::new(
+            format!(
+                "Argument #{} ($options) must be of type Elephant\\Json\\DecoderOptions|int|null, {} given",
+                position,
+                arg.get_type()
+            ),
+            0,
+            ce::type_error(),
+        ))
+    }
+}
+
+/// Validates a non-negative size or count argument.
+fn limit(param: &str, value: i64) -> PhpResult<usize> {
+    usize::try_from(value)
+        .map_err(|_| value_error(&format!("Argument #1 (${}) must be greater than or equal to 0", param)))
+}
+
+fn choice<T: Copy>(param: &str, value: &str, choices: &[(&str, T)]) -> PhpResult<T> {
+    if let Some((_, choice)) = choices.iter().find(|(name, _)| *name == value) {
+        return Ok(*choice);
+    }
+
+    let names: Vec<String> = choices.iter().map(|(name, _)| format!("\"{}\"", name)).collect();
+    Err(value_error(&format!("Argument #1 (${}) must be one of {}", param, names.join(", "))))
+}
+
+fn char_policy(policy: &str) -> PhpResult<CharPolicy> {
+    choice(
+        "policy",
+        policy,
+        &[
+            ("reject", CharPolicy::Reject),
+            ("replace", CharPolicy::Replace),
+            ("pass", CharPolicy::Pass),
+        ],
+    )
+}
@@ -0,0 +1,52 @@
+//! Handing control back to PHP during long decodes and encodes, so event
+//! loops built on Fibers (revolt, ReactPHP, Swoole's fiber mode) keep
+//! serving other tasks while a huge document is processed.
+
+use std::cell::Cell;
+
+use ext_php_rs::types::ZendCallable;
+
+use crate::error::JsonError;
+use crate::options::Pace;
+
+/// Tracks progress against a [`Pace`] and yields each time another
+/// interval's worth of bytes has been processed.
+pub(crate) struct Pacer {
+    pace: Pace,
+    /// Byte count at which to yield next.
+    next: Cell<usize>,
+}
+
+impl Pacer {
+    pub fn new(pace: Pace) -> Self {
+        Self {
+            next: Cell::new(pace.bytes),
+            pace,
+        }
+    }
+
+    /// Called with the number of bytes read or written so far.
+    pub fn advance(&self, processed: usize) -> Result<(), JsonError> {
+        if processed < self.next.get() {
+            return Ok(());
+        }
+        self.next.set((processed / self.pace.bytes + 1) * self.pace.bytes);
+        match &self.pace.callback {
+            Some(callback) => callback.call(vec![&(processed as i64)]).map(drop),
+            None => suspend(),
+        }
+    }
+}
+
+/// `Fiber::suspend()`, when running inside a Fiber.
+fn suspend() -> Result<(), JsonError> {
+    let call = |name: &str| {
+        ZendCallable::try_from_name(name)
+            .and_then(|function| function.try_call(vec![]))
+            .map_err(JsonError::engine)
+    };
+    if call("Fiber::getCurrent")?.is_null() {
+        return Ok(());
+    }
+    call("Fiber::suspend").map(drop)
+}
@@ -0,0 +1,119 @@
+//! JSON Pointer (RFC 6901) helpers, and `Pointer` for paths applied to
+//! many documents.
+
+use std::borrow::Cow;
+use std::rc::Rc;
+
+use ext_php_rs::convert::FromZval;
+use ext_php_rs::flags::DataType;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::Zval;
+use ext_php_rs::zend::ce;
+
+/// A JSON Pointer split into its reference tokens once. Every method taking
+/// a pointer string also accepts a `Pointer`, which saves unescaping the
+/// path again on each call.
+///
+/// `*` and `**` are ordinary member names to `get()` and the `Document`
+/// methods; only `Codec::filterKeys()` treats them as wildcards.
+#[php_class]
+#[php(name = "Elephant\\Json\\Pointer")]
+#[php(implements(ce = ce::stringable, stub = "\\Stringable"))]
+pub struct Pointer {
+    text: String,
+    tokens: Rc<[String]>,
+}
+
+#[php_impl]
+impl Pointer {
+    /// Throws a `ValueError` unless `$pointer` is empty or starts with `/`.
+    pub fn compile(pointer: String) -> PhpResult<Pointer> {
+        let tokens = tokens(&pointer).map_err(|message| PhpException::new(message, 0, ce::value_error()))?;
+        Ok(Self {
+            text: pointer,
+            tokens: tokens.into(),
+        })
+    }
+
+    /// The unescaped reference tokens.
+    pub fn tokens(&self) -> Vec<String> {
+        self.tokens.to_vec()
+    }
+
+    pub fn __to_string(&self) -> String {
+        self.text.clone()
+    }
+}
+
+/// A pointer argument: a string, parsed where it is used, or a compiled
+/// [`Pointer`].
+pub struct PointerArg {
+    text: String,
+    compiled: Option<Rc<[String]>>,
+}
+
+impl PointerArg {
+    pub fn root() -> Self {
+        Self {
+            text: String::new(),
+            compiled: Some(Rc::from([])),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn tokens(&self) -> Result<Cow<'_, [String]>, String> {
+        match &self.compiled {
+            Some(tokens) => Ok(Cow::Borrowed(tokens)),
+            None => Ok(Cow::Owned(tokens(&self.text)?)),
+        }
+    }
+}
+
+impl FromZval<'_> for PointerArg {
+    const TYPE: DataType = DataType::Mixed;
+
+    fn from_zval(zval: &Zval) -> Option<Self> {
+        if let Some(pointer) = zval.extract::<&Pointer>() {
+            return Some(Self {
+                text: pointer.text.clone(),
+                compiled: Some(pointer.tokens.clone()),
+            });
+        }
+        zval.str().map(|text| Self {
+            text: text.to_string(),
+            compiled: None,
+        })
+    }
+}
+
+/// Splits `pointer` into its unescaped reference tokens; `""` refers to the
+/// whole document and has none.
+pub(crate) fn tokens(pointer: &str) -> Result<Vec<String>, String> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(path) = pointer.strip_prefix('/') else {
+        return Err(format!("Invalid JSON Pointer '{}': must be empty or start with '/'", pointer));
+    };
+    Ok(path.split('/').map(|token| token.replace("~1", "/").replace("~0", "~")).collect())
+}
+
+/// Escapes a member name for use as a reference token.
+pub(crate) fn escape(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+/// The pointer made of `tokens`, the inverse of [`tokens`].
+pub(crate) fn join(tokens: &[String]) -> String {
+    tokens.iter().map(|token| format!("/{}", escape(token))).collect()
+}
+
+/// A decimal array index without leading zeros; `-` never names an existing
+/// element.
+pub(crate) fn array_index(token: &str) -> Option<usize> {
+    let canonical = token == "0" || (!token.starts_with('0') && !token.starts_with('+'));
+    canonical.then(|| token.parse().ok()).flatten()
+}
@@ -0,0 +1,65 @@
+//! Numbers kept as written, for proxies and signers that must re-emit a
+//! document byte for byte.
+
+use ext_php_rs::prelude::*;
+use ext_php_rs::zend::ce;
+
+use crate::reader::is_number;
+
+/// A JSON number holding its exact lexeme, as `DecoderOptions::rawNumbers()`
+/// decodes every number. Encoding writes the lexeme back unchanged, so
+/// `1.10` stays `1.10` and `1e400` survives.
+#[php_class]
+#[php(name = "Elephant\\Json\\Number")]
+#[php(implements(ce = ce::stringable, stub = "\\Stringable"))]
+pub struct Number {
+    raw: String,
+}
+
+#[php_impl]
+impl Number {
+    pub fn __construct(lexeme: String) -> PhpResult<Self> {
+        if !is_number(&lexeme) {
+            return Err(value_error("Argument #1 ($lexeme) must be a JSON number"));
+        }
+        Ok(Self { raw: lexeme })
+    }
+
+    pub fn __to_string(&self) -> String {
+        self.raw.clone()
+    }
+
+    /// Whether the lexeme has neither a fraction nor an exponent.
+    pub fn is_integer(&self) -> bool {
+        !self.raw.contains(['.', 'e', 'E'])
+    }
+
+    /// Throws a `ValueError` unless the number is an integer within the PHP
+    /// int range.
+    pub fn to_int(&self) -> PhpResult<i64> {
+        match self.is_integer() {
+            true => self.raw.parse().map_err(|_| value_error(&format!("{} is out of the int range", self.raw))),
+            false => Err(value_error(&format!("{} is not an integer", self.raw))),
+        }
+    }
+
+    /// The nearest float, `INF` or `-INF` for magnitudes beyond its range.
+    pub fn to_float(&self) -> f64 {
+        // The lexeme was validated, so it always parses.
+        self.raw.parse().unwrap_or(f64::NAN)
+    }
+}
+
+impl Number {
+    pub(crate) fn new(raw: &str) -> Self {
+        Self { raw: raw.to_string() }
+    }
+
+    pub(crate) fn lexeme(&self) -> &str {
+        &self.raw
+    }
+}
+
+fn value_error(message: &str) -> PhpException {
+    PhpException::new(message.to_string(), 0, ce::value_error())
+}
@@ -0,0 +1,372 @@
+use std::fmt;
+
+use crate::escape::CharPolicy;
+use crate::flags;
+
+/// Containers nested deeper than this fail whatever depth the caller
+/// allows, as the parsers that build values recurse once per level and
+/// would otherwise run out of stack.
+pub(crate) const MAX_NESTING: usize = 4096;
+
+/// A structural event produced while reading a JSON document.
+///
+/// String-like events carry the raw lexeme including quotes and escapes so
+/// callers can decide whether they need the decoded content at all.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Event<'a> {
+    BeginObject,
+    EndObject,
+    BeginArray,
+    EndArray,
+    Key(&'a str),
+    String(&'a str),
+    Number(&'a str),
+    Bool(bool),
+    Null,
+}
+
+#[derive(Debug)]
+pub(crate) struct ReadError {
+    pub message: String,
+    pub offset: usize,
+    /// The `JSON_ERROR_*` code this maps to.
+    pub code: i64,
+}
+
+impl ReadError {
+    pub fn new(message: impl Into<String>, offset: usize) -> Self {
+        Self::with_code(flags::ERROR_SYNTAX, message, offset)
+    }
+
+    pub fn with_code(code: i64, message: impl Into<String>, offset: usize) -> Self {
+        Self {
+            message: message.into(),
+            offset,
+            code,
+        }
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    /// A value is required (document start, after `:` or after `,` in an array).
+    Value,
+    /// Right after `[`: a value or `]`.
+    ValueOrEnd,
+    /// Right after `{`: a key or `}`.
+    KeyOrEnd,
+    /// After `,` in an object.
+    Key,
+    /// After a key.
+    Colon,
+    /// After a complete value: `,`, the closing bracket, or end of input.
+    Separator,
+    Done,
+}
+
+/// Pull parser yielding [`Event`]s in document order, validating the
+/// grammar as it goes without allocating PHP values.
+pub(crate) struct Reader<'a> {
+    input: &'a str,
+    pos: usize,
+    /// Open containers; `true` for objects.
+    stack: Vec<bool>,
+    state: State,
+    /// Offset of the token behind the last event.
+    token_start: usize,
+    /// Whether raw control characters are allowed inside strings.
+    control_chars: bool,
+    /// Whether the document must be an object or array.
+    container_root: bool,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            pos: 0,
+            stack: Vec::new(),
+            state: State::Value,
+            token_start: 0,
+            control_chars: false,
+            container_root: false,
+        }
+    }
+
+    /// Leaves raw control characters in strings for the caller to handle.
+    pub fn with_control_chars(mut self, allowed: bool) -> Self {
+        self.control_chars = allowed;
+        self
+    }
+
+    /// Rejects a scalar as the whole document, as RFC 4627 did.
+    pub fn with_container_root(mut self, required: bool) -> Self {
+        self.container_root = required;
+        self
+    }
+
+    /// Bytes of input not yet consumed.
+    pub fn remaining(&self) -> usize {
+        self.input.len() - self.pos
+    }
+
+    /// Byte offset where the token of the last returned event starts.
+    pub fn token_start(&self) -> usize {
+        self.token_start
+    }
+
+    /// Bytes consumed so far.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Current container nesting depth.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Returns the next event and the byte offset where its token starts,
+    /// or `None` once the document is complete.
+    pub fn next_event(&mut self) -> Result<Option<(usize, Event<'a>)>, ReadError> {
+        loop {
+            self.skip_whitespace();
+            let start = self.pos;
+            self.token_start = start;
+            let Some(&byte) = self.input.as_bytes().get(start) else {
+                return match self.state {
+                    State::Done => Ok(None),
+                    _ => Err(ReadError::new("Unexpected end of input", start)),
+                };
+            };
+
+            match self.state {
+                State::Done => {
+                    return Err(ReadError::new("Unexpected trailing content", start));
+                }
+                State::Colon => {
+                    if byte != b':' {
+                        return Err(ReadError::new("Expected ':'", start));
+                    }
+                    self.pos += 1;
+                    self.state = State::Value;
+                }
+                State::Separator => match byte {
+                    b',' => {
+                        self.pos += 1;
+                        self.state = if self.in_object() { State::Key } else { State::Value };
+                    }
+                    b']' | b'}' => return self.close(byte, start).map(Some),
+                    _ => return Err(ReadError::new("Expected ',' or closing bracket", start)),
+                },
+                State::KeyOrEnd | State::Key => match byte {
+                    b'}' if self.state == State::KeyOrEnd => {
+                        return self.close(byte, start).map(Some);
+                    }
+                    b'"' => {
+                        let raw = self.scan_string()?;
+                        self.state = State::Colon;
+                        return Ok(Some((start, Event::Key(raw))));
+                    }
+                    _ => return Err(ReadError::new("Expected object key", start)),
+                },
+                State::Value | State::ValueOrEnd => {
+                    if byte == b']' && self.state == State::ValueOrEnd {
+                        return self.close(byte, start).map(Some);
+                    }
+                    return self.scan_value(byte, start).map(Some);
+                }
+            }
+        }
+    }
+
+    /// Consumes the value starting at the current position and returns its
+    /// raw byte span. Must be called where a value is expected.
+    pub fn skip_value(&mut self) -> Result<(usize, usize), ReadError> {
+        let depth = self.depth();
+        let mut start = None;
+
+        loop {
+            let (offset, _) = self
+                .next_event()?
+                .ok_or_else(|| ReadError::new("Unexpected end of input", self.pos))?;
+            let start = *start.get_or_insert(offset);
+            if self.depth() == depth {
+                return Ok((start, self.pos));
+            }
+        }
+    }
+
+    fn in_object(&self) -> bool {
+        self.stack.last().copied().unwrap_or(false)
+    }
+
+    fn after_value(&mut self) {
+        self.state = if self.stack.is_empty() { State::Done } else { State::Separator };
+    }
+
+    fn close(&mut self, byte: u8, start: usize) -> Result<(usize, Event<'a>), ReadError> {
+        let is_object = byte == b'}';
+        if self.stack.last() != Some(&is_object) {
+            return Err(ReadError::new("Mismatched closing bracket", start));
+        }
+        self.stack.pop();
+        self.pos += 1;
+        self.after_value();
+        let event = if is_object { Event::EndObject } else { Event::EndArray };
+        Ok((start, event))
+    }
+
+    fn scan_value(&mut self, byte: u8, start: usize) -> Result<(usize, Event<'a>), ReadError> {
+        if self.container_root && self.stack.is_empty() && !matches!(byte, b'{' | b'[') {
+            return Err(ReadError::new("Expected an object or array", start));
+        }
+        let event = match byte {
+            b'{' => {
+                self.pos += 1;
+                self.stack.push(true);
+                self.state = State::KeyOrEnd;
+                return Ok((start, Event::BeginObject));
+            }
+            b'[' => {
+                self.pos += 1;
+                self.stack.push(false);
+                self.state = State::ValueOrEnd;
+                return Ok((start, Event::BeginArray));
+            }
+            b'"' => Event::String(self.scan_string()?),
+            b'-' | b'0'..=b'9' => Event::Number(self.scan_number()?),
+            b't' => self.scan_literal("true", Event::Bool(true))?,
+            b'f' => self.scan_literal("false", Event::Bool(false))?,
+            b'n' => self.scan_literal("null", Event::Null)?,
+            _ => return Err(ReadError::new("Unexpected character", start)),
+        };
+        self.after_value();
+        Ok((start, event))
+    }
+
+    fn scan_literal(&mut self, word: &str, event: Event<'a>) -> Result<Event<'a>, ReadError> {
+        if !self.input[self.pos..].starts_with(word) {
+            return Err(ReadError::new("Invalid literal", self.pos));
+        }
+        self.pos += word.len();
+        Ok(event)
+    }
+
+    fn scan_number(&mut self) -> Result<&'a str, ReadError> {
+        let bytes = self.input.as_bytes();
+        let start = self.pos;
+        let mut pos = start;
+        let digits = |pos: &mut usize| {
+            let from = *pos;
+            while bytes.get(*pos).is_some_and(u8::is_ascii_digit) {
+                *pos += 1;
+            }
+            *pos - from
+        };
+
+        if bytes.get(pos) == Some(&b'-') {
+            pos += 1;
+        }
+        match bytes.get(pos) {
+            Some(b'0') => {
+                pos += 1;
+                if bytes.get(pos).is_some_and(u8::is_ascii_digit) {
+                    return Err(ReadError::new("Leading zeros are not allowed", start));
+                }
+            }
+            Some(b'1'..=b'9') => {
+                digits(&mut pos);
+            }
+            _ => return Err(ReadError::new("Invalid number", start)),
+        }
+        if bytes.get(pos) == Some(&b'.') {
+            pos += 1;
+            if digits(&mut pos) == 0 {
+                return Err(ReadError::new("Invalid number", start));
+            }
+        }
+        if matches!(bytes.get(pos), Some(b'e' | b'E')) {
+            pos += 1;
+            if matches!(bytes.get(pos), Some(b'+' | b'-')) {
+                pos += 1;
+            }
+            if digits(&mut pos) == 0 {
+                return Err(ReadError::new("Invalid number", start));
+            }
+        }
+
+        self.pos = pos;
+        Ok(&self.input[start..pos])
+    }
+
+    fn scan_string(&mut self) -> Result<&'a str, ReadError> {
+        let bytes = self.input.as_bytes();
+        let start = self.pos;
+        let mut pos = start + 1;
+
+        loop {
+            match bytes.get(pos) {
+                None => return Err(ReadError::new("Unterminated string", start)),
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    match bytes.get(pos + 1) {
+                        Some(b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't') => pos += 2,
+                        Some(b'u') => {
+                            let hex = bytes.get(pos + 2..pos + 6);
+                            if !hex.is_some_and(|h| h.iter().all(u8::is_ascii_hexdigit)) {
+                                return Err(ReadError::new("Invalid unicode escape", pos));
+                            }
+                            pos += 6;
+                        }
+                        _ => return Err(ReadError::new("Invalid escape sequence", pos)),
+                    }
+                }
+                Some(&b) if b < 0x20 && !self.control_chars => {
+                    return Err(ReadError::with_code(
+                        flags::ERROR_CTRL_CHAR,
+                        "Control character in string",
+                        pos,
+                    ));
+                }
+                Some(_) => pos += 1,
+            }
+        }
+
+        self.pos = pos + 1;
+        Ok(&self.input[start..self.pos])
+    }
+
+    fn skip_whitespace(&mut self) {
+        let bytes = self.input.as_bytes();
+        while matches!(bytes.get(self.pos), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+}
+
+/// Decodes a raw string lexeme as produced by [`Reader`] (quotes included).
+pub(crate) fn string_content(raw: &str) -> Result<String, String> {
+    crate::escape::unescape(&raw[1..raw.len() - 1])
+}
+
+/// Whether `s` is exactly one JSON number literal.
+pub(crate) fn is_number(s: &str) -> bool {
+    let mut reader = Reader::new(s);
+    matches!(reader.next_event(), Ok(Some((0, Event::Number(n)))) if n.len() == s.len())
+}
+
+/// [`string_content`] with the decoder's surrogate and control character
+/// policies.
+pub(crate) fn decode_string(
+    raw: &str,
+    surrogates: CharPolicy,
+    controls: CharPolicy,
+) -> Result<String, ReadError> {
+    crate::escape::unescape_with(&raw[1..raw.len() - 1], surrogates, controls)
+}
@@ -0,0 +1,431 @@
+//! Best-effort repair of damaged JSON, e.g. hand-edited config, truncated
+//! payloads or language model output.
+
+use crate::reader::{is_number, Reader};
+
+/// One change made to the input, named by `kind` and located by the byte
+/// offset in the input where it applies.
+pub(crate) struct Fix {
+    pub kind: &'static str,
+    pub offset: usize,
+}
+
+pub(crate) struct Repaired {
+    pub json: String,
+    pub fixes: Vec<Fix>,
+}
+
+/// Rewrites `input` into valid JSON. Fails only if the result still does
+/// not parse, which would be a bug in the repairer.
+pub(crate) fn repair(input: &str) -> Result<Repaired, String> {
+    let repaired = Repairer::new(input).run();
+
+    let mut reader = Reader::new(&repaired.json);
+    while reader
+        .next_event()
+        .map_err(|e| format!("Could not repair the document: {}", e))?
+        .is_some()
+    {}
+    Ok(repaired)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Expect {
+    Value,
+    Key,
+    Colon,
+    /// A value is complete: a comma, a closing bracket or the end.
+    After,
+}
+
+struct Repairer<'a> {
+    input: &'a str,
+    pos: usize,
+    out: String,
+    fixes: Vec<Fix>,
+    /// Open containers; `true` for objects.
+    stack: Vec<bool>,
+    expect: Expect,
+    /// Input offset and output index of the last `,` written, which is
+    /// taken out again if it turns out to be trailing.
+    pending_comma: Option<(usize, usize)>,
+    /// Top-level values seen; more than one are wrapped in an array.
+    roots: usize,
+}
+
+impl<'a> Repairer<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            pos: 0,
+            out: String::with_capacity(input.len() + 16),
+            fixes: Vec::new(),
+            stack: Vec::new(),
+            expect: Expect::Value,
+            pending_comma: None,
+            roots: 0,
+        }
+    }
+
+    fn run(mut self) -> Repaired {
+        let bytes = self.input.as_bytes();
+        while let Some(&byte) = bytes.get(self.pos) {
+            let start = self.pos;
+            let next = bytes.get(start + 1).copied();
+            match byte {
+                b' ' | b'\t' | b'\n' | b'\r' => {
+                    self.out.push(byte as char);
+                    self.pos += 1;
+                }
+                b'/' if next == Some(b'/') => self.line_comment(),
+                b'#' => self.line_comment(),
+                b'/' if next == Some(b'*') => {
+                    self.fix("comment", start);
+                    self.pos = self.input[start + 2..]
+                        .find("*/")
+                        .map_or(self.input.len(), |end| start + 2 + end + 2);
+                }
+                b'"' | b'\'' => {
+                    let s = self.string(byte);
+                    self.scalar(start, &s, true);
+                }
+                b'{' | b'[' => {
+                    self.pos += 1;
+                    self.open(start, byte == b'{');
+                }
+                b'}' | b']' => {
+                    self.pos += 1;
+                    self.close(start, byte == b'}');
+                }
+                b',' => {
+                    self.pos += 1;
+                    self.comma(start);
+                }
+                b':' => {
+                    self.pos += 1;
+                    if self.expect == Expect::Colon {
+                        self.out.push(':');
+                        self.expect = Expect::Value;
+                    } else {
+                        self.fix("unexpected_colon", start);
+                    }
+                }
+                b'-' if next.is_some_and(|c| c.is_ascii_alphabetic()) => self.bareword(),
+                b'-' | b'+' | b'.' | b'0'..=b'9' => self.number(),
+                _ => {
+                    let c = self.input[start..].chars().next().unwrap_or_default();
+                    if c.is_alphabetic() || c == '_' || c == '$' {
+                        self.bareword();
+                    } else {
+                        self.fix("unexpected_character", start);
+                        self.pos += c.len_utf8();
+                    }
+                }
+            }
+        }
+        self.finish()
+    }
+
+    fn finish(mut self) -> Repaired {
+        let end = self.input.len();
+        while let Some(&object) = self.stack.last() {
+            self.end_container(end);
+            self.stack.pop();
+            self.fix("missing_bracket", end);
+            self.out.push(if object { '}' } else { ']' });
+            self.expect = Expect::After;
+        }
+        self.drop_trailing_comma();
+
+        if self.roots == 0 {
+            self.fix("empty", 0);
+            self.out.push_str("null");
+        } else if self.roots > 1 {
+            self.out = format!("[{}]", self.out);
+        }
+        Repaired {
+            json: self.out,
+            fixes: self.fixes,
+        }
+    }
+
+    fn fix(&mut self, kind: &'static str, offset: usize) {
+        self.fixes.push(Fix { kind, offset });
+    }
+
+    fn in_object(&self) -> bool {
+        self.stack.last().copied().unwrap_or(false)
+    }
+
+    fn line_comment(&mut self) {
+        self.fix("comment", self.pos);
+        self.pos = self.input[self.pos..]
+            .find('\n')
+            .map_or(self.input.len(), |end| self.pos + end);
+    }
+
+    /// Gets the output ready for a value or member name starting at
+    /// `offset`; returns whether a member name is due.
+    fn before_value(&mut self, offset: usize) -> bool {
+        match self.expect {
+            Expect::After => {
+                if self.stack.is_empty() {
+                    self.fix("concatenated_values", offset);
+                }
+                if self.pending_comma.take().is_none() {
+                    if !self.stack.is_empty() {
+                        self.fix("missing_comma", offset);
+                    }
+                    self.out.push(',');
+                }
+                self.expect = if self.in_object() { Expect::Key } else { Expect::Value };
+            }
+            Expect::Colon => {
+                self.fix("missing_colon", offset);
+                self.out.push(':');
+                self.expect = Expect::Value;
+            }
+            Expect::Value | Expect::Key => {}
+        }
+        if self.stack.is_empty() {
+            self.roots += 1;
+        }
+        self.expect == Expect::Key
+    }
+
+    /// Writes a string, number or literal. `quoted` tells whether `text` is
+    /// already a JSON string, so it may serve as a member name as is.
+    fn scalar(&mut self, offset: usize, text: &str, quoted: bool) {
+        if self.before_value(offset) {
+            if quoted {
+                self.out.push_str(text);
+            } else {
+                self.fix("unquoted_key", offset);
+                self.out.push_str(&quote(text));
+            }
+            self.expect = Expect::Colon;
+            return;
+        }
+        self.out.push_str(text);
+        self.expect = Expect::After;
+    }
+
+    fn open(&mut self, offset: usize, object: bool) {
+        if self.before_value(offset) {
+            self.fix("missing_key", offset);
+            self.out.push_str("\"\":");
+        }
+        self.out.push(if object { '{' } else { '[' });
+        self.stack.push(object);
+        self.expect = if object { Expect::Key } else { Expect::Value };
+    }
+
+    fn close(&mut self, offset: usize, object: bool) {
+        if !self.stack.contains(&object) {
+            self.fix("unmatched_bracket", offset);
+            return;
+        }
+        while let Some(&top) = self.stack.last() {
+            self.end_container(offset);
+            self.stack.pop();
+            self.out.push(if top { '}' } else { ']' });
+            self.expect = Expect::After;
+            if top == object {
+                break;
+            }
+            self.fix("missing_bracket", offset);
+        }
+    }
+
+    /// Completes a member whose value is missing and drops a trailing comma
+    /// before the innermost container is closed. Runs while that container
+    /// is still on the stack.
+    fn end_container(&mut self, offset: usize) {
+        match self.expect {
+            Expect::Colon => {
+                self.fix("missing_value", offset);
+                self.out.push_str(":null");
+            }
+            Expect::Value if self.in_object() => {
+                self.fix("missing_value", offset);
+                self.out.push_str("null");
+            }
+            _ => self.drop_trailing_comma(),
+        }
+    }
+
+    fn drop_trailing_comma(&mut self) {
+        if let Some((offset, index)) = self.pending_comma.take() {
+            self.fix("trailing_comma", offset);
+            self.out.remove(index);
+        }
+    }
+
+    fn comma(&mut self, offset: usize) {
+        match self.expect {
+            Expect::After if self.pending_comma.is_none() => {}
+            Expect::Colon | Expect::Value if self.in_object() => {
+                self.end_container(offset);
+                self.expect = Expect::After;
+            }
+            _ => return self.fix("extra_comma", offset),
+        }
+        self.pending_comma = Some((offset, self.out.len()));
+        self.out.push(',');
+    }
+
+    /// Reads a double- or single-quoted string starting at the current
+    /// position and returns it as a valid JSON string.
+    fn string(&mut self, quote: u8) -> String {
+        let start = self.pos;
+        if quote == b'\'' {
+            self.fix("single_quotes", start);
+        }
+        self.pos += 1;
+
+        let mut s = String::from('"');
+        loop {
+            let Some(c) = self.input[self.pos..].chars().next() else {
+                self.fix("unterminated_string", start);
+                break;
+            };
+            self.pos += c.len_utf8();
+            match c {
+                c if c as u32 == u32::from(quote) => break,
+                '\\' => self.escape(&mut s, quote),
+                '"' => s.push_str("\\\""),
+                '\u{0}'..='\u{1f}' => {
+                    self.fix("control_character", self.pos - 1);
+                    match c {
+                        '\n' => s.push_str("\\n"),
+                        '\r' => s.push_str("\\r"),
+                        '\t' => s.push_str("\\t"),
+                        c => s.push_str(&format!("\\u{:04x}", c as u32)),
+                    }
+                }
+                c => s.push(c),
+            }
+        }
+        s.push('"');
+        s
+    }
+
+    /// Handles the character after a backslash inside a string.
+    fn escape(&mut self, s: &mut String, quote: u8) {
+        let rest = &self.input[self.pos..];
+        match rest.as_bytes().first() {
+            Some(&c @ (b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't')) => {
+                s.push('\\');
+                s.push(c as char);
+                self.pos += 1;
+            }
+            Some(b'u') if rest.len() >= 5 && rest.as_bytes()[1..5].iter().all(u8::is_ascii_hexdigit) => {
+                s.push('\\');
+                s.push_str(&rest[..5]);
+                self.pos += 5;
+            }
+            Some(b'\'') => {
+                if quote != b'\'' {
+                    self.fix("invalid_escape", self.pos - 1);
+                }
+                s.push('\'');
+                self.pos += 1;
+            }
+            _ => {
+                self.fix("invalid_escape", self.pos - 1);
+                s.push_str("\\\\");
+            }
+        }
+    }
+
+    fn number(&mut self) {
+        let start = self.pos;
+        let bytes = self.input.as_bytes();
+        while bytes
+            .get(self.pos)
+            .is_some_and(|c| matches!(c, b'0'..=b'9' | b'+' | b'-' | b'.' | b'e' | b'E'))
+        {
+            self.pos += 1;
+        }
+        let raw = &self.input[start..self.pos];
+
+        if is_number(raw) {
+            self.scalar(start, raw, false);
+            return;
+        }
+        match normalize_number(raw) {
+            Some(fixed) => {
+                self.fix("number", start);
+                self.scalar(start, &fixed, false);
+            }
+            None => {
+                self.fix("unquoted_string", start);
+                self.scalar(start, &quote(raw), true);
+            }
+        }
+    }
+
+    fn bareword(&mut self) {
+        let start = self.pos;
+        let end = self.input[start..]
+            .char_indices()
+            .skip(1)
+            .find(|(_, c)| !(c.is_alphanumeric() || matches!(c, '_' | '$' | '-' | '.')))
+            .map_or(self.input.len(), |(i, _)| start + i);
+        self.pos = end;
+        let word = &self.input[start..end];
+
+        if matches!(self.expect, Expect::Key) || (self.expect == Expect::After && self.in_object()) {
+            self.scalar(start, word, false);
+            return;
+        }
+        let literal = match word {
+            "true" | "false" | "null" => Some(word),
+            "True" | "TRUE" => Some("true"),
+            "False" | "FALSE" => Some("false"),
+            "None" | "NULL" | "Null" | "nil" | "undefined" | "NaN" | "Infinity" | "-Infinity" => {
+                Some("null")
+            }
+            _ => None,
+        };
+        match literal {
+            Some(literal) => {
+                if literal != word {
+                    self.fix("literal", start);
+                }
+                self.scalar(start, literal, false);
+            }
+            None => {
+                self.fix("unquoted_string", start);
+                self.scalar(start, &quote(word), true);
+            }
+        }
+    }
+}
+
+/// Quotes a bareword or a stray number lexeme as a JSON string.
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Fixes the number spellings other languages accept: a leading `+`,
+/// leading zeros, and a leading or trailing bare `.`.
+fn normalize_number(raw: &str) -> Option<String> {
+    let (sign, digits) = match raw.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", raw.strip_prefix('+').unwrap_or(raw)),
+    };
+    if !digits.bytes().any(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let mut fixed = digits.trim_start_matches('0').to_string();
+    if fixed.is_empty() || fixed.starts_with(['.', 'e', 'E']) {
+        fixed.insert(0, '0');
+    }
+    if let Some(stripped) = fixed.strip_suffix('.') {
+        fixed = stripped.to_string();
+    }
+    fixed = fixed.replace(".e", ".0e").replace(".E", ".0E");
+    let fixed = format!("{}{}", sign, fixed);
+    is_number(&fixed).then_some(fixed)
+}
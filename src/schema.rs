@@ -0,0 +1,555 @@
+//! A JSON Schema subset, for checking documents before they are sent.
+//!
+//! Schemas are checked once, when they are created, so an unsupported
+//! keyword fails loudly instead of being ignored; validation then walks the
+//! document and the schema together. References must be local (`#/...`).
+
+use ext_php_rs::boxed::ZBox;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ZendCallable, ZendHashTable, Zval};
+use ext_php_rs::zend::ce;
+use serde_json::{Map, Value};
+
+use crate::error::JsonError;
+use crate::{flags, pointer, EncodeConfig, JsonEncoder};
+
+/// Keywords that carry no assertion and are accepted as they are.
+const ANNOTATIONS: &[&str] = &[
+    "$schema",
+    "$id",
+    "$anchor",
+    "$comment",
+    "title",
+    "description",
+    "default",
+    "examples",
+    "deprecated",
+    "readOnly",
+    "writeOnly",
+    "format",
+    "contentMediaType",
+    "contentEncoding",
+];
+
+const TYPES: &[&str] = &["null", "boolean", "object", "array", "number", "integer", "string"];
+
+/// `$ref` hops allowed without descending into the document, which is
+/// where a reference cycle would otherwise loop forever.
+const MAX_REF_CHAIN: usize = 32;
+
+/// A place where a document breaks its schema.
+pub(crate) struct Violation {
+    /// JSON Pointer of the offending value.
+    pub path: String,
+    pub keyword: &'static str,
+    pub message: String,
+}
+
+/// A compiled-once schema for `Json::encodeValidated()` and `validate()`:
+///
+/// ```php
+/// $schema = new Schema(file_get_contents('order.schema.json'));
+/// $json = Json::encodeValidated($order, $schema);
+/// ```
+///
+/// Supports `type`, `enum`, `const`, the numeric, string, array and object
+/// size keywords, `pattern`, `properties`, `patternProperties`,
+/// `additionalProperties`, `propertyNames`, `required`, `items`,
+/// `prefixItems`, `contains`, `uniqueItems`, `allOf`, `anyOf`, `oneOf`,
+/// `not`, `if`/`then`/`else` and local `$ref`s into `$defs`.
+#[php_class]
+#[php(name = "Elephant\\Json\\Schema")]
+#[derive(Clone)]
+pub struct Schema {
+    root: Value,
+}
+
+#[php_impl]
+impl Schema {
+    /// `$schema` is the schema as JSON text, or already decoded into arrays
+    /// or objects.
+    pub fn __construct(schema: &Zval) -> PhpResult<Self> {
+        let json = match schema.str() {
+            Some(json) if schema.is_string() => json.to_string(),
+            _ => JsonEncoder::new(EncodeConfig::from_flags(Some(0), None)).encode(schema)?,
+        };
+        let invalid = |message: String| {
+            PhpException::new(
+                format!("Argument #1 ($schema) is not a valid schema: {}", message),
+                0,
+                ce::value_error(),
+            )
+        };
+        let root: Value = serde_json::from_str(&json).map_err(|err| invalid(err.to_string()))?;
+        check_schema(&root, &root).map_err(|err| invalid(err.to_string()))?;
+        Ok(Self { root })
+    }
+
+    /// Checks the JSON document `$json` and returns every violation as a
+    /// `['path', 'keyword', 'message']` entry; empty when it conforms.
+    pub fn validate(&self, json: &str) -> PhpResult<ZBox<ZendHashTable>> {
+        let mut report = ZendHashTable::new();
+        for violation in self.violations(json)? {
+            let mut entry = ZendHashTable::new();
+            entry.insert("path", violation.path)?;
+            entry.insert("keyword", violation.keyword)?;
+            entry.insert("message", violation.message)?;
+            report.push(entry)?;
+        }
+        Ok(report)
+    }
+}
+
+impl Schema {
+    pub(crate) fn violations(&self, json: &str) -> Result<Vec<Violation>, JsonError> {
+        let document: Value = serde_json::from_str(json).map_err(JsonError::syntax)?;
+        let mut validator = Validator {
+            root: &self.root,
+            violations: Vec::new(),
+            refs: 0,
+        };
+        validator.validate(&self.root, &document, "")?;
+        Ok(validator.violations)
+    }
+
+    /// Fails with the first violation, located at its path.
+    pub(crate) fn enforce(&self, json: &str) -> Result<(), JsonError> {
+        match self.violations(json)?.into_iter().next() {
+            None => Ok(()),
+            Some(violation) => {
+                let mut err = JsonError::new(
+                    flags::ERROR_SCHEMA_VIOLATION,
+                    format!("Schema violation ({}): {}", violation.keyword, violation.message),
+                );
+                err.path = violation.path;
+                Err(err)
+            }
+        }
+    }
+}
+
+fn schema_error(message: impl Into<String>) -> JsonError {
+    JsonError::new(flags::ERROR_SCHEMA_VIOLATION, message)
+}
+
+/// Rejects keywords outside the supported subset and malformed values, so
+/// validation can rely on the schema's shape.
+fn check_schema(schema: &Value, root: &Value) -> Result<(), JsonError> {
+    let map = match schema {
+        Value::Bool(_) => return Ok(()),
+        Value::Object(map) => map,
+        _ => return Err(schema_error("A schema must be an object or a boolean")),
+    };
+    for (keyword, value) in map {
+        check_keyword(keyword, value, root).map_err(|err| err.at(keyword))?;
+    }
+    Ok(())
+}
+
+fn check_keyword(keyword: &str, value: &Value, root: &Value) -> Result<(), JsonError> {
+    let expected = |what: &str| Err(schema_error(format!("\"{}\" must be {}", keyword, what)));
+    match keyword {
+        _ if ANNOTATIONS.contains(&keyword) => Ok(()),
+        "$defs" | "definitions" | "properties" | "patternProperties" => match entries(value) {
+            Some(entries) => entries
+                .iter()
+                .try_for_each(|(name, schema)| check_schema(schema, root).map_err(|err| err.at(name))),
+            None => expected("an object of schemas"),
+        },
+        "additionalProperties" | "propertyNames" | "items" | "contains" | "not" | "if" | "then" | "else" => {
+            check_schema(value, root)
+        }
+        "prefixItems" | "allOf" | "anyOf" | "oneOf" => match value.as_array() {
+            Some(schemas) if !schemas.is_empty() => schemas
+                .iter()
+                .enumerate()
+                .try_for_each(|(i, schema)| check_schema(schema, root).map_err(|err| err.at(&i.to_string()))),
+            _ => expected("a non-empty array of schemas"),
+        },
+        "type" => {
+            let names: Vec<&Value> = match value {
+                Value::Array(names) => names.iter().collect(),
+                name => vec![name],
+            };
+            match names.iter().all(|name| name.as_str().is_some_and(|name| TYPES.contains(&name))) {
+                true => Ok(()),
+                false => expected("a type name or an array of them"),
+            }
+        }
+        "enum" if value.is_array() => Ok(()),
+        "enum" => expected("an array"),
+        "const" => Ok(()),
+        "required" => match value.as_array() {
+            Some(names) if names.iter().all(Value::is_string) => Ok(()),
+            _ => expected("an array of strings"),
+        },
+        "minLength" | "maxLength" | "minItems" | "maxItems" | "minProperties" | "maxProperties" => {
+            match value.as_u64() {
+                Some(_) => Ok(()),
+                None => expected("a non-negative integer"),
+            }
+        }
+        "minimum" | "maximum" | "exclusiveMinimum" | "exclusiveMaximum" if value.is_number() => Ok(()),
+        "minimum" | "maximum" | "exclusiveMinimum" | "exclusiveMaximum" => expected("a number"),
+        "multipleOf" => match value.as_f64() {
+            Some(m) if m > 0.0 => Ok(()),
+            _ => expected("a number greater than 0"),
+        },
+        "uniqueItems" if value.is_boolean() => Ok(()),
+        "uniqueItems" => expected("a boolean"),
+        "pattern" if value.is_string() => Ok(()),
+        "pattern" => expected("a string"),
+        "$ref" => match value.as_str() {
+            Some(reference) if resolve(root, reference).is_some() => Ok(()),
+            Some(reference) if reference.starts_with('#') => {
+                Err(schema_error(format!("\"$ref\" \"{}\" does not resolve", reference)))
+            }
+            Some(_) => Err(schema_error("Only local \"$ref\"s, starting with '#', are supported")),
+            None => expected("a string"),
+        },
+        _ => Err(schema_error(format!("Unsupported keyword \"{}\"", keyword))),
+    }
+}
+
+/// The members of a keyword holding named schemas. PHP encodes an empty
+/// array as `[]`, which is taken as an empty object here.
+fn entries(value: &Value) -> Option<Vec<(&String, &Value)>> {
+    match value {
+        Value::Object(map) => Some(map.iter().collect()),
+        Value::Array(items) if items.is_empty() => Some(Vec::new()),
+        _ => None,
+    }
+}
+
+fn resolve<'v>(root: &'v Value, reference: &str) -> Option<&'v Value> {
+    root.pointer(reference.strip_prefix('#')?)
+}
+
+struct Validator<'s> {
+    root: &'s Value,
+    violations: Vec<Violation>,
+    /// Consecutive `$ref`s followed at the current document location.
+    refs: usize,
+}
+
+impl<'s> Validator<'s> {
+    fn fail(&mut self, path: &str, keyword: &'static str, message: String) {
+        self.violations.push(Violation {
+            path: path.to_string(),
+            keyword,
+            message,
+        });
+    }
+
+    /// Whether `value` matches `schema`, without recording why not.
+    fn matches(&mut self, schema: &'s Value, value: &Value, path: &str) -> Result<bool, JsonError> {
+        let outer = std::mem::take(&mut self.violations);
+        let result = self.validate(schema, value, path);
+        let matched = self.violations.is_empty();
+        self.violations = outer;
+        result.map(|_| matched)
+    }
+
+    fn validate(&mut self, schema: &'s Value, value: &Value, path: &str) -> Result<(), JsonError> {
+        let map = match schema {
+            Value::Bool(true) => return Ok(()),
+            Value::Bool(false) => {
+                self.fail(path, "false", "No value is allowed here".to_string());
+                return Ok(());
+            }
+            Value::Object(map) => map,
+            _ => return Ok(()),
+        };
+
+        if let Some(reference) = map.get("$ref").and_then(Value::as_str) {
+            self.refs += 1;
+            if self.refs > MAX_REF_CHAIN {
+                return Err(schema_error(format!("\"$ref\" \"{}\" loops without end", reference)));
+            }
+            let target = resolve(self.root, reference).unwrap_or(&Value::Bool(true));
+            let result = self.validate(target, value, path);
+            self.refs -= 1;
+            result?;
+        }
+
+        self.validate_generic(map, value, path)?;
+        match value {
+            Value::Number(_) => self.validate_number(map, value, path),
+            Value::String(s) => self.validate_string(map, s, path)?,
+            Value::Array(items) => self.validate_array(map, items, path)?,
+            Value::Object(members) => self.validate_object(map, members, path)?,
+            Value::Null | Value::Bool(_) => {}
+        }
+        self.validate_combinators(map, value, path)
+    }
+
+    fn validate_generic(&mut self, map: &Map<String, Value>, value: &Value, path: &str) -> Result<(), JsonError> {
+        if let Some(types) = map.get("type") {
+            let allowed: Vec<&str> = match types {
+                Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+                name => name.as_str().into_iter().collect(),
+            };
+            let actual = type_of(value);
+            let ok = allowed
+                .iter()
+                .any(|name| *name == actual || (*name == "number" && actual == "integer"));
+            if !ok {
+                self.fail(path, "type", format!("Expected {}, got {}", allowed.join(" or "), actual));
+            }
+        }
+        let options = map.get("enum").and_then(Value::as_array);
+        if options.is_some_and(|options| !options.iter().any(|option| equal(option, value))) {
+            self.fail(path, "enum", "Value is not one of the allowed values".to_string());
+        }
+        if let Some(expected) = map.get("const").filter(|expected| !equal(expected, value)) {
+            self.fail(path, "const", format!("Expected {}", expected));
+        }
+        Ok(())
+    }
+
+    fn validate_number(&mut self, map: &Map<String, Value>, value: &Value, path: &str) {
+        let n = value.as_f64().unwrap_or(f64::NAN);
+        let bound = |keyword: &str| map.get(keyword).and_then(Value::as_f64);
+        type Check = (&'static str, fn(f64, f64) -> bool, &'static str);
+        let checks: [Check; 4] = [
+            ("minimum", |n, m| n >= m, "at least"),
+            ("maximum", |n, m| n <= m, "at most"),
+            ("exclusiveMinimum", |n, m| n > m, "greater than"),
+            ("exclusiveMaximum", |n, m| n < m, "less than"),
+        ];
+        for (keyword, holds, wording) in checks {
+            if let Some(limit) = bound(keyword).filter(|limit| !holds(n, *limit)) {
+                self.fail(path, keyword, format!("{} is not {} {}", value, wording, limit));
+            }
+        }
+        if let Some(m) = bound("multipleOf") {
+            let quotient = n / m;
+            if (quotient - quotient.round()).abs() > 1e-9 * quotient.abs().max(1.0) {
+                self.fail(path, "multipleOf", format!("{} is not a multiple of {}", value, m));
+            }
+        }
+    }
+
+    fn validate_string(&mut self, map: &Map<String, Value>, s: &str, path: &str) -> Result<(), JsonError> {
+        let length = s.chars().count() as u64;
+        if let Some(min) = map.get("minLength").and_then(Value::as_u64).filter(|min| length < *min) {
+            self.fail(path, "minLength", format!("String of {} characters is shorter than {}", length, min));
+        }
+        if let Some(max) = map.get("maxLength").and_then(Value::as_u64).filter(|max| length > *max) {
+            self.fail(path, "maxLength", format!("String of {} characters is longer than {}", length, max));
+        }
+        match map.get("pattern").and_then(Value::as_str) {
+            Some(pattern) if !pattern_matches(pattern, s)? => {
+                self.fail(path, "pattern", format!("String does not match /{}/", pattern));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn validate_array(&mut self, map: &'s Map<String, Value>, items: &[Value], path: &str) -> Result<(), JsonError> {
+        let count = items.len() as u64;
+        if let Some(min) = map.get("minItems").and_then(Value::as_u64).filter(|min| count < *min) {
+            self.fail(path, "minItems", format!("Array of {} items has fewer than {}", count, min));
+        }
+        if let Some(max) = map.get("maxItems").and_then(Value::as_u64).filter(|max| count > *max) {
+            self.fail(path, "maxItems", format!("Array of {} items has more than {}", count, max));
+        }
+        if map.get("uniqueItems") == Some(&Value::Bool(true)) {
+            let duplicate = (1..items.len()).find(|&i| items[..i].iter().any(|item| equal(item, &items[i])));
+            if let Some(i) = duplicate {
+                self.fail(&child(path, &i.to_string()), "uniqueItems", "Item repeats an earlier one".to_string());
+            }
+        }
+
+        let prefix = map.get("prefixItems").and_then(Value::as_array).map_or(&[][..], Vec::as_slice);
+        for (i, item) in items.iter().enumerate() {
+            let schema = match prefix.get(i) {
+                Some(schema) => schema,
+                None => match map.get("items") {
+                    Some(schema) => schema,
+                    None => break,
+                },
+            };
+            self.descend(schema, item, &child(path, &i.to_string()))?;
+        }
+
+        if let Some(schema) = map.get("contains") {
+            let mut found = false;
+            for (i, item) in items.iter().enumerate() {
+                if self.matches(schema, item, &child(path, &i.to_string()))? {
+                    found = true;
+                    break;
+                }
+            }
+            if !found {
+                self.fail(path, "contains", "No item matches the \"contains\" schema".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_object(
+        &mut self,
+        map: &'s Map<String, Value>,
+        members: &Map<String, Value>,
+        path: &str,
+    ) -> Result<(), JsonError> {
+        let count = members.len() as u64;
+        if let Some(min) = map.get("minProperties").and_then(Value::as_u64).filter(|min| count < *min) {
+            self.fail(path, "minProperties", format!("Object of {} members has fewer than {}", count, min));
+        }
+        if let Some(max) = map.get("maxProperties").and_then(Value::as_u64).filter(|max| count > *max) {
+            self.fail(path, "maxProperties", format!("Object of {} members has more than {}", count, max));
+        }
+        if let Some(Value::Array(required)) = map.get("required") {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !members.contains_key(name) {
+                    self.fail(path, "required", format!("Missing required property \"{}\"", name));
+                }
+            }
+        }
+
+        let properties = map.get("properties").and_then(entries).unwrap_or_default();
+        let patterns = map.get("patternProperties").and_then(entries).unwrap_or_default();
+        for (name, member) in members {
+            let member_path = child(path, name);
+            match map.get("propertyNames") {
+                Some(schema) if !self.matches(schema, &Value::String(name.clone()), &member_path)? => {
+                    self.fail(&member_path, "propertyNames", format!("Property name \"{}\" is not allowed", name));
+                }
+                _ => {}
+            }
+
+            let mut evaluated = false;
+            if let Some((_, schema)) = properties.iter().find(|(key, _)| *key == name) {
+                evaluated = true;
+                self.descend(schema, member, &member_path)?;
+            }
+            for (pattern, schema) in &patterns {
+                if pattern_matches(pattern, name)? {
+                    evaluated = true;
+                    self.descend(schema, member, &member_path)?;
+                }
+            }
+            match map.get("additionalProperties") {
+                Some(Value::Bool(false)) if !evaluated => {
+                    self.fail(&member_path, "additionalProperties", format!("Property \"{}\" is not allowed", name));
+                }
+                Some(schema) if !evaluated => self.descend(schema, member, &member_path)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_combinators(&mut self, map: &'s Map<String, Value>, value: &Value, path: &str) -> Result<(), JsonError> {
+        let schemas = |keyword: &str| map.get(keyword).and_then(Value::as_array).map_or(&[][..], Vec::as_slice);
+
+        for schema in schemas("allOf") {
+            self.validate(schema, value, path)?;
+        }
+        if map.contains_key("anyOf") {
+            let mut any = false;
+            for schema in schemas("anyOf") {
+                if self.matches(schema, value, path)? {
+                    any = true;
+                    break;
+                }
+            }
+            if !any {
+                self.fail(path, "anyOf", "Value matches none of the \"anyOf\" schemas".to_string());
+            }
+        }
+        if map.contains_key("oneOf") {
+            let mut matched = 0;
+            for schema in schemas("oneOf") {
+                matched += self.matches(schema, value, path)? as usize;
+            }
+            if matched != 1 {
+                self.fail(path, "oneOf", format!("Value matches {} of the \"oneOf\" schemas instead of one", matched));
+            }
+        }
+        match map.get("not") {
+            Some(schema) if self.matches(schema, value, path)? => {
+                self.fail(path, "not", "Value matches the \"not\" schema".to_string());
+            }
+            _ => {}
+        }
+        if let Some(condition) = map.get("if") {
+            let branch = match self.matches(condition, value, path)? {
+                true => map.get("then"),
+                false => map.get("else"),
+            };
+            if let Some(schema) = branch {
+                self.validate(schema, value, path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Validates a child value, which starts a new `$ref` chain.
+    fn descend(&mut self, schema: &'s Value, value: &Value, path: &str) -> Result<(), JsonError> {
+        let refs = std::mem::replace(&mut self.refs, 0);
+        let result = self.validate(schema, value, path);
+        self.refs = refs;
+        result
+    }
+}
+
+fn child(path: &str, segment: &str) -> String {
+    format!("{}/{}", path, pointer::escape(segment))
+}
+
+/// The JSON Schema type name of `value`; integral numbers are `integer`.
+fn type_of(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(n) if n.as_f64().is_some_and(|f| f.fract() == 0.0) => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// JSON equality, under which `1` and `1.0` are the same number.
+pub(crate) fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => match (x.as_i64(), y.as_i64()) {
+            (Some(x), Some(y)) => x == y,
+            _ => x.as_f64() == y.as_f64(),
+        },
+        (Value::Array(x), Value::Array(y)) => x.len() == y.len() && x.iter().zip(y).all(|(x, y)| equal(x, y)),
+        (Value::Object(x), Value::Object(y)) => {
+            x.len() == y.len() && x.iter().all(|(key, x)| y.get(key).is_some_and(|y| equal(x, y)))
+        }
+        _ => a == b,
+    }
+}
+
+/// Runs a schema `pattern` through PCRE in UTF-8 mode. Patterns are not
+/// anchored, as the specification requires.
+fn pattern_matches(pattern: &str, subject: &str) -> Result<bool, JsonError> {
+    let mut regex = String::with_capacity(pattern.len() + 3);
+    regex.push('~');
+    let mut escaped = false;
+    for c in pattern.chars() {
+        if c == '~' && !escaped {
+            regex.push('\\');
+        }
+        escaped = c == '\\' && !escaped;
+        regex.push(c);
+    }
+    regex.push_str("~u");
+
+    let result = ZendCallable::try_from_name("preg_match")
+        .and_then(|preg_match| preg_match.try_call(vec![&regex, &subject]))
+        .map_err(JsonError::engine)?;
+    match result.long() {
+        Some(matched) => Ok(matched == 1),
+        None => Err(schema_error(format!("Invalid pattern /{}/", pattern))),
+    }
+}
@@ -0,0 +1,18 @@
+//! Sentinel an encode replacer returns to leave a member out.
+
+use ext_php_rs::prelude::*;
+
+/// Returned from a replacer, drops the object member being encoded. In a
+/// list, where leaving an element out would shift the others, `null` is
+/// written instead.
+#[php_class]
+#[php(name = "Elephant\\Json\\Skip")]
+#[derive(Default)]
+pub struct Skip;
+
+#[php_impl]
+impl Skip {
+    pub fn __construct() -> Self {
+        Self
+    }
+}
@@ -0,0 +1,549 @@
+//! Smile, the binary JSON format Jackson and Elasticsearch use, transcoded
+//! to and from JSON text like UBJSON.
+//!
+//! Repeated keys are written as back-references to the first occurrence,
+//! which is where most of the savings on record-shaped data come from.
+//! Integers take the smallest type that holds them, other numbers float64,
+//! integers beyond int64 `BigInteger` and numbers beyond float64
+//! `BigDecimal`. Shared string values and binary are read but not
+//! written; binary decodes to its base64 text.
+
+use std::collections::HashMap;
+
+use crate::base64url;
+use crate::error::JsonError;
+use crate::flags;
+use crate::number::float_lexeme;
+use crate::ion::to_decimal;
+use crate::reader::{string_content, Event, Reader};
+use crate::writer::{JsonWriter, WriterConfig};
+
+const HEADER: &[u8] = b":)\n";
+/// Header flags; the version in the high nibble is 0.
+const SHARED_NAMES: u8 = 0x01;
+const SHARED_VALUES: u8 = 0x02;
+/// Entries a shared name or value table holds before it starts over.
+const MAX_SHARED: usize = 1024;
+
+/// Converts a JSON document to Smile.
+pub(crate) fn from_json(json: &str) -> Result<Vec<u8>, JsonError> {
+    let mut out = Vec::with_capacity(json.len());
+    out.extend_from_slice(HEADER);
+    out.push(SHARED_NAMES);
+    let mut reader = Reader::new(json);
+    let mut names: HashMap<String, usize> = HashMap::new();
+    while let Some((_, event)) = reader.next_event().map_err(JsonError::read)? {
+        match event {
+            Event::BeginObject => out.push(0xFA),
+            Event::EndObject => out.push(0xFB),
+            Event::BeginArray => out.push(0xF8),
+            Event::EndArray => out.push(0xF9),
+            Event::Key(raw) => write_key(&mut out, string_content(raw).map_err(JsonError::syntax)?, &mut names),
+            Event::String(raw) => write_string(&mut out, &string_content(raw).map_err(JsonError::syntax)?),
+            Event::Number(raw) => write_number(&mut out, raw),
+            Event::Bool(true) => out.push(0x23),
+            Event::Bool(false) => out.push(0x22),
+            Event::Null => out.push(0x21),
+        }
+    }
+    Ok(out)
+}
+
+fn write_key(out: &mut Vec<u8>, name: String, names: &mut HashMap<String, usize>) {
+    if name.is_empty() {
+        out.push(0x20);
+        return;
+    }
+    if let Some(&index) = names.get(&name) {
+        match index {
+            0..=63 => out.push(0x40 + index as u8),
+            _ => out.extend_from_slice(&[0x30 + (index >> 8) as u8, index as u8]),
+        }
+        return;
+    }
+    let len = name.len();
+    let long = match (len, name.is_ascii()) {
+        (1..=64, true) => {
+            out.push(0x80 + (len - 1) as u8);
+            false
+        }
+        (2..=57, false) => {
+            out.push(0xC0 + (len - 2) as u8);
+            false
+        }
+        _ => {
+            out.push(0x34);
+            true
+        }
+    };
+    out.extend_from_slice(name.as_bytes());
+    if long {
+        out.push(0xFC);
+    }
+    if names.len() == MAX_SHARED {
+        names.clear();
+    }
+    let index = names.len();
+    names.insert(name, index);
+}
+
+fn write_string(out: &mut Vec<u8>, s: &str) {
+    let len = s.len();
+    match (len, s.is_ascii()) {
+        (0, _) => out.push(0x20),
+        (1..=32, true) => out.push(0x40 + (len - 1) as u8),
+        (33..=64, true) => out.push(0x60 + (len - 33) as u8),
+        (2..=33, false) => out.push(0x80 + (len - 2) as u8),
+        (34..=64, false) => out.push(0xA0 + (len - 34) as u8),
+        (_, ascii) => {
+            out.push(if ascii { 0xE0 } else { 0xE4 });
+            out.extend_from_slice(s.as_bytes());
+            out.push(0xFC);
+            return;
+        }
+    }
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_number(out: &mut Vec<u8>, raw: &str) {
+    if !raw.contains(['.', 'e', 'E']) {
+        match raw.parse::<i64>() {
+            Ok(n @ -16..=15) => out.push(0xC0 + zigzag(n) as u8),
+            Ok(n) if i32::try_from(n).is_ok() => {
+                out.push(0x24);
+                write_vint(out, zigzag(n));
+            }
+            Ok(n) => {
+                out.push(0x25);
+                write_vint(out, zigzag(n));
+            }
+            Err(_) => {
+                out.push(0x26);
+                write_int_bytes(out, raw);
+            }
+        }
+        return;
+    }
+    // Numbers that overflow float64 or underflow it to zero.
+    let (mantissa, exponent) = raw.split_once(['e', 'E']).unwrap_or((raw, "0"));
+    let zero = !mantissa.bytes().any(|b| matches!(b, b'1'..=b'9'));
+    if let Some(f) = raw.parse::<f64>().ok().filter(|f| f.is_finite() && (*f != 0.0 || zero)) {
+        out.push(0x29);
+        let bits = f.to_bits();
+        out.extend((0..10).rev().map(|i| (bits >> (7 * i)) as u8 & 0x7F));
+        return;
+    }
+    // Otherwise the digits and the power of ten to scale them by.
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let exponent = exponent.trim_start_matches('+').parse::<i64>().unwrap_or(0);
+    let scale = (fraction.len() as i64 - exponent).clamp(i32::MIN.into(), i32::MAX.into());
+    out.push(0x2A);
+    write_vint(out, zigzag(scale));
+    write_int_bytes(out, &format!("{}{}", whole, fraction));
+}
+
+fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+/// Writes `n` seven bits per byte, most significant first, ending in a byte
+/// with the high bit set that carries six.
+fn write_vint(out: &mut Vec<u8>, mut n: u64) {
+    let mut bytes = vec![0x80 | (n & 0x3F) as u8];
+    n >>= 6;
+    while n > 0 {
+        bytes.push((n & 0x7F) as u8);
+        n >>= 7;
+    }
+    out.extend(bytes.iter().rev());
+}
+
+/// Writes the integer `digits` as the length and 7-bit encoded two's
+/// complement bytes of a Java `BigInteger`.
+fn write_int_bytes(out: &mut Vec<u8>, digits: &str) {
+    let (negative, digits) = match digits.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, digits),
+    };
+    // Little-endian magnitude.
+    let mut bytes: Vec<u8> = vec![0];
+    for d in digits.bytes() {
+        let mut carry = u32::from(d - b'0');
+        for byte in &mut bytes {
+            let value = u32::from(*byte) * 10 + carry;
+            *byte = value as u8;
+            carry = value >> 8;
+        }
+        if carry > 0 {
+            bytes.push(carry as u8);
+        }
+    }
+    let negative = negative && bytes.iter().any(|b| *b != 0);
+    if negative {
+        let mut carry = true;
+        for byte in &mut bytes {
+            *byte = !*byte;
+            if carry {
+                *byte = byte.wrapping_add(1);
+                carry = *byte == 0;
+            }
+        }
+    }
+    if bytes.last().is_some_and(|b| b & 0x80 != 0) != negative {
+        bytes.push(if negative { 0xFF } else { 0 });
+    }
+    bytes.reverse();
+    write_vint(out, bytes.len() as u64);
+    write_7bit(out, &bytes);
+}
+
+/// Packs `data` into bytes of seven bits each: whole groups of seven bytes
+/// into eight, and a shorter tail into one byte more than it has, the last
+/// holding the leftover bits.
+fn write_7bit(out: &mut Vec<u8>, data: &[u8]) {
+    for chunk in data.chunks(7) {
+        let bits = chunk.iter().fold(0u64, |acc, &b| (acc << 8) | u64::from(b));
+        let rest = chunk.len() * 8 % 7;
+        for i in (0..chunk.len() * 8 / 7).rev() {
+            out.push((bits >> (rest + 7 * i)) as u8 & 0x7F);
+        }
+        if rest > 0 {
+            out.push((bits & ((1 << rest) - 1)) as u8);
+        }
+    }
+}
+
+/// Converts Smile to a JSON document of at most `max_len` bytes. Nested
+/// containers are tracked on the heap, as in UBJSON.
+pub(crate) fn to_json(bytes: &[u8], max_len: Option<usize>) -> Result<String, JsonError> {
+    let config = WriterConfig::compact();
+    let mut writer = JsonWriter::new(&config);
+    let mut input = Input {
+        bytes,
+        pos: 0,
+        names: Vec::new(),
+        values: Vec::new(),
+        shared_names: false,
+        shared_values: false,
+    };
+    input.header()?;
+    // Whether each open container is an object.
+    let mut stack: Vec<bool> = Vec::new();
+
+    loop {
+        if let Some(max) = max_len.filter(|max| writer.len() > *max) {
+            return Err(JsonError::new(
+                flags::ERROR_INPUT_TOO_LARGE,
+                format!("Smile expands to more than the maximum of {} bytes of JSON", max),
+            ));
+        }
+        if stack.last() == Some(&true) {
+            let at = input.pos;
+            let token = input.byte()?;
+            if token == 0xFB {
+                writer.end_object();
+                stack.pop();
+                if stack.is_empty() {
+                    break;
+                }
+                continue;
+            }
+            writer.key(&input.key(token, at)?);
+        }
+
+        let at = input.pos;
+        match input.byte()? {
+            0xF8 => {
+                writer.begin_array();
+                stack.push(false);
+                continue;
+            }
+            0xFA => {
+                writer.begin_object();
+                stack.push(true);
+                continue;
+            }
+            0xF9 if stack.last() == Some(&false) => {
+                writer.end_array();
+                stack.pop();
+            }
+            0x20 => writer.string(""),
+            0x21 => writer.null(),
+            0x22 => writer.bool(false),
+            0x23 => writer.bool(true),
+            0x24 => {
+                let n = input.zigzag(5)?;
+                if i32::try_from(n).is_err() {
+                    return Err(input.error_at(at, "int32 out of range"));
+                }
+                writer.number(&n.to_string());
+            }
+            0x25 => writer.number(&input.zigzag(10)?.to_string()),
+            0x26 => {
+                let len = input.length()?;
+                writer.number(&int_lexeme(&input.seven_bit(len)?));
+            }
+            0x28 => {
+                let f = f32::from_bits(input.packed(5)? as u32);
+                writer.number(&float_lexeme(f.is_finite().then(|| f.to_string()), "Smile", at)?);
+            }
+            0x29 => {
+                let f = f64::from_bits(input.packed(10)?);
+                let lexeme = serde_json::Number::from_f64(f).map(|n| n.to_string());
+                writer.number(&float_lexeme(lexeme, "Smile", at)?);
+            }
+            0x2A => {
+                let scale = input.zigzag(5)?;
+                let len = input.length()?;
+                writer.number(&decimal_lexeme(&int_lexeme(&input.seven_bit(len)?), scale));
+            }
+            token @ (0x40..=0xBF | 0xE0 | 0xE4) => {
+                let s = input.string(token, at)?;
+                writer.string(&s);
+            }
+            token @ (0x01..=0x1F | 0xEC..=0xEF) => {
+                let index = match token {
+                    0x01..=0x1F => usize::from(token - 1),
+                    _ => usize::from(token & 0x03) << 8 | usize::from(input.byte()?),
+                };
+                let s = input
+                    .values
+                    .get(index)
+                    .filter(|_| input.shared_values)
+                    .ok_or_else(|| input.error_at(at, "invalid shared string reference"))?;
+                writer.string(s);
+            }
+            token @ 0xC0..=0xDF => {
+                let n = i64::from(token & 0x1F);
+                writer.number(&((n >> 1) ^ -(n & 1)).to_string());
+            }
+            0xE8 => {
+                let len = input.length()?;
+                writer.string(&base64url::encode_standard(&input.seven_bit(len)?));
+            }
+            0xFD => {
+                let len = input.length()?;
+                writer.string(&base64url::encode_standard(input.take(len)?));
+            }
+            _ => return Err(input.error_at(at, "unexpected token")),
+        }
+        if stack.is_empty() {
+            break;
+        }
+    }
+
+    if input.bytes.get(input.pos) == Some(&0xFF) {
+        input.pos += 1;
+    }
+    if input.pos < bytes.len() {
+        return Err(input.error_at(input.pos, "trailing data"));
+    }
+    Ok(writer.finish())
+}
+
+/// The decimal digits of two's complement big-endian `bytes`.
+fn int_lexeme(bytes: &[u8]) -> String {
+    let negative = bytes.first().is_some_and(|b| b & 0x80 != 0);
+    let mut magnitude = bytes.to_vec();
+    if negative {
+        for byte in &mut magnitude {
+            *byte = !*byte;
+        }
+        for byte in magnitude.iter_mut().rev() {
+            *byte = byte.wrapping_add(1);
+            if *byte != 0 {
+                break;
+            }
+        }
+    }
+    let hex: String = magnitude.iter().map(|b| format!("{:02x}", b)).collect();
+    let digits = to_decimal(&hex, 16).unwrap_or_default();
+    match negative {
+        true => format!("-{}", digits),
+        false => digits,
+    }
+}
+
+/// A number lexeme for `unscaled` × 10^-`scale`, which decodes as a float.
+fn decimal_lexeme(unscaled: &str, scale: i64) -> String {
+    let (sign, digits) = match unscaled.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", unscaled),
+    };
+    match usize::try_from(scale) {
+        Ok(scale) if scale > 0 && scale < digits.len() => {
+            let (whole, fraction) = digits.split_at(digits.len() - scale);
+            format!("{}{}.{}", sign, whole, fraction)
+        }
+        _ => format!("{}{}e{}", sign, digits, -scale),
+    }
+}
+
+struct Input<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    names: Vec<String>,
+    values: Vec<String>,
+    shared_names: bool,
+    shared_values: bool,
+}
+
+impl<'a> Input<'a> {
+    fn error_at(&self, pos: usize, detail: &str) -> JsonError {
+        JsonError::new(flags::ERROR_SYNTAX, format!("Malformed Smile at byte {}: {}", pos, detail))
+    }
+
+    fn header(&mut self) -> Result<(), JsonError> {
+        if !self.bytes.starts_with(HEADER) {
+            return Err(self.error_at(0, "missing header"));
+        }
+        self.pos = HEADER.len();
+        let flags = self.byte()?;
+        if flags >> 4 != 0 {
+            return Err(self.error_at(3, "unsupported version"));
+        }
+        self.shared_names = flags & SHARED_NAMES != 0;
+        self.shared_values = flags & SHARED_VALUES != 0;
+        Ok(())
+    }
+
+    fn byte(&mut self) -> Result<u8, JsonError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], JsonError> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos.saturating_add(len))
+            .ok_or_else(|| self.error_at(self.bytes.len(), "unexpected end of input"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// A variable-length unsigned integer of at most `max_bytes` bytes.
+    fn vint(&mut self, max_bytes: usize) -> Result<u64, JsonError> {
+        let at = self.pos;
+        let mut n: u64 = 0;
+        for _ in 0..max_bytes {
+            let b = self.byte()?;
+            if b & 0x80 != 0 {
+                return n
+                    .checked_mul(64)
+                    .map(|n| n | u64::from(b & 0x3F))
+                    .ok_or_else(|| self.error_at(at, "integer out of range"));
+            }
+            n = n.checked_mul(128).ok_or_else(|| self.error_at(at, "integer out of range"))? | u64::from(b);
+        }
+        Err(self.error_at(at, "integer too long"))
+    }
+
+    fn zigzag(&mut self, max_bytes: usize) -> Result<i64, JsonError> {
+        let n = self.vint(max_bytes)?;
+        Ok((n >> 1) as i64 ^ -((n & 1) as i64))
+    }
+
+    fn length(&mut self) -> Result<usize, JsonError> {
+        let at = self.pos;
+        usize::try_from(self.vint(10)?).map_err(|_| self.error_at(at, "length out of range"))
+    }
+
+    /// `len` bytes of seven bits each, most significant first.
+    fn packed(&mut self, len: usize) -> Result<u64, JsonError> {
+        Ok(self.take(len)?.iter().fold(0, |acc, &b| (acc << 7) | u64::from(b & 0x7F)))
+    }
+
+    /// `len` bytes of data packed seven bits per byte, see [`write_7bit`].
+    fn seven_bit(&mut self, len: usize) -> Result<Vec<u8>, JsonError> {
+        let mut out = Vec::with_capacity(len.min(self.bytes.len()));
+        let mut left = len;
+        while left > 0 {
+            let n = left.min(7);
+            let groups = n * 8 / 7;
+            let rest = n * 8 % 7;
+            let encoded = self.take(groups + usize::from(rest > 0))?;
+            let mut bits = encoded[..groups].iter().fold(0u64, |acc, &b| (acc << 7) | u64::from(b & 0x7F));
+            if rest > 0 {
+                bits = (bits << rest) | (u64::from(encoded[groups]) & ((1 << rest) - 1));
+            }
+            out.extend((0..n).rev().map(|i| (bits >> (8 * i)) as u8));
+            left -= n;
+        }
+        Ok(out)
+    }
+
+    fn utf8(&self, bytes: &[u8], at: usize) -> Result<String, JsonError> {
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| JsonError::new(flags::ERROR_UTF8, format!("Malformed UTF-8 in Smile string at byte {}", at)))
+    }
+
+    /// Text up to the `0xFC` end marker.
+    fn terminated(&mut self, at: usize) -> Result<String, JsonError> {
+        let rest = &self.bytes[self.pos..];
+        let len = rest
+            .iter()
+            .position(|&b| b == 0xFC)
+            .ok_or_else(|| self.error_at(at, "unterminated string"))?;
+        let s = self.utf8(&rest[..len], at)?;
+        self.pos += len + 1;
+        Ok(s)
+    }
+
+    /// A string value. Short ones are remembered for back-references when
+    /// the header enables them.
+    fn string(&mut self, token: u8, at: usize) -> Result<String, JsonError> {
+        let len = match token {
+            0xE0 | 0xE4 => return self.terminated(at),
+            0x40..=0x5F => (token & 0x1F) as usize + 1,
+            0x60..=0x7F => (token & 0x1F) as usize + 33,
+            0x80..=0x9F => (token & 0x1F) as usize + 2,
+            _ => (token & 0x1F) as usize + 34,
+        };
+        let bytes = self.take(len)?;
+        let s = self.utf8(bytes, at)?;
+        if self.shared_values {
+            remember(&mut self.values, &s);
+        }
+        Ok(s)
+    }
+
+    /// An object key, literal or a back-reference to an earlier one.
+    fn key(&mut self, token: u8, at: usize) -> Result<String, JsonError> {
+        let name = match token {
+            0x20 => return Ok(String::new()),
+            0x30..=0x33 | 0x40..=0x7F => {
+                let index = match token {
+                    0x40..=0x7F => usize::from(token & 0x3F),
+                    _ => usize::from(token & 0x03) << 8 | usize::from(self.byte()?),
+                };
+                return self
+                    .names
+                    .get(index)
+                    .filter(|_| self.shared_names)
+                    .cloned()
+                    .ok_or_else(|| self.error_at(at, "invalid shared key reference"));
+            }
+            0x34 => self.terminated(at)?,
+            0x80..=0xBF => {
+                let bytes = self.take((token & 0x3F) as usize + 1)?;
+                self.utf8(bytes, at)?
+            }
+            0xC0..=0xF7 => {
+                let bytes = self.take((token & 0x3F) as usize + 2)?;
+                self.utf8(bytes, at)?
+            }
+            _ => return Err(self.error_at(at, "expected object key")),
+        };
+        if self.shared_names {
+            remember(&mut self.names, &name);
+        }
+        Ok(name)
+    }
+}
+
+/// Adds `s` to a shared string table, which starts over once full.
+fn remember(table: &mut Vec<String>, s: &str) {
+    if table.len() == MAX_SHARED {
+        table.clear();
+    }
+    table.push(s.to_string());
+}
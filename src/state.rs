@@ -0,0 +1,48 @@
+//! Typed accessors for the per-request values held in [`crate::globals`].
+
+use crate::error::JsonError;
+use crate::globals;
+use crate::options::{DecodeDefaults, EncoderOptions};
+
+/// Records the outcome of the latest non-throwing `json_*` call.
+pub(crate) fn set_last_error(error: Option<JsonError>) {
+    globals::with(|g| g.last_error = error);
+}
+
+pub(crate) fn last_error_code() -> i64 {
+    globals::with(|g| g.last_error.as_ref().map_or(0, |e| e.code))
+}
+
+pub(crate) fn set_encode_defaults(options: Option<EncoderOptions>) {
+    globals::replace(|g| &mut g.encode_defaults, options);
+}
+
+/// Options set with `Json::setEncodeDefaults()` for the current request.
+pub(crate) fn encode_defaults() -> Option<EncoderOptions> {
+    globals::with(|g| g.encode_defaults.clone())
+}
+
+pub(crate) fn set_decode_defaults(options: Option<DecodeDefaults>) {
+    globals::replace(|g| &mut g.decode_defaults, options);
+}
+
+/// Options set with `Json::setDecodeDefaults()` for the current request.
+pub(crate) fn decode_defaults() -> Option<DecodeDefaults> {
+    globals::with(|g| g.decode_defaults.clone())
+}
+
+pub(crate) extern "C" fn request_startup(_ty: i32, _module_number: i32) -> i32 {
+    set_last_error(None);
+    0
+}
+
+/// Drops per-request defaults, adapters, hydration plans and the metrics
+/// hook so they never leak into the next request served by the same worker.
+pub(crate) extern "C" fn request_shutdown(_ty: i32, _module_number: i32) -> i32 {
+    set_encode_defaults(None);
+    set_decode_defaults(None);
+    crate::adapters::reset();
+    crate::hydrate::reset();
+    crate::metrics::set_hook(None);
+    0
+}
@@ -0,0 +1,145 @@
+//! Byte I/O on PHP stream resources and PSR-7 `StreamInterface` objects.
+//!
+//! Streams are read to the end before decoding and written in one piece
+//! after encoding, except for NDJSON, which goes a chunk or a line at a
+//! time; PSR-7 objects are duck-typed by their `read()`, `eof()`,
+//! `write()`, `tell()` and `seek()` methods.
+
+use ext_php_rs::boxed::ZBox;
+use ext_php_rs::convert::{IntoZval, IntoZvalDyn};
+use ext_php_rs::error::Error;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ZendCallable, ZendObject, ZendStr, Zval};
+use ext_php_rs::zend::{ce, ExecutorGlobals};
+
+const READ_CHUNK: i64 = 65536;
+
+/// Reads everything left in `stream`, argument `position` of the caller.
+pub(crate) fn read_all(stream: &Zval, position: u8) -> PhpResult<Vec<u8>> {
+    if stream.is_resource() {
+        let contents = call("stream_get_contents", vec![stream])?;
+        return contents
+            .binary::<u8>()
+            .ok_or_else(|| "Failed to read from the stream".into());
+    }
+
+    let Some(object) = stream.object() else {
+        return Err(type_error(stream, position));
+    };
+    let mut out = Vec::new();
+    loop {
+        let eof = call_method(stream, object, "eof", vec![], position)?;
+        if eof.bool().unwrap_or(true) {
+            break;
+        }
+        let chunk = call_method(stream, object, "read", vec![&READ_CHUNK], position)?;
+        match chunk.binary::<u8>() {
+            Some(bytes) if !bytes.is_empty() => out.extend(bytes),
+            _ => break,
+        }
+    }
+    Ok(out)
+}
+
+/// Reads up to `READ_CHUNK` bytes from `stream`; `None` at the end.
+pub(crate) fn read_chunk(stream: &Zval, position: u8) -> PhpResult<Option<Vec<u8>>> {
+    let chunk = if stream.is_resource() {
+        call("fread", vec![stream, &READ_CHUNK])?
+    } else if let Some(object) = stream.object() {
+        let eof = call_method(stream, object, "eof", vec![], position)?;
+        if eof.bool().unwrap_or(true) {
+            return Ok(None);
+        }
+        call_method(stream, object, "read", vec![&READ_CHUNK], position)?
+    } else {
+        return Err(type_error(stream, position));
+    };
+    Ok(chunk.binary::<u8>().filter(|bytes| !bytes.is_empty()))
+}
+
+/// Writes `data` to `stream`, argument `position` of the caller, writing
+/// the rest again after a short write.
+pub(crate) fn write_all(stream: &Zval, data: &str, position: u8) -> PhpResult<()> {
+    let mut rest = data.as_bytes();
+    while !rest.is_empty() {
+        let mut chunk = Zval::new();
+        chunk.set_zend_string(ZendStr::new(rest, false));
+        let written = if stream.is_resource() {
+            call("fwrite", vec![stream, &chunk])?
+        } else if let Some(object) = stream.object() {
+            call_method(stream, object, "write", vec![&chunk], position)?
+        } else {
+            return Err(type_error(stream, position));
+        };
+
+        // Nothing written at all would repeat forever.
+        match written.long().and_then(|n| usize::try_from(n).ok()) {
+            Some(n) if n > 0 && n <= rest.len() => rest = &rest[n..],
+            _ => return Err("Failed to write to the stream".into()),
+        }
+    }
+    Ok(())
+}
+
+/// The stream's read position, if it reports one.
+pub(crate) fn tell(stream: &Zval) -> PhpResult<Option<i64>> {
+    if stream.is_resource() {
+        return Ok(call("ftell", vec![stream])?.long());
+    }
+    Ok(stream.object().and_then(|object| object.try_call_method("tell", vec![]).ok()?.long()))
+}
+
+/// Moves the read position to `offset`; false if the stream cannot seek.
+pub(crate) fn seek(stream: &Zval, offset: i64) -> PhpResult<bool> {
+    if stream.is_resource() {
+        return Ok(call("fseek", vec![stream, &offset])?.long() == Some(0));
+    }
+    Ok(stream
+        .object()
+        .is_some_and(|object| object.try_call_method("seek", vec![&offset]).is_ok()))
+}
+
+pub(crate) fn call(name: &str, args: Vec<&dyn IntoZvalDyn>) -> PhpResult<Zval> {
+    let function = ZendCallable::try_from_name(name)?;
+    function.try_call(args).map_err(|err| match err {
+        Error::Exception(exception) => rethrow(exception),
+        err => err.into(),
+    })
+}
+
+/// Calls `method` on a PSR-7 stream object. Only a missing method means
+/// `stream` is not one; whatever the method throws propagates as it is.
+fn call_method(
+    stream: &Zval,
+    object: &ZendObject,
+    method: &str,
+    args: Vec<&dyn IntoZvalDyn>,
+    position: u8,
+) -> PhpResult<Zval> {
+    let result = object.try_call_method(method, args).map_err(|err| match err {
+        Error::Callable => type_error(stream, position),
+        err => err.into(),
+    })?;
+    match ExecutorGlobals::take_exception() {
+        Some(exception) => Err(rethrow(exception)),
+        None => Ok(result),
+    }
+}
+
+/// Throws `exception` again once the call returns, in place of a new one.
+fn rethrow(exception: ZBox<ZendObject>) -> PhpException {
+    let exception = exception.into_zval(false).unwrap_or_default();
+    PhpException::default(String::new()).with_object(exception)
+}
+
+fn type_error(stream: &Zval, position: u8) -> PhpException {
+    PhpException::new(
+        format!(
+            "Argument #{} ($stream) must be a stream resource or a Psr\\Http\\Message\\StreamInterface, {} given",
+            position,
+            stream.get_type()
+        ),
+        0,
+        ce::type_error(),
+    )
+}
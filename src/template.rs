@@ -0,0 +1,151 @@
+//! Record shapes for encoding long lists of same-shaped rows, see
+//! `Template::forKeys()`.
+
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ArrayKey, ZendHashTable, Zval};
+use ext_php_rs::zend::ce;
+
+use crate::error::JsonError;
+use crate::escape::escape_into;
+use crate::options::EncoderOptions;
+use crate::writer::JsonWriter;
+use crate::{callback, is_list, EncodeConfig, JsonEncoder};
+
+/// The keys of a record, in order, for encoding lists of rows that all
+/// have them:
+///
+/// ```php
+/// $template = Template::forKeys(['id', 'name', 'price']);
+/// $json = $template->encode($rows);
+/// ```
+///
+/// The keys are escaped once per call rather than once per row. Rows with
+/// other keys, or the same keys in another order, are encoded as
+/// `encode()` would, so the output does not depend on the template.
+#[php_class]
+#[php(name = "Elephant\\Json\\Template")]
+pub struct Template {
+    keys: Vec<String>,
+}
+
+#[php_impl]
+impl Template {
+    pub fn for_keys(keys: Vec<String>) -> PhpResult<Self> {
+        for (i, key) in keys.iter().enumerate() {
+            if keys[..i].contains(key) {
+                return Err(PhpException::new(
+                    format!("Duplicate template key \"{}\"", key),
+                    0,
+                    ce::value_error(),
+                ));
+            }
+        }
+        Ok(Self { keys })
+    }
+
+    /// The keys of the first row of `$rows`.
+    pub fn infer(rows: &ZendHashTable) -> PhpResult<Self> {
+        let first = rows.values().next().and_then(Zval::array).ok_or_else(|| {
+            PhpException::new("The first row must be an array".to_string(), 0, ce::value_error())
+        })?;
+        Ok(Self {
+            keys: first.iter().map(|(key, _)| key.to_string()).collect(),
+        })
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        self.keys.clone()
+    }
+
+    /// Encodes the list `$rows`, with the same options and replacer as
+    /// `encode()`. Any other value, and any value with a replacer, is
+    /// encoded as `encode()` would.
+    #[php(defaults(options = None, depth = None, replacer = None))]
+    pub fn encode(
+        &self,
+        rows: &Zval,
+        options: Option<&Zval>,
+        depth: Option<i64>,
+        replacer: Option<&Zval>,
+    ) -> PhpResult<String> {
+        let options = EncoderOptions::resolve(options, 2)?;
+        let mut config = EncodeConfig::new(&options, depth);
+        config.replacer = callback(replacer, 4, "replacer")?;
+        Ok(self.encode_rows(&JsonEncoder::new(config), rows)?)
+    }
+}
+
+impl Template {
+    fn encode_rows(&self, encoder: &JsonEncoder, rows: &Zval) -> Result<String, JsonError> {
+        let config = &encoder.config;
+        let force_object = config.native.is_some_and(|native| native.force_object);
+        let Some(table) = rows
+            .array()
+            .filter(|table| !table.is_empty() && !force_object && config.replacer.is_none() && is_list(table))
+        else {
+            return encoder.encode(rows);
+        };
+        if config.max_depth < 1 {
+            return Err(JsonError::depth());
+        }
+
+        let writer_config = config.writer_config();
+        let quoted: Vec<String> = self
+            .keys
+            .iter()
+            .map(|key| {
+                let mut quoted = String::with_capacity(key.len() + 2);
+                escape_into(&mut quoted, key, &config.escaping);
+                quoted
+            })
+            .collect();
+        // Positions in the template, in the order members are written.
+        let mut order: Vec<usize> = (0..self.keys.len()).collect();
+        if config.sort_keys {
+            order.sort_by(|&a, &b| self.keys[a].cmp(&self.keys[b]));
+        }
+
+        let mut writer = JsonWriter::new(&writer_config);
+        writer.begin_array_of(encoder.shape(table));
+        let mut values: Vec<&Zval> = Vec::with_capacity(self.keys.len());
+        for (i, (_, row)) in table.iter().enumerate() {
+            let Some(members) = row.array().filter(|members| self.fits(members)) else {
+                encoder.write(&mut writer, row, 1).map_err(|err| err.at(&i.to_string()))?;
+                continue;
+            };
+            values.clear();
+            values.extend(members.values());
+            writer.begin_object();
+            for &k in &order {
+                let value = values[k];
+                if config.skip_nulls && value.is_null() {
+                    continue;
+                }
+                writer.escaped_key(&quoted[k]);
+                encoder
+                    .write(&mut writer, value, 2)
+                    .map_err(|err| err.at(&self.keys[k]).at(&i.to_string()))?;
+            }
+            writer.end_object();
+        }
+        writer.end_array();
+
+        let mut json = writer.finish();
+        if config.trailing_newline {
+            json.push('\n');
+        }
+        Ok(json)
+    }
+
+    /// Whether `row` has exactly the template's keys, in its order, and so
+    /// encodes as an object with them.
+    fn fits(&self, row: &ZendHashTable) -> bool {
+        row.len() == self.keys.len()
+            && !is_list(row)
+            && row.iter().zip(&self.keys).all(|((key, _), name)| match key {
+                ArrayKey::Long(n) => n.to_string() == *name,
+                ArrayKey::String(key) => key == *name,
+                ArrayKey::Str(key) => key == name,
+            })
+    }
+}
@@ -0,0 +1,125 @@
+//! Lexical access to a document for linters, formatters and highlighters.
+
+use ext_php_rs::boxed::ZBox;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ZendHashTable, Zval};
+use ext_php_rs::zend::ce;
+
+use crate::reader::{Event, ReadError, Reader};
+
+struct Token {
+    kind: &'static str,
+    start: usize,
+    end: usize,
+    /// Nesting level the token sits at; brackets count as outside the
+    /// container they delimit.
+    depth: usize,
+}
+
+/// Splits a document into its tokens, punctuation included. Whitespace is
+/// not reported; it is whatever lies between two tokens.
+fn tokenize(json: &str) -> Result<Vec<Token>, ReadError> {
+    let mut reader = Reader::new(json);
+    let mut tokens = Vec::new();
+    let mut end = 0;
+
+    while let Some((start, event)) = reader.next_event()? {
+        let depth = reader.depth();
+        let (kind, depth) = match event {
+            Event::BeginObject => ("begin_object", depth - 1),
+            Event::EndObject => ("end_object", depth),
+            Event::BeginArray => ("begin_array", depth - 1),
+            Event::EndArray => ("end_array", depth),
+            Event::Key(_) => ("key", depth),
+            Event::String(_) => ("string", depth),
+            Event::Number(_) => ("number", depth),
+            Event::Bool(_) => ("bool", depth),
+            Event::Null => ("null", depth),
+        };
+
+        // The reader consumes `:` and `,` without an event of their own;
+        // both sit at the level of the token they precede.
+        for (i, byte) in json.as_bytes()[end..start].iter().enumerate() {
+            let separator = match byte {
+                b':' => "colon",
+                b',' => "comma",
+                _ => continue,
+            };
+            tokens.push(Token {
+                kind: separator,
+                start: end + i,
+                end: end + i + 1,
+                depth,
+            });
+        }
+
+        end = reader.position();
+        tokens.push(Token { kind, start, end, depth });
+    }
+    Ok(tokens)
+}
+
+/// The tokens of a document, as `['type' => ..., 'lexeme' => ..., 'offset'
+/// => ..., 'depth' => ...]` entries. The document is checked and split up
+/// front; entries are only built as they are iterated.
+#[php_class]
+#[php(name = "Elephant\\Json\\Tokens")]
+#[php(implements(ce = ce::iterator, stub = "\\Iterator"))]
+#[php(implements(ce = ce::countable, stub = "\\Countable"))]
+pub struct Tokens {
+    json: String,
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+#[php_impl]
+impl Tokens {
+    pub fn count(&self) -> i64 {
+        self.tokens.len() as i64
+    }
+
+    pub fn rewind(&mut self) {
+        self.position = 0;
+    }
+
+    pub fn valid(&self) -> bool {
+        self.position < self.tokens.len()
+    }
+
+    pub fn current(&self) -> PhpResult<Zval> {
+        let mut zval = Zval::new();
+        match self.tokens.get(self.position) {
+            Some(token) => zval.set_hashtable(self.entry(token)?),
+            None => zval.set_null(),
+        }
+        Ok(zval)
+    }
+
+    pub fn key(&self) -> Option<i64> {
+        self.valid().then_some(self.position as i64)
+    }
+
+    pub fn next(&mut self) {
+        self.position += 1;
+    }
+}
+
+impl Tokens {
+    pub(crate) fn new(json: String) -> PhpResult<Self> {
+        let tokens = tokenize(&json).map_err(|e| format!("JSON syntax error: {}", e))?;
+        Ok(Self {
+            json,
+            tokens,
+            position: 0,
+        })
+    }
+
+    fn entry(&self, token: &Token) -> PhpResult<ZBox<ZendHashTable>> {
+        let mut entry = ZendHashTable::new();
+        entry.insert("type", token.kind)?;
+        entry.insert("lexeme", &self.json[token.start..token.end])?;
+        entry.insert("offset", token.start as i64)?;
+        entry.insert("depth", token.depth as i64)?;
+        Ok(entry)
+    }
+}
@@ -0,0 +1,33 @@
+//! Placeholder for the parts of a document a truncating decode leaves out.
+
+use ext_php_rs::prelude::*;
+
+/// Stands in for a value past `truncateDepth()` or for the array elements
+/// past `truncateItems()`. `start` and `end` delimit the omitted bytes, so
+/// `substr($json, $t->start, $t->length())` recovers them.
+#[php_class]
+#[php(name = "Elephant\\Json\\Truncated")]
+pub struct Truncated {
+    #[php(prop)]
+    pub start: i64,
+    /// Exclusive.
+    #[php(prop)]
+    pub end: i64,
+}
+
+#[php_impl]
+impl Truncated {
+    pub fn __construct(start: i64, end: i64) -> Self {
+        Self::new(start, end)
+    }
+
+    pub fn length(&self) -> i64 {
+        self.end - self.start
+    }
+}
+
+impl Truncated {
+    pub(crate) fn new(start: i64, end: i64) -> Self {
+        Self { start, end }
+    }
+}
@@ -0,0 +1,291 @@
+//! UBJSON (Universal Binary JSON, Draft 12), transcoded to and from JSON
+//! text so that both directions go through the same encoder and decoder,
+//! and with them every encode and decode option.
+//!
+//! Integers are written in the smallest type that holds them, floats as
+//! float64 and integers beyond int64 as high-precision numbers. Containers
+//! are written in the plain form; the optimized `$` type and `#` count
+//! forms are read too.
+
+use crate::error::JsonError;
+use crate::flags;
+use crate::number::float_lexeme;
+use crate::reader::{is_number, string_content, Event, Reader};
+use crate::writer::{JsonWriter, WriterConfig};
+
+/// Converts a JSON document to UBJSON.
+pub(crate) fn from_json(json: &str) -> Result<Vec<u8>, JsonError> {
+    let mut out = Vec::with_capacity(json.len());
+    let mut reader = Reader::new(json);
+    while let Some((_, event)) = reader.next_event().map_err(JsonError::read)? {
+        match event {
+            Event::BeginObject => out.push(b'{'),
+            Event::EndObject => out.push(b'}'),
+            Event::BeginArray => out.push(b'['),
+            Event::EndArray => out.push(b']'),
+            Event::Key(raw) => write_str(&mut out, &string_content(raw).map_err(JsonError::syntax)?),
+            Event::String(raw) => {
+                out.push(b'S');
+                write_str(&mut out, &string_content(raw).map_err(JsonError::syntax)?);
+            }
+            Event::Number(raw) => write_number(&mut out, raw),
+            Event::Bool(true) => out.push(b'T'),
+            Event::Bool(false) => out.push(b'F'),
+            Event::Null => out.push(b'Z'),
+        }
+    }
+    Ok(out)
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_int(out, s.len() as i64);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_number(out: &mut Vec<u8>, raw: &str) {
+    if !raw.contains(['.', 'e', 'E']) {
+        if let Ok(n) = raw.parse::<i64>() {
+            return write_int(out, n);
+        }
+    } else if let Some(f) = raw.parse::<f64>().ok().filter(|f| f.is_finite()) {
+        out.push(b'D');
+        out.extend_from_slice(&f.to_be_bytes());
+        return;
+    }
+    out.push(b'H');
+    write_str(out, raw);
+}
+
+fn write_int(out: &mut Vec<u8>, n: i64) {
+    if let Ok(n) = i8::try_from(n) {
+        out.push(b'i');
+        out.extend_from_slice(&n.to_be_bytes());
+    } else if let Ok(n) = u8::try_from(n) {
+        out.push(b'U');
+        out.push(n);
+    } else if let Ok(n) = i16::try_from(n) {
+        out.push(b'I');
+        out.extend_from_slice(&n.to_be_bytes());
+    } else if let Ok(n) = i32::try_from(n) {
+        out.push(b'l');
+        out.extend_from_slice(&n.to_be_bytes());
+    } else {
+        out.push(b'L');
+        out.extend_from_slice(&n.to_be_bytes());
+    }
+}
+
+/// Most `null`, `true` or `false` elements a typed container may declare,
+/// unless the input is larger.
+const MAX_EMPTY_ELEMENTS: usize = 65536;
+
+/// An open container while reading.
+struct Frame {
+    object: bool,
+    /// Element type of a strongly typed container.
+    element: Option<u8>,
+    /// Element count of a counted container.
+    count: Option<usize>,
+    seen: usize,
+}
+
+/// Converts UBJSON to a JSON document of at most `max_len` bytes. Nested
+/// containers are tracked on the heap, so deep input cannot overflow the
+/// stack before the decoder's depth limit applies to the result.
+pub(crate) fn to_json(bytes: &[u8], max_len: Option<usize>) -> Result<String, JsonError> {
+    let config = WriterConfig::compact();
+    let mut writer = JsonWriter::new(&config);
+    let mut input = Input { bytes, pos: 0 };
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut started = false;
+
+    loop {
+        if let Some(max) = max_len.filter(|max| writer.len() > *max) {
+            return Err(JsonError::new(
+                flags::ERROR_INPUT_TOO_LARGE,
+                format!("UBJSON expands to more than the maximum of {} bytes of JSON", max),
+            ));
+        }
+        let marker = match stack.last_mut() {
+            None if started => break,
+            None => {
+                started = true;
+                input.marker()?
+            }
+            Some(frame) => {
+                let end = match frame.count {
+                    Some(count) => frame.seen == count,
+                    None => {
+                        let closing = if frame.object { b'}' } else { b']' };
+                        input.skip_noops();
+                        input.bytes.get(input.pos) == Some(&closing)
+                    }
+                };
+                if end {
+                    if frame.count.is_none() {
+                        input.pos += 1;
+                    }
+                    match frame.object {
+                        true => writer.end_object(),
+                        false => writer.end_array(),
+                    }
+                    stack.pop();
+                    continue;
+                }
+                frame.seen += 1;
+                if frame.object {
+                    writer.key(&input.string()?);
+                }
+                match frame.element {
+                    Some(marker) => marker,
+                    None => input.marker()?,
+                }
+            }
+        };
+
+        match marker {
+            b'Z' => writer.null(),
+            b'T' => writer.bool(true),
+            b'F' => writer.bool(false),
+            b'i' | b'U' | b'I' | b'l' | b'L' => writer.number(&input.int(marker)?.to_string()),
+            b'd' => {
+                let f = f32::from_be_bytes(input.array()?);
+                writer.number(&float_lexeme(f.is_finite().then(|| f.to_string()), "UBJSON", input.pos)?);
+            }
+            b'D' => {
+                let f = f64::from_be_bytes(input.array()?);
+                let lexeme = serde_json::Number::from_f64(f).map(|n| n.to_string());
+                writer.number(&float_lexeme(lexeme, "UBJSON", input.pos)?);
+            }
+            b'H' => {
+                let at = input.pos;
+                let raw = input.string()?;
+                if !is_number(&raw) {
+                    return Err(input.error_at(at, "invalid high-precision number"));
+                }
+                writer.number(&raw);
+            }
+            b'C' => {
+                let c = input.array::<1>()?[0];
+                if !c.is_ascii() {
+                    return Err(input.error_at(input.pos - 1, "char out of range"));
+                }
+                writer.string(&(c as char).to_string());
+            }
+            b'S' => writer.string(&input.string()?),
+            b'[' | b'{' => {
+                let object = marker == b'{';
+                let (element, count) = input.container_header()?;
+                match object {
+                    true => writer.begin_object(),
+                    false => writer.begin_array(),
+                }
+                stack.push(Frame {
+                    object,
+                    element,
+                    count,
+                    seen: 0,
+                });
+            }
+            _ => return Err(input.error_at(input.pos - 1, "unexpected marker")),
+        }
+    }
+
+    input.skip_noops();
+    if input.pos < bytes.len() {
+        return Err(input.error_at(input.pos, "trailing data"));
+    }
+    Ok(writer.finish())
+}
+
+struct Input<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Input<'_> {
+    fn error_at(&self, pos: usize, detail: &str) -> JsonError {
+        JsonError::new(flags::ERROR_SYNTAX, format!("Malformed UBJSON at byte {}: {}", pos, detail))
+    }
+
+    fn skip_noops(&mut self) {
+        while self.bytes.get(self.pos) == Some(&b'N') {
+            self.pos += 1;
+        }
+    }
+
+    /// The next type marker, past any no-ops.
+    fn marker(&mut self) -> Result<u8, JsonError> {
+        self.skip_noops();
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], JsonError> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + N)
+            .ok_or_else(|| self.error_at(self.bytes.len(), "unexpected end of input"))?;
+        self.pos += N;
+        Ok(bytes.try_into().unwrap_or([0; N]))
+    }
+
+    fn int(&mut self, marker: u8) -> Result<i64, JsonError> {
+        Ok(match marker {
+            b'i' => i8::from_be_bytes(self.array()?) as i64,
+            b'U' => self.array::<1>()?[0] as i64,
+            b'I' => i16::from_be_bytes(self.array()?) as i64,
+            b'l' => i32::from_be_bytes(self.array()?) as i64,
+            b'L' => i64::from_be_bytes(self.array()?),
+            _ => return Err(self.error_at(self.pos - 1, "expected an integer type")),
+        })
+    }
+
+    /// A length: an integer of any type, not negative.
+    fn length(&mut self) -> Result<usize, JsonError> {
+        let at = self.pos;
+        let marker = self.marker()?;
+        usize::try_from(self.int(marker)?).map_err(|_| self.error_at(at, "negative length"))
+    }
+
+    /// A string's length and UTF-8 bytes, without a marker.
+    fn string(&mut self) -> Result<String, JsonError> {
+        let len = self.length()?;
+        let start = self.pos;
+        let bytes = self
+            .bytes
+            .get(start..start.saturating_add(len))
+            .ok_or_else(|| self.error_at(self.bytes.len(), "unexpected end of input"))?;
+        self.pos += len;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| JsonError::new(flags::ERROR_UTF8, format!("Malformed UTF-8 in UBJSON string at byte {}", start)))
+    }
+
+    /// The optional `$` element type and `#` count after `[` or `{`.
+    fn container_header(&mut self) -> Result<(Option<u8>, Option<usize>), JsonError> {
+        let element = match self.bytes.get(self.pos) {
+            Some(b'$') => {
+                self.pos += 1;
+                Some(self.array::<1>()?[0])
+            }
+            _ => None,
+        };
+        let count = match self.bytes.get(self.pos) {
+            Some(b'#') => {
+                self.pos += 1;
+                Some(self.length()?)
+            }
+            _ if element.is_some() => return Err(self.error_at(self.pos, "typed container without a count")),
+            _ => None,
+        };
+        if element == Some(b'N') {
+            return Err(self.error_at(self.pos - 1, "no-op as container type"));
+        }
+        // Values of these types take no bytes, so cap how far a few bytes
+        // can expand.
+        let empty = matches!(element, Some(b'Z' | b'T' | b'F'));
+        if let Some(count) = count.filter(|count| empty && *count > self.bytes.len().max(MAX_EMPTY_ELEMENTS)) {
+            return Err(self.error_at(self.pos, &format!("count {} is too large", count)));
+        }
+        Ok((element, count))
+    }
+}
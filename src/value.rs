@@ -0,0 +1,206 @@
+//! Typed navigation over decoded values.
+
+use ext_php_rs::binary::Binary;
+use ext_php_rs::boxed::ZBox;
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ZendHashTable, Zval};
+use ext_php_rs::zend::ce;
+
+use crate::options::DecoderOptions;
+use crate::pointer::PointerArg;
+use crate::{decode_bytes, pointer, DecodeConfig};
+
+/// The type names `type()` reports and `asArrayOf()` accepts.
+const TYPES: &[&str] = &["null", "bool", "int", "float", "string", "array", "object"];
+
+/// An immutable decoded value together with its JSON Pointer in the
+/// document it came from.
+///
+/// The `as*()` accessors return the value only if it has the expected type
+/// and otherwise throw a `TypeError` naming the pointer, so strictly typed
+/// code never handles `mixed`.
+#[php_class]
+#[php(name = "Elephant\\Json\\Value")]
+pub struct Value {
+    value: Zval,
+    path: String,
+}
+
+#[php_impl]
+impl Value {
+    /// Wraps an already decoded value.
+    pub fn of(value: &Zval) -> Value {
+        Self::new(value.shallow_clone(), String::new())
+    }
+
+    #[php(defaults(options = None))]
+    pub fn parse(json: Binary<u8>, options: Option<&DecoderOptions>) -> PhpResult<Value> {
+        let options = options
+            .cloned()
+            .unwrap_or_else(|| DecoderOptions::resolve(None, None).0);
+        let value = decode_bytes(&json, DecodeConfig::new(options, None))?;
+        Ok(Self::new(value, String::new()))
+    }
+
+    /// JSON Pointer of this value; `""` for the root.
+    pub fn path(&self) -> String {
+        self.path.clone()
+    }
+
+    /// One of `null`, `bool`, `int`, `float`, `string`, `array` (a list) or
+    /// `object` (an object or an array with keys).
+    #[php(name = "type")]
+    pub fn type_name(&self) -> &'static str {
+        type_of(&self.value)
+    }
+
+    /// The value at `$pointer`, relative to this one.
+    pub fn at(&self, pointer: PointerArg) -> PhpResult<Value> {
+        let mut value = self.value.shallow_clone();
+        let mut path = self.path.clone();
+        for token in pointer.tokens()?.iter() {
+            let child = match type_of(&value) {
+                "array" | "object" => child(&value, token),
+                _ => return Err(self.mismatch("array or object", &value, &path)),
+            };
+            path.push('/');
+            path.push_str(&pointer::escape(token));
+            value = child.ok_or_else(|| Self::missing(&path))?;
+        }
+        Ok(Self::new(value, path))
+    }
+
+    pub fn has(&self, pointer: PointerArg) -> bool {
+        self.at(pointer).is_ok()
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.value.is_null()
+    }
+
+    pub fn as_bool(&self) -> PhpResult<bool> {
+        self.value.bool().ok_or_else(|| self.type_error("bool"))
+    }
+
+    pub fn as_int(&self) -> PhpResult<i64> {
+        if self.value.is_long() {
+            return Ok(self.value.long().unwrap_or_default());
+        }
+        Err(self.type_error("int"))
+    }
+
+    /// Integers are accepted too, since JSON does not tell the two apart.
+    pub fn as_float(&self) -> PhpResult<f64> {
+        match type_of(&self.value) {
+            "int" | "float" => Ok(self
+                .value
+                .double()
+                .or_else(|| self.value.long().map(|i| i as f64))
+                .unwrap_or_default()),
+            _ => Err(self.type_error("float")),
+        }
+    }
+
+    pub fn as_string(&self) -> PhpResult<String> {
+        self.value
+            .string()
+            .filter(|_| self.value.is_string())
+            .ok_or_else(|| self.type_error("string"))
+    }
+
+    /// The members or elements of this array or object, each checked to be
+    /// of `$item_type`. Keys are kept.
+    pub fn as_array_of(&self, item_type: String) -> PhpResult<ZBox<ZendHashTable>> {
+        if !TYPES.contains(&item_type.as_str()) {
+            return Err(PhpException::new(
+                format!("Argument #1 ($item_type) must be one of '{}'", TYPES.join("', '")),
+                0,
+                ce::value_error(),
+            ));
+        }
+
+        let members = match (type_of(&self.value), self.value.array(), self.value.object()) {
+            ("array" | "object", Some(array), _) => array,
+            ("object", None, Some(object)) => object.get_properties()?,
+            _ => return Err(self.type_error("array or object")),
+        };
+
+        let mut result = ZendHashTable::new();
+        for (key, member) in members {
+            let path = format!("{}/{}", self.path, pointer::escape(&key.to_string()));
+            if type_of(member) != item_type {
+                return Err(self.mismatch(&item_type, member, &path));
+            }
+            result.insert(key, member.shallow_clone())?;
+        }
+        Ok(result)
+    }
+
+    /// The wrapped value as is.
+    pub fn value(&self) -> Zval {
+        self.value.shallow_clone()
+    }
+}
+
+impl Value {
+    pub(crate) fn new(value: Zval, path: String) -> Self {
+        Self { value, path }
+    }
+
+    pub(crate) fn missing(path: &str) -> PhpException {
+        PhpException::default(format!("No value at {}", location(path)))
+    }
+
+    fn type_error(&self, expected: &str) -> PhpException {
+        self.mismatch(expected, &self.value, &self.path)
+    }
+
+    fn mismatch(&self, expected: &str, value: &Zval, path: &str) -> PhpException {
+        PhpException::new(
+            format!("Expected {} at {}, got {}", expected, location(path), type_of(value)),
+            0,
+            ce::type_error(),
+        )
+    }
+}
+
+pub(crate) fn location(path: &str) -> String {
+    if path.is_empty() {
+        "the document root".to_string()
+    } else {
+        format!("'{}'", path)
+    }
+}
+
+fn type_of(value: &Zval) -> &'static str {
+    if value.is_null() {
+        "null"
+    } else if value.is_bool() {
+        "bool"
+    } else if value.is_long() {
+        "int"
+    } else if value.is_double() {
+        "float"
+    } else if value.is_string() {
+        "string"
+    } else if let Some(array) = value.array() {
+        if array.has_sequential_keys() { "array" } else { "object" }
+    } else {
+        "object"
+    }
+}
+
+/// Looks up a reference token in an array or an object's properties.
+fn child(value: &Zval, token: &str) -> Option<Zval> {
+    if let Some(array) = value.array() {
+        return match type_of(value) {
+            "array" => pointer::array_index(token).and_then(|i| array.get_index(i as i64)),
+            _ => array.get(token),
+        }
+        .map(Zval::shallow_clone);
+    }
+    value
+        .object()
+        .and_then(|object| object.get_property::<&Zval>(token).ok())
+        .map(Zval::shallow_clone)
+}
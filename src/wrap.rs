@@ -0,0 +1,59 @@
+//! Wrappers overriding whether an array encodes as a JSON list or object.
+
+use ext_php_rs::prelude::*;
+use ext_php_rs::types::{ZendHashTable, Zval};
+use ext_php_rs::zend::ce;
+
+/// Encodes the wrapped array as a JSON object, even when it is empty or
+/// its keys are `0..n`: `new AsObject([])` is `{}`.
+#[php_class]
+#[php(name = "Elephant\\Json\\AsObject")]
+pub struct AsObject {
+    array: Zval,
+}
+
+#[php_impl]
+impl AsObject {
+    pub fn __construct(array: &Zval) -> PhpResult<Self> {
+        Ok(Self { array: array_arg(array)? })
+    }
+}
+
+/// Encodes the values of the wrapped array as a JSON list in iteration
+/// order, whatever their keys: `new AsList([3 => 'a', 'x' => 'b'])` is
+/// `["a","b"]`.
+#[php_class]
+#[php(name = "Elephant\\Json\\AsList")]
+pub struct AsList {
+    array: Zval,
+}
+
+#[php_impl]
+impl AsList {
+    pub fn __construct(array: &Zval) -> PhpResult<Self> {
+        Ok(Self { array: array_arg(array)? })
+    }
+}
+
+impl AsObject {
+    pub(crate) fn array(&self) -> Option<&ZendHashTable> {
+        self.array.array()
+    }
+}
+
+impl AsList {
+    pub(crate) fn array(&self) -> Option<&ZendHashTable> {
+        self.array.array()
+    }
+}
+
+fn array_arg(value: &Zval) -> PhpResult<Zval> {
+    if !value.is_array() {
+        return Err(PhpException::new(
+            format!("Argument #1 ($array) must be of type array, {} given", value.get_type()),
+            0,
+            ce::type_error(),
+        ));
+    }
+    Ok(value.shallow_clone())
+}
@@ -0,0 +1,335 @@
+use crate::escape::{escape_into, Escaping};
+use crate::reader::{string_content, Event};
+
+/// ANSI SGR codes used for each token class when colorizing output.
+pub(crate) struct Palette {
+    pub key: &'static str,
+    pub string: &'static str,
+    pub number: &'static str,
+    pub literal: &'static str,
+    pub punctuation: &'static str,
+}
+
+impl Palette {
+    pub const TERMINAL: Palette = Palette {
+        key: "1;34",
+        string: "0;32",
+        number: "0;33",
+        literal: "0;35",
+        punctuation: "1;39",
+    };
+}
+
+/// Pretty-printing layouts, see `EncoderOptions::style()`.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub(crate) enum Style {
+    /// One element per line.
+    #[default]
+    Expanded,
+    /// Lists of scalars on a single line.
+    CompactArrays,
+    /// Lists of containers "cuddled": `[{`, `}, {` and `}]`.
+    KAndR,
+    KAndRCompactArrays,
+}
+
+impl Style {
+    pub const NAMES: &[(&str, Style)] = &[
+        ("expanded", Style::Expanded),
+        ("compact-arrays", Style::CompactArrays),
+        ("k&r", Style::KAndR),
+        ("k&r-compact-arrays", Style::KAndRCompactArrays),
+    ];
+
+    fn compact_arrays(self) -> bool {
+        matches!(self, Style::CompactArrays | Style::KAndRCompactArrays)
+    }
+
+    fn cuddled(self) -> bool {
+        matches!(self, Style::KAndR | Style::KAndRCompactArrays)
+    }
+}
+
+/// What a list holds, which decides its layout under a `Style`.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum ArrayShape {
+    Scalars,
+    Containers,
+    Mixed,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Layout {
+    Expanded,
+    /// On one line, as is everything nested inside.
+    Inline,
+    /// Elements start on the bracket's line and add no indentation.
+    Cuddled,
+}
+
+struct Frame {
+    has_items: bool,
+    layout: Layout,
+}
+
+pub(crate) struct WriterConfig {
+    pub indent: Option<String>,
+    pub style: Style,
+    pub escaping: Escaping,
+    pub palette: Option<&'static Palette>,
+    /// Initial output buffer size in bytes.
+    pub capacity: usize,
+}
+
+impl WriterConfig {
+    pub fn compact() -> Self {
+        Self {
+            indent: None,
+            style: Style::Expanded,
+            escaping: Escaping::default(),
+            palette: None,
+            capacity: 0,
+        }
+    }
+
+    pub fn pretty() -> Self {
+        Self {
+            indent: Some("  ".to_string()),
+            ..Self::compact()
+        }
+    }
+}
+
+/// Incremental JSON text writer.
+///
+/// Callers emit structure and scalars in document order; the writer takes
+/// care of separators, indentation, escaping and coloring.
+pub(crate) struct JsonWriter<'a> {
+    config: &'a WriterConfig,
+    out: String,
+    /// One entry per open container.
+    stack: Vec<Frame>,
+    after_key: bool,
+    /// Bytes already handed on by `flush()`.
+    flushed: usize,
+}
+
+impl<'a> JsonWriter<'a> {
+    pub fn new(config: &'a WriterConfig) -> Self {
+        Self {
+            config,
+            out: String::with_capacity(config.capacity),
+            stack: Vec::new(),
+            after_key: false,
+            flushed: 0,
+        }
+    }
+
+    /// Continues after the existing contents of `out`, reusing its
+    /// allocation.
+    pub fn appending(config: &'a WriterConfig, out: String) -> Self {
+        Self {
+            out,
+            ..Self::new(config)
+        }
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+
+    /// Bytes written so far.
+    pub fn len(&self) -> usize {
+        self.flushed + self.out.len()
+    }
+
+    /// Bytes written since the last `flush()`.
+    pub fn pending(&self) -> usize {
+        self.out.len()
+    }
+
+    /// Hands what was written since the last call to `sink` and clears it,
+    /// keeping the allocation.
+    pub fn flush<E>(&mut self, sink: impl FnOnce(&str) -> Result<(), E>) -> Result<(), E> {
+        sink(&self.out)?;
+        self.flushed += self.out.len();
+        self.out.clear();
+        Ok(())
+    }
+
+    pub fn begin_array(&mut self) {
+        self.begin_array_of(ArrayShape::Mixed);
+    }
+
+    /// Begins a list whose elements are known to be `shape`, so the style
+    /// can lay it out accordingly.
+    pub fn begin_array_of(&mut self, shape: ArrayShape) {
+        self.before_value();
+        self.punct("[");
+        let style = self.config.style;
+        let layout = match shape {
+            _ if self.config.indent.is_none() => Layout::Expanded,
+            _ if self.in_inline() => Layout::Inline,
+            ArrayShape::Scalars if style.compact_arrays() => Layout::Inline,
+            ArrayShape::Containers if style.cuddled() => Layout::Cuddled,
+            _ => Layout::Expanded,
+        };
+        self.stack.push(Frame { has_items: false, layout });
+    }
+
+    pub fn end_array(&mut self) {
+        self.close("]");
+    }
+
+    pub fn begin_object(&mut self) {
+        self.before_value();
+        self.punct("{");
+        let layout = if self.in_inline() { Layout::Inline } else { Layout::Expanded };
+        self.stack.push(Frame { has_items: false, layout });
+    }
+
+    pub fn end_object(&mut self) {
+        self.close("}");
+    }
+
+    pub fn key(&mut self, key: &str) {
+        self.before_value();
+        let code = self.config.palette.map(|p| p.key);
+        self.colored(code, |out, config| escape_into(out, key, &config.escaping));
+        self.punct(":");
+        if self.config.indent.is_some() {
+            self.out.push(' ');
+        }
+        self.after_key = true;
+    }
+
+    /// Like [`key`](Self::key), for a key already quoted and escaped with
+    /// this writer's settings.
+    pub fn escaped_key(&mut self, quoted: &str) {
+        self.before_value();
+        let code = self.config.palette.map(|p| p.key);
+        self.colored(code, |out, _| out.push_str(quoted));
+        self.punct(":");
+        if self.config.indent.is_some() {
+            self.out.push(' ');
+        }
+        self.after_key = true;
+    }
+
+    pub fn null(&mut self) {
+        self.literal("null");
+    }
+
+    pub fn bool(&mut self, b: bool) {
+        self.literal(if b { "true" } else { "false" });
+    }
+
+    pub fn number(&mut self, n: &str) {
+        self.before_value();
+        let code = self.config.palette.map(|p| p.number);
+        self.colored(code, |out, _| out.push_str(n));
+    }
+
+    pub fn string(&mut self, s: &str) {
+        self.before_value();
+        let code = self.config.palette.map(|p| p.string);
+        self.colored(code, |out, config| escape_into(out, s, &config.escaping));
+    }
+
+    /// Re-emits a reader event. Numbers are copied verbatim; strings are
+    /// decoded and escaped again with this writer's settings.
+    pub fn event(&mut self, event: Event) -> Result<(), String> {
+        match event {
+            Event::BeginObject => self.begin_object(),
+            Event::EndObject => self.end_object(),
+            Event::BeginArray => self.begin_array(),
+            Event::EndArray => self.end_array(),
+            Event::Key(raw) => self.key(&string_content(raw)?),
+            Event::String(raw) => self.string(&string_content(raw)?),
+            Event::Number(raw) => self.number(raw),
+            Event::Bool(b) => self.bool(b),
+            Event::Null => self.null(),
+        }
+        Ok(())
+    }
+
+    /// Writes `text` where the next value would go, as is. The result is
+    /// no longer JSON; for human-readable renderings only.
+    pub fn annotation(&mut self, text: &str) {
+        self.before_value();
+        self.out.push_str(text);
+    }
+
+    fn literal(&mut self, text: &str) {
+        self.before_value();
+        let code = self.config.palette.map(|p| p.literal);
+        self.colored(code, |out, _| out.push_str(text));
+    }
+
+    /// Emits the separator and line break owed before the next element.
+    fn before_value(&mut self) {
+        if self.after_key {
+            self.after_key = false;
+            return;
+        }
+
+        let Some(frame) = self.stack.last_mut() else {
+            return;
+        };
+
+        let needs_comma = frame.has_items;
+        let layout = frame.layout;
+        frame.has_items = true;
+        if needs_comma {
+            self.punct(",");
+        }
+        match layout {
+            Layout::Expanded => self.newline(self.level()),
+            Layout::Inline | Layout::Cuddled if needs_comma => self.out.push(' '),
+            Layout::Inline | Layout::Cuddled => {}
+        }
+    }
+
+    fn close(&mut self, bracket: &str) {
+        let frame = self.stack.pop();
+        if frame.is_some_and(|frame| frame.has_items && frame.layout == Layout::Expanded) {
+            self.newline(self.level());
+        }
+        self.punct(bracket);
+    }
+
+    fn in_inline(&self) -> bool {
+        self.stack.last().is_some_and(|frame| frame.layout == Layout::Inline)
+    }
+
+    /// Indentation level: the open containers, less the cuddled ones.
+    fn level(&self) -> usize {
+        self.stack.iter().filter(|frame| frame.layout != Layout::Cuddled).count()
+    }
+
+    fn newline(&mut self, depth: usize) {
+        if let Some(indent) = &self.config.indent {
+            self.out.push('\n');
+            for _ in 0..depth {
+                self.out.push_str(indent);
+            }
+        }
+    }
+
+    fn punct(&mut self, text: &str) {
+        let code = self.config.palette.map(|p| p.punctuation);
+        self.colored(code, |out, _| out.push_str(text));
+    }
+
+    fn colored(&mut self, code: Option<&str>, write: impl FnOnce(&mut String, &WriterConfig)) {
+        if let Some(code) = code {
+            self.out.push_str("\x1b[");
+            self.out.push_str(code);
+            self.out.push('m');
+            write(&mut self.out, self.config);
+            self.out.push_str("\x1b[0m");
+        } else {
+            write(&mut self.out, self.config);
+        }
+    }
+}
@@ -0,0 +1,99 @@
+<?php
+// Round-trips values through Smile, UBJSON and Ion, and decodes hand-made
+// Smile with back-references under each header flag.
+//
+// Run with the extension loaded:
+//   php -d extension=target/release/libelephant_json.so tests/binary.php
+//
+// Exits non-zero when any case fails.
+
+use Elephant\Json\Annotated;
+
+$attempt = function (callable $call) {
+    try {
+        return $call();
+    } catch (Exception $e) {
+        return 'error ' . $e->getCode();
+    }
+};
+
+// Annotated values as plain arrays, so results compare with ===.
+$plain = function ($value) use (&$plain) {
+    if ($value instanceof Annotated) {
+        return ['annotations' => $value->annotations(), 'value' => $plain($value->value())];
+    }
+    return is_array($value) ? array_map($plain, $value) : $value;
+};
+
+$keys = [];
+for ($i = 0; $i < 70; $i++) {
+    $keys["k$i"] = $i;
+}
+
+$values = [
+    'scalars' => [null, true, false, 0, -16, 15, PHP_INT_MAX, PHP_INT_MIN, 1.5, -0.25, 2.0, 1e300, 5e-324],
+    'strings' => ['', 'hello', str_repeat('a', 75), "caf\u{e9}", str_repeat("\u{e9}", 40), "quote \" and \\ and \n"],
+    'records' => [['id' => 1, 'name' => 'a', 'tags' => ['x']], ['id' => 2, 'name' => 'b', 'tags' => []]],
+    'empty key' => ['' => 0, 'a' => ['' => [[]]]],
+    'two-byte back-references' => [$keys, $keys],
+];
+
+$cases = [];
+foreach ($values as $name => $value) {
+    $cases["Smile $name"] = [$attempt(fn () => Json::decodeSmile(Json::encodeSmile($value), true)), $value];
+    $cases["UBJSON $name"] = [$attempt(fn () => Json::decodeUbjson(Json::encodeUbjson($value), true)), $value];
+    $cases["Ion $name"] = [$attempt(fn () => Json::decodeIon(Json::encodeIon($value), true)), $value];
+}
+
+$annotated = [new Annotated(['point', 'my type', 'null', '$10'], ['x' => 1.5]), new Annotated(['id'], 'v')];
+$cases['Ion annotations'] = [$plain($attempt(fn () => Json::decodeIon(Json::encodeIon($annotated), true))), $plain($annotated)];
+
+$smile = ":)\n";
+$cases += [
+    'Smile enables shared names only' => [substr(Json::encodeSmile([]), 0, 4), "$smile\x01"],
+    'Smile writes repeated keys as back-references' => [
+        Json::encodeSmile([['id' => 1], ['id' => 2]]),
+        "$smile\x01\xf8\xfa\x81id\xc2\xfb\xfa\x40\xc4\xfb\xf9",
+    ],
+    'Smile shared name reference' => [
+        $attempt(fn () => Json::decodeSmile("$smile\x01\xf8\xfa\x81id\xc2\xfb\xfa\x40\xc4\xfb\xf9", true)),
+        [['id' => 1], ['id' => 2]],
+    ],
+    'Smile shared name reference without the flag' => [
+        $attempt(fn () => Json::decodeSmile("$smile\x00\xf8\xfa\x81id\xc2\xfb\xfa\x40\xc4\xfb\xf9", true)),
+        'error ' . JSON_ERROR_SYNTAX,
+    ],
+    'Smile shared value reference' => [
+        $attempt(fn () => Json::decodeSmile("$smile\x03\xf8\x41ab\x01\xf9", true)),
+        ['ab', 'ab'],
+    ],
+    'Smile shared value reference without the flag' => [
+        $attempt(fn () => Json::decodeSmile("$smile\x01\xf8\x41ab\x01\xf9", true)),
+        'error ' . JSON_ERROR_SYNTAX,
+    ],
+    'Smile whole float64 stays a float' => [
+        $attempt(fn () => Json::decodeSmile("$smile\x00\x29\x00\x40\x00\x00\x00\x00\x00\x00\x00\x00")),
+        2.0,
+    ],
+    'Smile float64 infinity' => [
+        $attempt(fn () => Json::decodeSmile("$smile\x00\x29\x00\x7f\x78\x00\x00\x00\x00\x00\x00\x00")),
+        'error ' . JSON_ERROR_INF_OR_NAN,
+    ],
+    'UBJSON whole float64 stays a float' => [$attempt(fn () => Json::decodeUbjson("D\x40\x00\x00\x00\x00\x00\x00\x00")), 2.0],
+    'UBJSON float32' => [$attempt(fn () => Json::decodeUbjson("d\x3f\xc0\x00\x00")), 1.5],
+    'UBJSON float64 infinity' => [
+        $attempt(fn () => Json::decodeUbjson("D\x7f\xf0\x00\x00\x00\x00\x00\x00")),
+        'error ' . JSON_ERROR_INF_OR_NAN,
+    ],
+];
+
+$failures = 0;
+foreach ($cases as $name => [$actual, $expected]) {
+    if ($actual !== $expected) {
+        $failures++;
+        printf("FAIL %s\n  expected: %s\n  actual:   %s\n", $name, var_export($expected, true), var_export($actual, true));
+    }
+}
+
+printf("%d failures\n", $failures);
+exit($failures === 0 ? 0 : 1);
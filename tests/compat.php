@@ -0,0 +1,84 @@
+<?php
+// Differential check of the extension against ext/json.
+//
+// Run with both extensions loaded:
+//   php -d extension=target/release/libelephant_json.so tests/compat.php
+//
+// Exits non-zero when any case differs.
+
+if (!function_exists('json_encode') || (new ReflectionFunction('json_encode'))->getExtensionName() !== 'json') {
+    fwrite(STDERR, "ext/json is required as the reference implementation\n");
+    exit(2);
+}
+
+$values = [
+    null, true, false, 0, -1, PHP_INT_MAX, PHP_INT_MIN, 1.5, -0.25, 1e100, NAN, INF, -INF,
+    0.0, -0.0, 1.0, 100.0, 0.1, 1 / 3, 1e15, 1e16, 1e17, 1e-4, 1e-5, -2.5e-10, 1.5e300, PHP_FLOAT_EPSILON,
+    '12', ' 1.5 ', '1e3', '-0', '.5', '0x1A', '9223372036854775808', '1e999', '12abc',
+    '', 'hello', "quote\" backslash\\ slash/", "tab\tnewline\ncr\r", "\u{1}\u{1f}",
+    'é', '😀', "\u{2028}\u{2029}", '<tag attr=\'x\'>&amp;</tag>',
+    [], [1, 2, 3], [1 => 'a', 2 => 'b'], ['a' => 1, 'b' => [true, null]],
+    ['nested' => ['deeper' => ['deepest' => [1, [2, [3]]]]]],
+    ['empty' => [], 'list' => [[], [[]]], 'object' => new stdClass()],
+    (object) ['a' => 1.0, 'b' => ['x' => '2']],
+];
+
+$flagSets = [
+    0,
+    JSON_UNESCAPED_SLASHES,
+    JSON_UNESCAPED_UNICODE,
+    JSON_UNESCAPED_UNICODE | JSON_UNESCAPED_LINE_TERMINATORS,
+    JSON_HEX_TAG | JSON_HEX_AMP | JSON_HEX_APOS | JSON_HEX_QUOT,
+    JSON_PRETTY_PRINT,
+    JSON_PRETTY_PRINT | JSON_FORCE_OBJECT,
+    JSON_PRESERVE_ZERO_FRACTION,
+    JSON_NUMERIC_CHECK,
+    JSON_NUMERIC_CHECK | JSON_PRESERVE_ZERO_FRACTION,
+];
+
+// Json::COMPAT follows serialize_precision as json_encode() does.
+$precisions = [-1, 17, 14];
+
+$documents = [
+    '{"a":1,"b":[1,2,3],"c":{"d":null}}', '[]', '{}', '"é"', '"😀"',
+    '1', '-0', '1.0e3', '  [1, 2]  ', '[1,]', '{"a" 1}', '', 'nul', '"\u0000"',
+];
+
+$failures = 0;
+$report = function (string $what, $expected, $actual) use (&$failures) {
+    if ($expected !== $actual) {
+        $failures++;
+        printf("MISMATCH %s\n  ext/json: %s\n  elephant: %s\n", $what, var_export($expected, true), var_export($actual, true));
+    }
+};
+
+foreach ($precisions as $precision) {
+    ini_set('serialize_precision', (string) $precision);
+    foreach ($values as $value) {
+        foreach ($flagSets as $flags) {
+            try {
+                $actual = Json::encode($value, $flags | Json::COMPAT);
+            } catch (Exception $e) {
+                $actual = false;
+            }
+            $what = sprintf('encode(%s, %d) with serialize_precision=%d', var_export($value, true), $flags, $precision);
+            $report($what, json_encode($value, $flags), $actual);
+        }
+    }
+}
+ini_restore('serialize_precision');
+
+foreach ($documents as $json) {
+    foreach ([false, true] as $assoc) {
+        try {
+            $actual = Json::decode($json, $assoc);
+        } catch (Exception $e) {
+            $actual = null;
+        }
+        $report(sprintf('decode(%s, %s)', var_export($json, true), var_export($assoc, true)), json_decode($json, $assoc), $actual);
+    }
+    $report(sprintf('validate(%s)', var_export($json, true)), json_validate($json), Json::validate($json));
+}
+
+printf("%d mismatches\n", $failures);
+exit($failures === 0 ? 0 : 1);
@@ -0,0 +1,64 @@
+<?php
+// Checks Document lookups and edits, including those on containers that
+// end the document, and Json::edit() on the root.
+//
+// Run with the extension loaded:
+//   php -d extension=target/release/libelephant_json.so tests/document.php
+//
+// Exits non-zero when any case fails.
+
+use Elephant\Json\Document;
+
+$missing = function (callable $case): string {
+    try {
+        $case();
+    } catch (Exception $e) {
+        return 'missing';
+    }
+    return 'found';
+};
+
+$cases = [
+    'has missing member' => [fn () => Document::parse('{"a":1}')->has('/b'), false],
+    'has present member' => [fn () => Document::parse('{"a":1}')->has('/a'), true],
+    'has index past the end' => [fn () => Document::parse('[1,2]')->has('/2'), false],
+    'has in empty array' => [fn () => Document::parse('[]')->has('/0'), false],
+    'has in nested last container' => [fn () => Document::parse('{"a":[1,2]}')->has('/a/2'), false],
+    'get index past the end' => [fn () => $missing(fn () => Document::parse('[1,2]')->get('/5')), 'missing'],
+    'get missing nested member' => [fn () => $missing(fn () => Document::parse('{"a":{"b":1}}')->get('/a/c')), 'missing'],
+    'get last element' => [fn () => Document::parse('[1,2]')->get('/1'), 2],
+    'with new member' => [fn () => Json::decode(Document::parse('{"a":1}')->with('/b', 2)->raw(), true), ['a' => 1, 'b' => 2]],
+    'with existing member' => [fn () => Json::decode(Document::parse('{"a":1}')->with('/a', 2)->raw(), true), ['a' => 2]],
+    'with append' => [fn () => Json::decode(Document::parse('[1,2]')->with('/-', 3)->raw(), true), [1, 2, 3]],
+    'without first element' => [fn () => Json::decode(Document::parse('[1,2]')->without('/0')->raw(), true), [2]],
+    'without last member' => [fn () => Json::decode(Document::parse('{"a":1,"b":2}')->without('/b')->raw(), true), ['a' => 1]],
+    'without only member' => [fn () => Json::decode(Document::parse('{"a":1}')->without('/a')->raw(), true), []],
+    'push on root' => [fn () => Json::decode(Document::parse('[1,2]')->push('', 3)->raw(), true), [1, 2, 3]],
+    'push on empty root' => [fn () => Json::decode(Document::parse('[]')->push('', 1)->raw(), true), [1]],
+    'edit root' => [fn () => Json::decode(Json::edit('[1,2]', [
+        ['op' => 'push', 'path' => '', 'value' => 3],
+        ['op' => 'remove', 'path' => '/0'],
+        ['op' => 'set', 'path' => '/0', 'value' => 'x'],
+    ]), true), ['x', 3]],
+    'edit root object' => [fn () => Json::decode(Json::edit('{"a":1}', [
+        ['op' => 'set', 'path' => '/b', 'value' => [1]],
+        ['op' => 'push', 'path' => '/b', 'value' => 2],
+        ['op' => 'remove', 'path' => '/a'],
+    ]), true), ['b' => [1, 2]]],
+];
+
+$failures = 0;
+foreach ($cases as $name => [$case, $expected]) {
+    try {
+        $actual = $case();
+    } catch (Throwable $e) {
+        $actual = get_class($e) . ': ' . $e->getMessage();
+    }
+    if ($actual !== $expected) {
+        $failures++;
+        printf("FAIL %s\n  expected: %s\n  actual:   %s\n", $name, var_export($expected, true), var_export($actual, true));
+    }
+}
+
+printf("%d failures\n", $failures);
+exit($failures === 0 ? 0 : 1);
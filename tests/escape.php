@@ -0,0 +1,45 @@
+<?php
+// Checks that Json::escapeString() escapes exactly as Json::encode() does
+// inside a string, under the same options.
+//
+// Run with the extension loaded:
+//   php -d extension=target/release/libelephant_json.so tests/escape.php
+//
+// Exits non-zero when any case differs.
+
+use Elephant\Json\EncoderOptions;
+
+$strings = [
+    'plain',
+    "quote \" backslash \\ slash /",
+    "tab\t newline\n bell\x07 escape\x1b",
+    '<tag> & \'apos\'',
+    "caf\u{e9} \u{2028} \u{2029} \u{1f418}",
+];
+
+$options = [
+    'none' => null,
+    'flags' => JSON_HEX_TAG | JSON_HEX_AMP | JSON_HEX_APOS | JSON_HEX_QUOT,
+    'unescaped' => JSON_UNESCAPED_SLASHES | JSON_UNESCAPED_UNICODE,
+    'compat' => Json::COMPAT | JSON_HEX_TAG,
+    'hexCase upper' => EncoderOptions::new()->hexCase('upper'),
+    'hexCase upper unescapedUnicode' => EncoderOptions::new()->unescapedUnicode()->hexCase('upper'),
+    'minimalEscapes' => EncoderOptions::new()->minimalEscapes(),
+    'minimalEscapes unescapedUnicode' => EncoderOptions::new()->unescapedUnicode()->minimalEscapes(),
+    'minimalEscapes hexCase upper' => EncoderOptions::new()->minimalEscapes()->hexCase('upper'),
+];
+
+$failures = 0;
+foreach ($options as $name => $option) {
+    foreach ($strings as $string) {
+        $expected = substr(Json::encode($string, $option), 1, -1);
+        $actual = Json::escapeString($string, $option);
+        if ($expected !== $actual) {
+            $failures++;
+            printf("MISMATCH %s %s\n  encode:       %s\n  escapeString: %s\n", $name, var_export($string, true), $expected, $actual);
+        }
+    }
+}
+
+printf("%d mismatches\n", $failures);
+exit($failures === 0 ? 0 : 1);
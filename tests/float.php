@@ -0,0 +1,53 @@
+<?php
+// Checks that numbers too large for a float fail under finiteFloats() and
+// strictSecurity(), and still decode to INF otherwise.
+//
+// Run with the extension loaded:
+//   php -d extension=target/release/libelephant_json.so tests/float.php
+//
+// Exits non-zero when any case fails.
+
+use Elephant\Json\DecoderOptions;
+
+$decode = function (string $json, DecoderOptions $options) {
+    try {
+        return Json::decode($json, $options);
+    } catch (Exception $e) {
+        return 'error ' . $e->getCode();
+    }
+};
+
+$overflow = 'error ' . Json::ERROR_FLOAT_OVERFLOW;
+$huge = '1' . str_repeat('0', 400);
+
+$cases = [
+    'strictSecurity rejects 1e400' => [$decode('1e400', DecoderOptions::strictSecurity()), $overflow],
+    'strictSecurity rejects -1e400' => [$decode('[-1e400]', DecoderOptions::strictSecurity()), $overflow],
+    'finiteFloats rejects a huge fraction' => [$decode("{$huge}.5", DecoderOptions::new()->finiteFloats()), $overflow],
+    'finiteFloats rejects a huge integer as float' => [
+        $decode($huge, DecoderOptions::new()->intOverflow('float')->finiteFloats()),
+        $overflow,
+    ],
+    'finiteFloats keeps large finite floats' => [$decode('1.5e308', DecoderOptions::new()->finiteFloats()), 1.5e308],
+    'finiteFloats lets underflow reach zero' => [$decode('1e-400', DecoderOptions::new()->finiteFloats()), 0.0],
+    'finiteFloats(false) gives INF' => [
+        $decode('1e400', DecoderOptions::strictSecurity()->finiteFloats(false)),
+        INF,
+    ],
+    'default gives INF' => [$decode('-1e400', DecoderOptions::new()), -INF],
+    'floats("string") keeps the lexeme' => [
+        $decode('1e400', DecoderOptions::new()->floats('string')->finiteFloats()),
+        '1e400',
+    ],
+];
+
+$failures = 0;
+foreach ($cases as $name => [$actual, $expected]) {
+    if ($actual !== $expected) {
+        $failures++;
+        printf("FAIL %s\n  expected: %s\n  actual:   %s\n", $name, var_export($expected, true), var_export($actual, true));
+    }
+}
+
+printf("%d failures\n", $failures);
+exit($failures === 0 ? 0 : 1);
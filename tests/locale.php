@@ -0,0 +1,50 @@
+<?php
+// Checks that number formatting and parsing ignore setlocale().
+//
+// Run with the extension loaded:
+//   php -d extension=target/release/libelephant_json.so tests/locale.php
+//
+// Locales that are not installed are skipped. Exits non-zero when any case
+// differs between locales.
+
+use Elephant\Json\{DecoderOptions, EncoderOptions};
+
+$locales = [
+    'de_DE' => ['de_DE.UTF-8', 'de_DE.utf8', 'de_DE'],
+    'tr_TR' => ['tr_TR.UTF-8', 'tr_TR.utf8', 'tr_TR'],
+    'fr_FR' => ['fr_FR.UTF-8', 'fr_FR.utf8', 'fr_FR'],
+];
+
+$cases = [
+    'encode float' => fn () => Json::encode([1.5, -0.25, 1e100, 1e-7, 123456.789]),
+    'encode precision' => fn () => Json::encode(3.14159, EncoderOptions::new()->precision(3)),
+    'encode fixed point' => fn () => Json::encode(1e21, EncoderOptions::new()->fixedPoint()),
+    'decode float' => fn () => var_export(Json::decode('[1.5,-0.25,1e100,1E-7]'), true),
+    'decode float string' => fn () => var_export(Json::decode('[1.50]', DecoderOptions::new()->floats('string')), true),
+    'round trip' => fn () => Json::encode(Json::decode('{"price":19.99,"rate":0.075}')),
+    // Turkish has a dotless i; key case conversion must not follow the locale.
+    'key case upper' => fn () => var_export(Json::decode('{"item_id":1}', DecoderOptions::new()->assoc()->keyCase('upper')), true),
+    'key case lower' => fn () => var_export(Json::decode('{"ITEM_ID":1}', DecoderOptions::new()->assoc()->keyCase('lower')), true),
+];
+
+setlocale(LC_ALL, 'C');
+$expected = array_map(fn ($case) => $case(), $cases);
+
+$failures = 0;
+foreach ($locales as $name => $candidates) {
+    if (setlocale(LC_ALL, ...$candidates) === false) {
+        printf("SKIP %s (not installed)\n", $name);
+        continue;
+    }
+    foreach ($cases as $what => $case) {
+        $actual = $case();
+        if ($actual !== $expected[$what]) {
+            $failures++;
+            printf("MISMATCH %s under %s\n  C: %s\n  %s: %s\n", $what, $name, $expected[$what], $name, $actual);
+        }
+    }
+}
+setlocale(LC_ALL, 'C');
+
+printf("%d mismatches\n", $failures);
+exit($failures === 0 ? 0 : 1);
@@ -0,0 +1,52 @@
+<?php
+// Checks that the memory guard rejects only documents that do not fit.
+//
+// Run with the extension loaded:
+//   php -d extension=target/release/libelephant_json.so -d memory_limit=128M tests/memory.php
+//
+// Exits non-zero when any case fails.
+
+use Elephant\Json\DecoderOptions;
+
+$guard = DecoderOptions::new()->memoryGuard();
+$failures = 0;
+
+$check = function (string $name, callable $case) use (&$failures) {
+    try {
+        $result = $case();
+    } catch (Throwable $e) {
+        $result = get_class($e) . ': ' . $e->getMessage();
+    }
+    if ($result !== true) {
+        $failures++;
+        printf("FAIL %s\n  %s\n", $name, var_export($result, true));
+    }
+};
+
+// About 28 MB of long strings decodes to little more than its own size.
+$strings = '[' . implode(',', array_fill(0, 110000, '"' . str_repeat('x', 250) . '"')) . ']';
+$check('fitting document with the guard', function () use ($strings, $guard) {
+    return count(Json::decode($strings, $guard)) === 110000;
+});
+
+$check('guard is off by default', function () {
+    return ini_get('elephant_json.memory_guard') === '0';
+});
+unset($strings);
+
+// Small integers cost several times their input size; leave room for the
+// input only.
+$ints = '[' . str_repeat('1,', 3000000) . '1]';
+ini_set('memory_limit', (string) (memory_get_usage() + 8 * 1024 * 1024));
+$check('oversized document with the guard', function () use ($ints, $guard) {
+    try {
+        Json::decode($ints, $guard);
+    } catch (Exception $e) {
+        return $e->getCode() === Json::ERROR_MEMORY_LIMIT;
+    }
+    return 'decoded without hitting the limit';
+});
+ini_set('memory_limit', '128M');
+
+printf("%d failures\n", $failures);
+exit($failures === 0 ? 0 : 1);
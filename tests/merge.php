@@ -0,0 +1,51 @@
+<?php
+// Differential check of Json::mergeRecursive() against array_merge_recursive().
+//
+// Run with both extensions loaded:
+//   php -d extension=target/release/libelephant_json.so tests/merge.php
+//
+// Exits non-zero when any case differs.
+
+if (!function_exists('json_encode') || (new ReflectionFunction('json_encode'))->getExtensionName() !== 'json') {
+    fwrite(STDERR, "ext/json is required as the reference implementation\n");
+    exit(2);
+}
+
+$cases = [
+    [],
+    ['{}'],
+    ['[3, 1, 2]'],
+    ['{"a": 1}', '{"a": 2}'],
+    ['{"a": 1}', '{"b": 2}'],
+    ['{"a": null}', '{"a": 1}'],
+    ['{"a": 1}', '{"a": null}'],
+    ['{"a": [1, 2]}', '{"a": 3}'],
+    ['{"a": 1}', '{"a": [2, 3]}'],
+    ['{"a": {"b": 1}}', '{"a": {"b": 2, "c": 3}}'],
+    ['{"a": {"b": {"c": 1}}}', '{"a": {"b": {"c": [2]}}}', '{"a": {"b": {"c": {"d": 3}}}}'],
+    ['[1, 2]', '[3]', '{"x": 4}'],
+    ['{"5": "a", "x": "b"}', '{"5": "c"}'],
+    ['{"list": {"3": "a"}}', '{"list": ["b"]}'],
+    ['{"a": {}}', '{"a": {"b": 1}}'],
+    ['{"a": "x"}', '{"a": {}}'],
+    ['{"a": true}', '{"a": false}', '{"a": 1.5}'],
+    ['{"db": {"host": "localhost", "port": 5432, "options": ["ssl"]}}', '{"db": {"host": "db.internal", "options": ["pool"]}}'],
+];
+
+$failures = 0;
+foreach ($cases as $documents) {
+    $arrays = array_map(fn (string $json) => json_decode($json, true), $documents);
+    $expected = json_encode(array_merge_recursive(...$arrays));
+    try {
+        $actual = Json::mergeRecursive($documents, Json::COMPAT);
+    } catch (Throwable $e) {
+        $actual = get_class($e) . ': ' . $e->getMessage();
+    }
+    if ($expected !== $actual) {
+        $failures++;
+        printf("MISMATCH mergeRecursive(%s)\n  native:   %s\n  elephant: %s\n", var_export($documents, true), $expected, $actual);
+    }
+}
+
+printf("%d mismatches\n", $failures);
+exit($failures === 0 ? 0 : 1);
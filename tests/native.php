@@ -0,0 +1,68 @@
+<?php
+// Checks the replacement json_decode() and json_validate() against output
+// recorded from ext/json, for PHP builds that do not have it.
+//
+// Run with the replacement registered:
+//   php -d extension=target/release/libelephant_json.so -d elephant_json.replace_native=1 tests/native.php
+//
+// Exits non-zero when any case differs.
+
+if (!function_exists('json_decode') || (new ReflectionFunction('json_decode'))->getExtensionName() === 'json') {
+    fwrite(STDERR, "the replacement functions are only registered when ext/json is not loaded\n");
+    exit(2);
+}
+
+// Each case is a call and what ext/json returned for it, along with
+// json_last_error() afterwards.
+$cases = [
+    'decode valid' => [fn () => json_decode('"ok"'), ['ok', JSON_ERROR_NONE]],
+    'decode syntax error' => [fn () => json_decode('[1,]'), [null, JSON_ERROR_SYNTAX]],
+    'decode byte order mark' => [fn () => json_decode("\xEF\xBB\xBF1"), [null, JSON_ERROR_SYNTAX]],
+    'decode invalid UTF-8' => [fn () => json_decode("\"a\xFFb\""), [null, JSON_ERROR_UTF8]],
+    'decode ignore' => [fn () => json_decode("\"a\xFFb\"", false, 512, JSON_INVALID_UTF8_IGNORE), ['ab', JSON_ERROR_NONE]],
+    'decode substitute' => [
+        fn () => json_decode("\"a\xFFb\"", false, 512, JSON_INVALID_UTF8_SUBSTITUTE),
+        ["a\u{FFFD}b", JSON_ERROR_NONE],
+    ],
+    'decode substitute wins' => [
+        fn () => json_decode("\"a\xFFb\"", false, 512, JSON_INVALID_UTF8_IGNORE | JSON_INVALID_UTF8_SUBSTITUTE),
+        ["a\u{FFFD}b", JSON_ERROR_NONE],
+    ],
+    'decode substitute per byte' => [
+        fn () => json_decode("\"\xE2\x82\"", false, 512, JSON_INVALID_UTF8_SUBSTITUTE),
+        ["\u{FFFD}\u{FFFD}", JSON_ERROR_NONE],
+    ],
+    'decode ignore in key' => [
+        fn () => json_decode("{\"k\xFF\":1}", true, 512, JSON_INVALID_UTF8_IGNORE),
+        [['k' => 1], JSON_ERROR_NONE],
+    ],
+    'decode invalid UTF-8 outside strings' => [
+        fn () => json_decode("[1]\xFF", true, 512, JSON_INVALID_UTF8_IGNORE),
+        [null, JSON_ERROR_UTF8],
+    ],
+    'decode throw' => [fn () => json_decode("\"\xFF\"", false, 512, JSON_THROW_ON_ERROR), ['JsonException 5', JSON_ERROR_NONE]],
+    'validate valid' => [fn () => json_validate('{"a":[1]}'), [true, JSON_ERROR_NONE]],
+    'validate empty' => [fn () => json_validate(''), [false, JSON_ERROR_SYNTAX]],
+    'validate depth' => [fn () => json_validate('[[1]]', 1), [false, JSON_ERROR_DEPTH]],
+    'validate invalid UTF-8' => [fn () => json_validate("\"a\xFFb\""), [false, JSON_ERROR_UTF8]],
+    'validate ignore' => [fn () => json_validate("\"a\xFFb\"", 512, JSON_INVALID_UTF8_IGNORE), [true, JSON_ERROR_NONE]],
+    'validate other flags' => [fn () => json_validate('1', 512, JSON_INVALID_UTF8_SUBSTITUTE), ['ValueError 0', JSON_ERROR_NONE]],
+];
+
+$failures = 0;
+foreach ($cases as $name => [$case, $expected]) {
+    json_decode('null');
+    try {
+        $result = $case();
+    } catch (Throwable $e) {
+        $result = get_class($e) . ' ' . $e->getCode();
+    }
+    $actual = [$result, json_last_error()];
+    if ($actual !== $expected) {
+        $failures++;
+        printf("MISMATCH %s\n  ext/json: %s\n  elephant: %s\n", $name, var_export($expected, true), var_export($actual, true));
+    }
+}
+
+printf("%d mismatches\n", $failures);
+exit($failures === 0 ? 0 : 1);
@@ -0,0 +1,52 @@
+<?php
+// Checks Json::repair() on the kinds of damage it is meant for: unquoted
+// keys, single quotes, trailing commas, missing closing brackets, missing
+// values and concatenated log lines.
+//
+// Run with the extension loaded:
+//   php -d extension=target/release/libelephant_json.so tests/repair.php
+//
+// Exits non-zero when any case differs.
+
+$cases = [
+    // Unquoted keys and single quotes.
+    "{name: 'elephant'}" => '{"name": "elephant"}',
+    "{'a': 'it\\'s'}" => '{"a": "it\'s"}',
+    // Trailing commas.
+    '[1, 2,]' => '[1, 2]',
+    '{"a":1,}' => '{"a":1}',
+    // Missing closing brackets, including truncation right after a key or
+    // an opening bracket.
+    '[1, 2' => '[1, 2]',
+    '{"a":' => '{"a":null}',
+    '[{"a":' => '[{"a":null}]',
+    '{"a":[' => '{"a":[]}',
+    '{"a": {"b": [1, {"c": ' => '{"a": {"b": [1, {"c": null}]}}',
+    '{"a": "unterminated' => '{"a": "unterminated"}',
+    // Missing values and commas.
+    '{a:1, b:}' => '{"a":1, "b":null}',
+    '{"a":1 "b":2}' => '{"a":1 ,"b":2}',
+    // Concatenated log lines.
+    "{\"a\":1}\n{\"b\":2}" => "[{\"a\":1}\n,{\"b\":2}]",
+    // Comments and other languages' literals.
+    "// config\n{debug: True, level: None,}" => "\n{\"debug\": true, \"level\": null}",
+];
+
+$failures = 0;
+foreach ($cases as $broken => $expected) {
+    try {
+        $actual = Json::repair($broken);
+    } catch (Throwable $e) {
+        $actual = get_class($e) . ': ' . $e->getMessage();
+    }
+    if ($actual !== $expected) {
+        $failures++;
+        printf("MISMATCH repair(%s)\n  expected: %s\n  actual:   %s\n", var_export($broken, true), $expected, $actual);
+    } elseif (!Json::validate($actual)) {
+        $failures++;
+        printf("INVALID repair(%s): %s\n", var_export($broken, true), $actual);
+    }
+}
+
+printf("%d mismatches\n", $failures);
+exit($failures === 0 ? 0 : 1);
@@ -0,0 +1,75 @@
+<?php
+// Checks stream writes: short writes are retried until everything is
+// written, and exceptions a PSR-7 stream throws reach the caller unchanged.
+//
+// Run with the extension loaded:
+//   php -d extension=target/release/libelephant_json.so tests/stream.php
+//
+// Exits non-zero when any case fails.
+
+// Duck-typed like the PSR-7 streams the extension accepts; takes at most
+// `$limit` bytes per write().
+class ShortStream
+{
+    public string $contents = '';
+
+    public function __construct(private int $limit, private ?Throwable $error = null) {}
+
+    public function write(string $data): int
+    {
+        if ($this->error !== null) {
+            throw $this->error;
+        }
+        $this->contents .= substr($data, 0, $this->limit);
+        return min(strlen($data), $this->limit);
+    }
+}
+
+$value = ['name' => "caf\u{e9}", 'list' => range(1, 50)];
+$json = Json::encode($value);
+
+$cases = [
+    'short writes to a PSR-7 stream' => function () use ($value, $json) {
+        $stream = new ShortStream(3);
+        return Json::encodeStream($stream, $value) === strlen($json) && $stream->contents === $json;
+    },
+    'resource' => function () use ($value, $json) {
+        $stream = fopen('php://memory', 'w+b');
+        Json::encodeStream($stream, $value);
+        rewind($stream);
+        return stream_get_contents($stream) === $json;
+    },
+    'exception from write()' => function () use ($value) {
+        $error = new RuntimeException('disk full', 28);
+        try {
+            Json::encodeStream(new ShortStream(3, $error), $value);
+        } catch (Throwable $e) {
+            return $e === $error;
+        }
+        return 'no exception';
+    },
+    'not a stream' => function () use ($value) {
+        try {
+            Json::encodeStream(new stdClass(), $value);
+        } catch (TypeError $e) {
+            return true;
+        }
+        return 'no TypeError';
+    },
+];
+
+$failures = 0;
+foreach ($cases as $name => $case) {
+    try {
+        $result = $case();
+    } catch (Throwable $e) {
+        $result = get_class($e) . ': ' . $e->getMessage();
+    }
+    if ($result !== true) {
+        $failures++;
+        printf("FAIL %s\n  %s\n", $name, var_export($result, true));
+    }
+}
+
+printf("%d failures\n", $failures);
+exit($failures === 0 ? 0 : 1);
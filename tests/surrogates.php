@@ -0,0 +1,49 @@
+<?php
+// Checks each loneSurrogates() policy. "pass" keeps the six characters of
+// the escape as written, not WTF-8 bytes.
+//
+// Run with the extension loaded:
+//   php -d extension=target/release/libelephant_json.so tests/surrogates.php
+//
+// Exits non-zero when any case fails.
+
+use Elephant\Json\DecoderOptions;
+
+$decode = function (string $json, ?string $policy) {
+    $options = DecoderOptions::new();
+    if ($policy !== null) {
+        $options->loneSurrogates($policy);
+    }
+    try {
+        return Json::decode($json, $options);
+    } catch (Exception $e) {
+        return 'error ' . $e->getCode();
+    }
+};
+
+$cases = [
+    'reject by default' => [$decode('"\uD800"', null), 'error ' . JSON_ERROR_UTF16],
+    'reject' => [$decode('"a\uDC00"', 'reject'), 'error ' . JSON_ERROR_UTF16],
+    'replace' => [$decode('"a\uD800b"', 'replace'), "a\u{FFFD}b"],
+    'pass high' => [$decode('"\uD800"', 'pass'), '\uD800'],
+    'pass low' => [$decode('"a\uDC00b"', 'pass'), 'a\uDC00b'],
+    'pass keeps case' => [$decode('"\ud800A"', 'pass'), '\ud800A'],
+    'pass two highs' => [$decode('"\uD800\uD800"', 'pass'), '\uD800\uD800'],
+    'pass is valid UTF-8' => [preg_match('//u', $decode('"\uD800"', 'pass')), 1],
+    'pass re-encodes the backslash' => [Json::encode($decode('"\uD800"', 'pass')), '"\\\\uD800"'],
+    'pairs decode under every policy' => [
+        [$decode('"\uD83D\uDE00"', 'reject'), $decode('"\uD83D\uDE00"', 'replace'), $decode('"\uD83D\uDE00"', 'pass')],
+        ["\u{1F600}", "\u{1F600}", "\u{1F600}"],
+    ],
+];
+
+$failures = 0;
+foreach ($cases as $name => [$actual, $expected]) {
+    if ($actual !== $expected) {
+        $failures++;
+        printf("FAIL %s\n  expected: %s\n  actual:   %s\n", $name, var_export($expected, true), var_export($actual, true));
+    }
+}
+
+printf("%d failures\n", $failures);
+exit($failures === 0 ? 0 : 1);
@@ -0,0 +1,50 @@
+<?php
+// Checks that Template::encode() produces what Json::encode() does for the
+// same rows, options and replacer.
+//
+// Run with the extension loaded:
+//   php -d extension=target/release/libelephant_json.so tests/template.php
+//
+// Exits non-zero when any case differs.
+
+use Elephant\Json\{EncoderOptions, Skip, Template};
+
+$rows = [
+    ['id' => 1, 'name' => 'Lamp', 'price' => '19.90', 'note' => null],
+    ['id' => 2, 'name' => "Caf\u{e9} <table>", 'price' => '120.00', 'note' => 'oak'],
+    ['id' => 3, 'name' => 'Rug', 'note' => null, 'price' => '49.50'],
+    ['id' => 4, 'name' => 'Shelf'],
+];
+$template = Template::forKeys(['id', 'name', 'price', 'note']);
+
+$options = [
+    'none' => null,
+    'flags' => JSON_PRETTY_PRINT | JSON_HEX_TAG | JSON_UNESCAPED_UNICODE,
+    'compat' => Json::COMPAT | JSON_FORCE_OBJECT,
+    'sortKeys skipNulls' => EncoderOptions::new()->sortKeys()->skipNulls(),
+];
+
+$replacers = [
+    'none' => null,
+    'uppercase names' => fn (string|int $key, mixed $value) => $key === 'name' ? strtoupper($value) : $value,
+    'skip notes' => fn (string|int $key, mixed $value) => $key === 'note' ? new Skip() : $value,
+    'replace rows' => fn (string|int $key, mixed $value, string $pointer) =>
+        preg_match('~^/\d+$~', $pointer) ? ['row' => $pointer] : $value,
+];
+
+$failures = 0;
+foreach ($options as $optionName => $option) {
+    foreach ($replacers as $replacerName => $replacer) {
+        foreach (['rows' => $rows, 'empty' => [], 'map' => ['a' => $rows[0]]] as $inputName => $input) {
+            $expected = Json::encode($input, $option, null, $replacer);
+            $actual = $template->encode($input, $option, null, $replacer);
+            if ($expected !== $actual) {
+                $failures++;
+                printf("MISMATCH %s / %s / %s\n  encode:   %s\n  template: %s\n", $optionName, $replacerName, $inputName, $expected, $actual);
+            }
+        }
+    }
+}
+
+printf("%d mismatches\n", $failures);
+exit($failures === 0 ? 0 : 1);
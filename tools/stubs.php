@@ -0,0 +1,108 @@
+<?php
+// Emits a .stub.php for IDEs and static analyzers from the loaded
+// extension's reflection data:
+//
+//   php -d extension=target/release/libelephant_json.so tools/stubs.php > elephant_json.stub.php
+
+const EXTENSION = 'elephant_json';
+
+// Parameters whose arginfo can only say `mixed` because they accept a union.
+const NARROWED = [
+    'Elephant\Json\Codec::decode' => ['as_array' => '\Elephant\Json\DecoderOptions|bool|null'],
+    'Elephant\Json\Codec::encode' => ['options' => '\Elephant\Json\EncoderOptions|int|null'],
+    'Elephant\Json\Codec::escapeString' => ['flags' => '\Elephant\Json\EncoderOptions|int|null'],
+    'Elephant\Json\Codec::setEncodeDefaults' => ['options' => '\Elephant\Json\EncoderOptions|int|null'],
+    'Elephant\Json\Codec::setDecodeDefaults' => ['options' => '\Elephant\Json\DecoderOptions|int|null'],
+    'Elephant\Json\encode' => ['options' => '\Elephant\Json\EncoderOptions|int|null'],
+    'Elephant\Json\decode' => ['options' => '\Elephant\Json\DecoderOptions|bool|null'],
+    'json_encode' => ['flags' => '\Elephant\Json\EncoderOptions|int|null'],
+];
+
+function type_string(?ReflectionType $type): string
+{
+    if ($type === null) {
+        return 'mixed';
+    }
+    if ($type instanceof ReflectionNamedType) {
+        $name = $type->isBuiltin() ? $type->getName() : '\\' . $type->getName();
+        $nullable = $type->allowsNull() && !in_array($name, ['mixed', 'null'], true);
+        return ($nullable ? '?' : '') . $name;
+    }
+    return (string) $type;
+}
+
+function parameters(ReflectionFunctionAbstract $fn, string $key): string
+{
+    $narrowed = NARROWED[$key] ?? [];
+    $params = [];
+    foreach ($fn->getParameters() as $param) {
+        $code = ($narrowed[$param->getName()] ?? type_string($param->getType())) . ' $' . $param->getName();
+        if ($param->isDefaultValueAvailable()) {
+            $code .= ' = ' . strtolower(var_export($param->getDefaultValue(), true));
+        } elseif ($param->isOptional()) {
+            $code .= ' = null';
+        }
+        $params[] = $code;
+    }
+    return implode(', ', $params);
+}
+
+function short_name(string $name): string
+{
+    $pos = strrpos($name, '\\');
+    return $pos === false ? $name : substr($name, $pos + 1);
+}
+
+function namespace_of(string $name): string
+{
+    $pos = strrpos($name, '\\');
+    return $pos === false ? '' : substr($name, 0, $pos);
+}
+
+$extension = new ReflectionExtension(EXTENSION);
+$blocks = [];
+
+foreach ($extension->getClasses() as $class) {
+    $code = 'class ' . $class->getShortName();
+    if ($parent = $class->getParentClass()) {
+        $code .= ' extends \\' . $parent->getName();
+    }
+    $code .= "\n{\n";
+
+    foreach ($class->getReflectionConstants() as $constant) {
+        if ($constant->getDeclaringClass()->getName() === $class->getName()) {
+            $code .= sprintf("    public const %s = %s;\n", $constant->getName(), var_export($constant->getValue(), true));
+        }
+    }
+
+    foreach ($class->getMethods() as $method) {
+        if ($method->getDeclaringClass()->getName() !== $class->getName()) {
+            continue;
+        }
+        $code .= sprintf(
+            "\n    public %sfunction %s(%s)%s {}\n",
+            $method->isStatic() ? 'static ' : '',
+            $method->getName(),
+            parameters($method, $class->getName() . '::' . $method->getName()),
+            $method->getName() === '__construct' ? '' : ': ' . type_string($method->getReturnType()),
+        );
+    }
+
+    $blocks[namespace_of($class->getName())][] = $code . '}';
+}
+
+foreach ($extension->getFunctions() as $function) {
+    $blocks[namespace_of($function->getName())][] = sprintf(
+        "function %s(%s): %s {}",
+        short_name($function->getName()),
+        parameters($function, $function->getName()),
+        type_string($function->getReturnType()),
+    );
+}
+
+echo "<?php\n\n// Generated by tools/stubs.php for " . EXTENSION . ' ' . $extension->getVersion() . ". Do not edit.\n";
+ksort($blocks);
+foreach ($blocks as $namespace => $items) {
+    echo "\nnamespace " . ($namespace === '' ? '' : $namespace . ' ') . "{\n\n";
+    echo implode("\n\n", $items), "\n\n}\n";
+}